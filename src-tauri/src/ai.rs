@@ -0,0 +1,1087 @@
+//! AI module - Chat provider abstraction shared by the main window and the overlay
+//! Both `ask_ai` and the overlay chat go through `ask`, so they always use the
+//! same model, system prompt, conversation history and error handling
+//! Any OpenAI-compatible endpoint works (Groq, Ollama, llama.cpp, LM Studio, vLLM)
+
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+// Time to open a TCP/TLS connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+// Time until the response headers (or a non-streamed body) arrive
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+// Longest gap allowed between two chunks of a streamed reply
+const STREAM_IDLE_TIMEOUT: Duration = Duration::from_secs(20);
+
+// Retry policy for transient failures (network, timeout, 429, 5xx)
+const MAX_ATTEMPTS: u32 = 3;
+const BACKOFF_BASE_MS: u64 = 500;
+const BACKOFF_MAX_MS: u64 = 8000;
+// Give up instead of waiting longer than this for a rate limit to reset
+const MAX_RETRY_AFTER_SECS: u64 = 20;
+
+// OpenAI-compatible chat completion request/response structures
+#[derive(Serialize)]
+struct CompletionRequest {
+    model: String,
+    messages: Vec<CompletionMessage>,
+    max_tokens: u32,
+    temperature: f32,
+    stream: bool,
+}
+
+#[derive(Serialize)]
+struct CompletionMessage {
+    role: String,
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct CompletionResponse {
+    choices: Option<Vec<CompletionChoice>>,
+    error: Option<CompletionError>,
+}
+
+#[derive(Deserialize)]
+struct CompletionChoice {
+    message: CompletionMessageResponse,
+}
+
+#[derive(Deserialize)]
+struct CompletionMessageResponse {
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct CompletionError {
+    message: String,
+    /// e.g. "rate_limit_exceeded" or "insufficient_quota"; some servers send a number
+    #[serde(default)]
+    code: Option<serde_json::Value>,
+    #[serde(default, rename = "type")]
+    kind: Option<String>,
+}
+
+impl CompletionError {
+    fn mentions(&self, needle: &str) -> bool {
+        let code = self.code.as_ref().map(|c| c.to_string()).unwrap_or_default();
+        code.contains(needle) || self.kind.as_deref().unwrap_or("").contains(needle)
+    }
+}
+
+/// Everything that can go wrong between a question and the cat's answer
+#[derive(Clone, Debug, PartialEq)]
+pub enum AiError {
+    /// The provider needs a key and none is configured
+    NoApiKey { provider: String },
+    /// Connection refused, DNS failure, dropped stream...
+    Network(String),
+    Timeout,
+    /// 401/403 - the key is wrong or revoked
+    Unauthorized { provider: String },
+    /// 429 - too many requests, worth retrying later
+    RateLimited { retry_after_secs: Option<u64> },
+    /// Out of credits - retrying won't help
+    QuotaExceeded(String),
+    /// The body wasn't what an OpenAI-compatible server sends
+    MalformedResponse(String),
+    /// The server answered but with no choices or no text
+    EmptyResponse { provider: String },
+    /// Any other error the provider reported, with its HTTP status if known
+    Provider { status: Option<u16>, message: String },
+    /// The user cancelled the request
+    Cancelled,
+}
+
+impl AiError {
+    /// Stable identifier the frontend can switch on
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AiError::NoApiKey { .. } => "noApiKey",
+            AiError::Network(_) => "network",
+            AiError::Timeout => "timeout",
+            AiError::Unauthorized { .. } => "unauthorized",
+            AiError::RateLimited { .. } => "rateLimited",
+            AiError::QuotaExceeded(_) => "quotaExceeded",
+            AiError::MalformedResponse(_) => "malformedResponse",
+            AiError::EmptyResponse { .. } => "emptyResponse",
+            AiError::Provider { .. } => "provider",
+            AiError::Cancelled => "cancelled",
+        }
+    }
+
+    /// Short, actionable message in the cat's voice for the overlay
+    pub fn user_message(&self) -> String {
+        match self {
+            AiError::NoApiKey { .. } => "Meow! Please set an API key in the main window first!".to_string(),
+            AiError::Network(_) => "Mrrp... I can't reach the internet. Check your connection?".to_string(),
+            AiError::Timeout => "Meow... that took too long. Try again?".to_string(),
+            AiError::Unauthorized { .. } => "Hiss! Your API key didn't work. Check it in the main window!".to_string(),
+            AiError::RateLimited { retry_after_secs: Some(secs) } => {
+                format!("Mrrp, too many questions! Try again in {} seconds *purr*", secs)
+            }
+            AiError::RateLimited { retry_after_secs: None } => {
+                "Mrrp, too many questions! Try again in a moment *purr*".to_string()
+            }
+            AiError::QuotaExceeded(_) => "Meow... your API quota is used up. Check your plan!".to_string(),
+            AiError::MalformedResponse(_) | AiError::EmptyResponse { .. } => {
+                "Meow? I got a confusing answer. Try again?".to_string()
+            }
+            AiError::Provider { .. } => "Meow... something went wrong!".to_string(),
+            AiError::Cancelled => "Okay, never mind! *purr*".to_string(),
+        }
+    }
+
+    /// Whether the same request might succeed if sent again
+    pub fn is_transient(&self) -> bool {
+        match self {
+            AiError::Network(_) | AiError::Timeout | AiError::RateLimited { .. } => true,
+            AiError::Provider { status: Some(status), .. } => *status >= 500,
+            _ => false,
+        }
+    }
+
+    fn from_reqwest(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            AiError::Timeout
+        } else if e.is_decode() {
+            AiError::MalformedResponse(e.to_string())
+        } else {
+            AiError::Network(e.to_string())
+        }
+    }
+
+    /// Classify a failed response from its status, headers and error body
+    fn from_response(
+        provider: &str,
+        status: reqwest::StatusCode,
+        headers: &reqwest::header::HeaderMap,
+        error: Option<CompletionError>,
+    ) -> Self {
+        let message = error
+            .as_ref()
+            .map(|e| e.message.clone())
+            .unwrap_or_else(|| format!("{} returned {}", provider, status));
+
+        if status.as_u16() == 402 || error.as_ref().is_some_and(|e| e.mentions("insufficient_quota")) {
+            return AiError::QuotaExceeded(message);
+        }
+        match status.as_u16() {
+            401 | 403 => AiError::Unauthorized { provider: provider.to_string() },
+            429 => AiError::RateLimited { retry_after_secs: retry_after_secs(headers) },
+            _ => AiError::Provider {
+                status: (!status.is_success()).then_some(status.as_u16()),
+                message,
+            },
+        }
+    }
+}
+
+impl fmt::Display for AiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AiError::NoApiKey { provider } => write!(f, "No API key set for {}", provider),
+            AiError::Network(e) => write!(f, "Request failed: {}", e),
+            AiError::Timeout => write!(f, "Request timed out"),
+            AiError::Unauthorized { provider } => write!(f, "{} rejected the API key", provider),
+            AiError::RateLimited { retry_after_secs: Some(secs) } => write!(f, "Rate limited, retry in {}s", secs),
+            AiError::RateLimited { retry_after_secs: None } => write!(f, "Rate limited"),
+            AiError::QuotaExceeded(e) => write!(f, "Quota exceeded: {}", e),
+            AiError::MalformedResponse(e) => write!(f, "Failed to parse response: {}", e),
+            AiError::EmptyResponse { provider } => write!(f, "No response from {}", provider),
+            AiError::Provider { status: Some(status), message } => write!(f, "{} ({})", message, status),
+            AiError::Provider { status: None, message } => write!(f, "{}", message),
+            AiError::Cancelled => write!(f, "Request cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for AiError {}
+
+/// Sent to the frontend as `{ kind, message, detail, status?, retryAfterSecs? }`
+impl Serialize for AiError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("AiError", 5)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.user_message())?;
+        state.serialize_field("detail", &self.to_string())?;
+        match self {
+            AiError::Provider { status: Some(status), .. } => state.serialize_field("status", status)?,
+            AiError::RateLimited { retry_after_secs: Some(secs) } => state.serialize_field("retryAfterSecs", secs)?,
+            _ => {}
+        }
+        state.end()
+    }
+}
+
+/// Seconds to wait from `Retry-After` (seconds form) or Groq's `x-ratelimit-reset-*` headers
+fn retry_after_secs(headers: &reqwest::header::HeaderMap) -> Option<u64> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    let retry_after = header("retry-after").and_then(|v| v.trim().parse::<f64>().ok());
+    if let Some(secs) = retry_after.filter(|secs| secs.is_finite() && *secs >= 0.0) {
+        return Some(secs.ceil() as u64);
+    }
+    ["x-ratelimit-reset-requests", "x-ratelimit-reset-tokens"]
+        .iter()
+        .filter_map(|name| header(name).and_then(parse_reset_duration))
+        .max()
+}
+
+/// Parse Groq-style durations such as "2m59.56s", "7.66s" or "120ms" into whole seconds
+fn parse_reset_duration(value: &str) -> Option<u64> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    let mut total = 0.0;
+    let mut number = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() || c == '.' {
+            number.push(c);
+            continue;
+        }
+        let amount: f64 = number.parse().ok()?;
+        number.clear();
+        total += match c {
+            'h' => amount * 3600.0,
+            'm' if chars.peek() == Some(&'s') => {
+                chars.next();
+                amount / 1000.0
+            }
+            'm' => amount * 60.0,
+            's' => amount,
+            _ => return None,
+        };
+    }
+    if !number.is_empty() {
+        return None;
+    }
+    Some(total.ceil() as u64)
+}
+
+// Streaming (`stream: true`) chunk structures
+#[derive(Deserialize)]
+struct StreamChunk {
+    choices: Option<Vec<StreamChoice>>,
+    error: Option<CompletionError>,
+}
+
+#[derive(Deserialize)]
+struct StreamChoice {
+    #[serde(default)]
+    delta: StreamDelta,
+}
+
+#[derive(Deserialize, Default)]
+struct StreamDelta {
+    content: Option<String>,
+}
+
+/// Incremental decoder for `text/event-stream` bodies
+#[derive(Default)]
+struct SseDecoder {
+    buffer: Vec<u8>,
+    data: String,
+}
+
+impl SseDecoder {
+    /// Feed raw bytes, returning the data of every event they complete
+    fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(bytes);
+
+        let mut events = Vec::new();
+        // Only decode whole lines so multi-byte characters split across chunks survive
+        while let Some(pos) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if line.is_empty() {
+                // Blank line dispatches the event
+                if !self.data.is_empty() {
+                    events.push(std::mem::take(&mut self.data));
+                }
+            } else if let Some(value) = line.strip_prefix("data:") {
+                if !self.data.is_empty() {
+                    self.data.push('\n');
+                }
+                self.data.push_str(value.strip_prefix(' ').unwrap_or(value));
+            }
+            // Comments and other fields (event, id, retry) are ignored
+        }
+        events
+    }
+
+    /// Flush an event left unterminated when the stream closed
+    fn finish(&mut self) -> Option<String> {
+        self.push(b"\n\n").pop()
+    }
+}
+
+/// A single chat message in provider-neutral form
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn system(content: &str) -> Self {
+        Self { role: "system".to_string(), content: content.to_string() }
+    }
+
+    pub fn user(content: &str) -> Self {
+        Self { role: "user".to_string(), content: content.to_string() }
+    }
+
+    pub fn assistant(content: &str) -> Self {
+        Self { role: "assistant".to_string(), content: content.to_string() }
+    }
+}
+
+/// Everything a provider needs to produce one completion
+#[derive(Clone, Debug)]
+pub struct ChatRequest {
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    pub max_tokens: u32,
+    pub temperature: f32,
+}
+
+pub type ChatFuture<'a> = Pin<Box<dyn Future<Output = Result<String, AiError>> + Send + 'a>>;
+
+pub type CheckFuture<'a> = Pin<Box<dyn Future<Output = Result<(), AiError>> + Send + 'a>>;
+
+/// Callback receiving each piece of the reply as it streams in
+pub type TokenSink<'a> = Box<dyn FnMut(&str) + Send + 'a>;
+
+/// A backend that can turn a chat request into the assistant's reply
+pub trait ChatProvider: Send + Sync {
+    /// Human readable provider name, used in logs and error messages
+    fn name(&self) -> &str;
+
+    /// Model used when the caller doesn't ask for a specific one
+    fn model(&self) -> &str;
+
+    /// Whether requests without an API key should be rejected up front
+    fn requires_api_key(&self) -> bool;
+
+    /// Send the request, passing tokens to `on_token` as they arrive, and
+    /// resolve to the full reply text
+    fn stream<'a>(
+        &'a self,
+        api_key: String,
+        request: ChatRequest,
+        on_token: TokenSink<'a>,
+    ) -> ChatFuture<'a>;
+
+    /// Check that the endpoint is reachable and accepts the key
+    fn check_api_key(&self, api_key: String) -> CheckFuture<'_>;
+}
+
+const GROQ_BASE_URL: &str = "https://api.groq.com/openai/v1";
+const GROQ_MODEL: &str = "meta-llama/llama-4-maverick-17b-128e-instruct";
+
+/// Where and how to reach an OpenAI-compatible chat completion endpoint
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderConfig {
+    pub name: String,
+    /// Base URL up to and including the API version, e.g. `http://localhost:11434/v1`
+    pub base_url: String,
+    pub model: String,
+    /// Local servers (Ollama, llama.cpp) usually run without a key
+    #[serde(default)]
+    pub requires_api_key: bool,
+}
+
+impl ProviderConfig {
+    /// Hosted Groq, the default provider
+    pub fn groq() -> Self {
+        Self {
+            name: "Groq".to_string(),
+            base_url: GROQ_BASE_URL.to_string(),
+            model: GROQ_MODEL.to_string(),
+            requires_api_key: true,
+        }
+    }
+
+    /// Apply `CATPANION_BASE_URL` / `CATPANION_MODEL` on top of the saved config
+    pub fn with_env_overrides(mut self) -> Self {
+        if let Ok(base_url) = std::env::var("CATPANION_BASE_URL") {
+            if !base_url.trim().is_empty() {
+                self.name = "OpenAI-compatible".to_string();
+                self.base_url = base_url.trim().to_string();
+                self.requires_api_key = false;
+            }
+        }
+        if let Ok(model) = std::env::var("CATPANION_MODEL") {
+            if !model.trim().is_empty() {
+                self.model = model.trim().to_string();
+            }
+        }
+        self
+    }
+
+    /// Reject configs that can't possibly reach an endpoint
+    pub fn validate(&self) -> Result<(), String> {
+        if self.base_url.trim().is_empty() {
+            return Err("Base URL must not be empty".to_string());
+        }
+        if !self.base_url.starts_with("http://") && !self.base_url.starts_with("https://") {
+            return Err(format!("Base URL must start with http:// or https://: {}", self.base_url));
+        }
+        if self.model.trim().is_empty() {
+            return Err("Model must not be empty".to_string());
+        }
+        Ok(())
+    }
+
+    fn chat_completions_url(&self) -> String {
+        format!("{}/chat/completions", self.base_url.trim_end_matches('/'))
+    }
+
+    fn models_url(&self) -> String {
+        format!("{}/models", self.base_url.trim_end_matches('/'))
+    }
+}
+
+/// HTTP client shared by every request, so connections are pooled and reused
+fn http_client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .build()
+            .expect("failed to build HTTP client")
+    })
+}
+
+/// Lets a request be aborted from another thread
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    inner: Arc<CancelState>,
+}
+
+#[derive(Default)]
+struct CancelState {
+    cancelled: AtomicBool,
+    notify: tokio::sync::Notify,
+}
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        self.inner.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Resolve once `cancel` has been called
+    pub async fn cancelled(&self) {
+        loop {
+            // Created before the check so a cancel in between isn't missed
+            let notified = self.inner.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Provider for any server speaking the OpenAI chat completion API
+pub struct OpenAiCompatibleProvider {
+    config: ProviderConfig,
+}
+
+impl OpenAiCompatibleProvider {
+    pub fn new(config: ProviderConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl ChatProvider for OpenAiCompatibleProvider {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn model(&self) -> &str {
+        &self.config.model
+    }
+
+    fn requires_api_key(&self) -> bool {
+        self.config.requires_api_key
+    }
+
+    fn stream<'a>(
+        &'a self,
+        api_key: String,
+        request: ChatRequest,
+        mut on_token: TokenSink<'a>,
+    ) -> ChatFuture<'a> {
+        Box::pin(async move {
+            let request_body = CompletionRequest {
+                model: request.model,
+                messages: request
+                    .messages
+                    .into_iter()
+                    .map(|m| CompletionMessage { role: m.role, content: m.content })
+                    .collect(),
+                max_tokens: request.max_tokens,
+                temperature: request.temperature,
+                stream: true,
+            };
+
+            let mut builder = http_client()
+                .post(self.config.chat_completions_url())
+                .header("Content-Type", "application/json");
+            if !api_key.is_empty() {
+                builder = builder.header("Authorization", format!("Bearer {}", api_key));
+            }
+
+            let mut response = tokio::time::timeout(RESPONSE_TIMEOUT, builder.json(&request_body).send())
+                .await
+                .map_err(|_| AiError::Timeout)?
+                .map_err(AiError::from_reqwest)?;
+
+            let status = response.status();
+            if !status.is_success() {
+                let headers = response.headers().clone();
+                let body = response.text().await.map_err(AiError::from_reqwest)?;
+                let error = serde_json::from_str::<CompletionResponse>(&body).ok().and_then(|r| r.error);
+                return Err(AiError::from_response(self.name(), status, &headers, error));
+            }
+
+            let is_event_stream = response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.starts_with("text/event-stream"))
+                .unwrap_or(false);
+
+            if !is_event_stream {
+                // Servers that ignore `stream` answer with one JSON body
+                let headers = response.headers().clone();
+                let completion: CompletionResponse = response.json().await.map_err(AiError::from_reqwest)?;
+
+                if let Some(error) = completion.error {
+                    return Err(AiError::from_response(self.name(), status, &headers, Some(error)));
+                }
+                if let Some(choices) = completion.choices {
+                    if let Some(choice) = choices.first() {
+                        if !choice.message.content.is_empty() {
+                            on_token(&choice.message.content);
+                            return Ok(choice.message.content.clone());
+                        }
+                    }
+                }
+                return Err(AiError::EmptyResponse { provider: self.name().to_string() });
+            }
+
+            let mut decoder = SseDecoder::default();
+            let mut text = String::new();
+            let mut done = false;
+
+            while !done {
+                let chunk = tokio::time::timeout(STREAM_IDLE_TIMEOUT, response.chunk())
+                    .await
+                    .map_err(|_| AiError::Timeout)?
+                    .map_err(AiError::from_reqwest)?;
+
+                let events = match chunk {
+                    Some(bytes) => decoder.push(&bytes),
+                    None => {
+                        done = true;
+                        decoder.finish().into_iter().collect()
+                    }
+                };
+
+                for data in events {
+                    if apply_stream_event(self.name(), &data, &mut text, &mut on_token)? {
+                        done = true;
+                        break;
+                    }
+                }
+            }
+
+            if text.is_empty() {
+                return Err(AiError::EmptyResponse { provider: self.name().to_string() });
+            }
+            Ok(text)
+        })
+    }
+
+    fn check_api_key(&self, api_key: String) -> CheckFuture<'_> {
+        Box::pin(async move {
+            // Listing models is cheap and needs the same auth as a completion
+            let mut builder = http_client().get(self.config.models_url()).timeout(RESPONSE_TIMEOUT);
+            if !api_key.is_empty() {
+                builder = builder.header("Authorization", format!("Bearer {}", api_key));
+            }
+
+            let response = builder.send().await.map_err(AiError::from_reqwest)?;
+
+            let status = response.status();
+            if status.is_success() {
+                return Ok(());
+            }
+            let headers = response.headers().clone();
+            let error = response
+                .json::<CompletionResponse>()
+                .await
+                .ok()
+                .and_then(|r| r.error);
+            Err(AiError::from_response(self.name(), status, &headers, error))
+        })
+    }
+}
+
+/// Apply one SSE data payload to the reply so far, returning true on `[DONE]`
+fn apply_stream_event(
+    provider: &str,
+    data: &str,
+    text: &mut String,
+    on_token: &mut TokenSink<'_>,
+) -> Result<bool, AiError> {
+    if data.trim() == "[DONE]" {
+        return Ok(true);
+    }
+
+    let chunk: StreamChunk = serde_json::from_str(data)
+        .map_err(|e| AiError::MalformedResponse(format!("bad stream chunk: {}", e)))?;
+
+    if let Some(error) = chunk.error {
+        // Errors mid-stream arrive after a 200, so only the body tells us what happened
+        let status = if error.mentions("rate_limit") {
+            reqwest::StatusCode::TOO_MANY_REQUESTS
+        } else {
+            reqwest::StatusCode::OK
+        };
+        return Err(AiError::from_response(provider, status, &reqwest::header::HeaderMap::new(), Some(error)));
+    }
+
+    for choice in chunk.choices.unwrap_or_default() {
+        if let Some(content) = choice.delta.content {
+            if !content.is_empty() {
+                on_token(&content);
+                text.push_str(&content);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Payload of the `chat-token` event
+#[derive(Clone, Serialize)]
+pub struct ChatTokenEvent {
+    /// "main" for the main window, "overlay" for the cat's chat
+    pub source: String,
+    pub token: String,
+}
+
+/// Payload of the `chat-done` event
+#[derive(Clone, Serialize)]
+pub struct ChatDoneEvent {
+    pub source: String,
+    pub text: String,
+}
+
+/// Forward a streamed token to the main window
+pub fn emit_token(source: &str, token: &str) {
+    crate::events::emit(
+        "chat-token",
+        ChatTokenEvent { source: source.to_string(), token: token.to_string() },
+    );
+}
+
+/// Tell the main window a streamed reply has finished
+pub fn emit_done(source: &str, text: &str) {
+    crate::events::emit(
+        "chat-done",
+        ChatDoneEvent { source: source.to_string(), text: text.to_string() },
+    );
+}
+
+// Active provider, rebuilt from settings whenever they change
+static PROVIDER: Mutex<Option<Arc<dyn ChatProvider>>> = Mutex::new(None);
+
+/// Point the chat at a different OpenAI-compatible endpoint and save it
+pub fn set_provider_config(config: ProviderConfig) -> Result<(), String> {
+    config.validate()?;
+    crate::settings::update(|settings| settings.provider = config)?;
+    Ok(())
+}
+
+/// Get the config of the active provider
+pub fn provider_config() -> ProviderConfig {
+    crate::settings::current().provider.with_env_overrides()
+}
+
+/// Drop the active provider so the next request picks up new settings
+pub fn reload_provider() {
+    let config = provider_config();
+    log::info!("Using chat provider {} at {} ({})", config.name, config.base_url, config.model);
+    *PROVIDER.lock().unwrap() = None;
+}
+
+/// Get the active chat provider
+pub fn provider() -> Arc<dyn ChatProvider> {
+    let mut guard = PROVIDER.lock().unwrap();
+    guard
+        .get_or_insert_with(|| Arc::new(OpenAiCompatibleProvider::new(provider_config())))
+        .clone()
+}
+
+/// Answer with `provider` until the next `reload_provider`, e.g. a fake
+/// one in tests
+#[cfg(test)]
+pub fn set_provider(provider: Arc<dyn ChatProvider>) {
+    *PROVIDER.lock().unwrap() = Some(provider);
+}
+
+// In-flight requests by source ("main", "overlay"), so each can be cancelled
+static IN_FLIGHT: Mutex<Option<HashMap<String, CancelToken>>> = Mutex::new(None);
+
+/// Cancel the in-flight request from `source`, returning whether there was one
+pub fn cancel(source: &str) -> bool {
+    let token = IN_FLIGHT.lock().unwrap().as_mut().and_then(|map| map.remove(source));
+    match token {
+        Some(token) => {
+            log::info!("Cancelling {} chat request", source);
+            token.cancel();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Ask the cat a question as the active persona, streaming the reply into `on_token`
+/// Transient failures are retried with backoff until the first token arrives;
+/// a newer question from the same `source` cancels this one
+pub async fn ask(source: &str, question: String, mut on_token: TokenSink<'_>) -> Result<String, AiError> {
+    // Clone the Arc so the provider lock isn't held across the request
+    let provider = provider();
+    let api_key = crate::secrets::api_key();
+    if provider.requires_api_key() && api_key.is_empty() {
+        return Err(AiError::NoApiKey { provider: provider.name().to_string() });
+    }
+
+    let persona = crate::personas::active();
+    let system_prompt = crate::personas::render(&persona.system_prompt);
+    let request = ChatRequest {
+        model: persona.model.unwrap_or_else(|| provider.model().to_string()),
+        messages: crate::conversation::messages_for(&system_prompt, &question),
+        max_tokens: persona.max_tokens,
+        temperature: persona.temperature,
+    };
+
+    let token = CancelToken::default();
+    let previous = IN_FLIGHT
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(source.to_string(), token.clone());
+    if let Some(previous) = previous {
+        previous.cancel();
+    }
+
+    let mut attempt = 1;
+    let result = loop {
+        let mut streamed = false;
+        let sink: TokenSink<'_> = Box::new(|t: &str| {
+            streamed = true;
+            on_token(t);
+        });
+
+        let result = tokio::select! {
+            result = provider.stream(api_key.clone(), request.clone(), sink) => result,
+            _ = token.cancelled() => Err(AiError::Cancelled),
+        };
+
+        let error = match result {
+            Ok(answer) => break Ok(answer),
+            Err(e) => e,
+        };
+        // Retrying after tokens were shown would repeat them
+        if streamed || !error.is_transient() || attempt >= MAX_ATTEMPTS {
+            break Err(error);
+        }
+
+        let Some(delay) = retry_delay(&error, attempt) else {
+            break Err(error);
+        };
+        log::warn!(
+            "Chat request failed ({}), retrying in {:?} (attempt {}/{})",
+            error, delay, attempt + 1, MAX_ATTEMPTS
+        );
+
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = token.cancelled() => break Err(AiError::Cancelled),
+        }
+        attempt += 1;
+    };
+
+    // Only clear our own entry; a newer request may have replaced it
+    if let Some(map) = IN_FLIGHT.lock().unwrap().as_mut() {
+        if map.get(source).is_some_and(|t| Arc::ptr_eq(&t.inner, &token.inner)) {
+            map.remove(source);
+        }
+    }
+
+    let answer = result?;
+    crate::conversation::record(&question, &answer);
+    crate::history::append_turn(&question, &answer);
+    Ok(answer)
+}
+
+/// How long to wait before retrying after `error` on `attempt`: what a rate
+/// limit asks for, else backoff; `None` when the limit resets too far off
+fn retry_delay(error: &AiError, attempt: u32) -> Option<Duration> {
+    match error {
+        AiError::RateLimited { retry_after_secs: Some(secs) } if *secs > MAX_RETRY_AFTER_SECS => None,
+        AiError::RateLimited { retry_after_secs: Some(secs) } => Some(Duration::from_secs(*secs)),
+        _ => Some(backoff_delay(attempt)),
+    }
+}
+
+/// Exponential backoff with jitter: half the step fixed, half random
+fn backoff_delay(attempt: u32) -> Duration {
+    // Doubling stops well before the shift could overflow
+    let step = (BACKOFF_BASE_MS << (attempt.max(1) - 1).min(16)).min(BACKOFF_MAX_MS);
+    // Good enough randomness to keep clients from retrying in lockstep
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .subsec_nanos() as u64;
+    Duration::from_millis(step / 2 + nanos % (step / 2 + 1))
+}
+
+/// Check the stored key against the active provider
+pub async fn validate_api_key() -> Result<(), AiError> {
+    let provider = provider();
+    let api_key = crate::secrets::api_key();
+    if provider.requires_api_key() && api_key.is_empty() {
+        return Err(AiError::NoApiKey { provider: provider.name().to_string() });
+    }
+    provider.check_api_key(api_key).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run `data` through `apply_stream_event`, returning whether it ended the
+    /// stream and the text it added
+    fn apply(data: &str) -> Result<(bool, String), AiError> {
+        let mut text = String::new();
+        let mut sink: TokenSink<'_> = Box::new(|_| {});
+        let done = apply_stream_event("groq", data, &mut text, &mut sink)?;
+        Ok((done, text))
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> reqwest::header::HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (reqwest::header::HeaderName::from_static(name), value.parse().unwrap()))
+            .collect()
+    }
+
+    /// A provider's error body
+    fn body(json: &str) -> Option<CompletionError> {
+        Some(serde_json::from_str(json).unwrap())
+    }
+
+    #[test]
+    fn failed_responses_are_classified() {
+        let none = headers(&[]);
+        let waiting = headers(&[("retry-after", "7")]);
+        let unauthorized = AiError::Unauthorized { provider: "groq".to_string() };
+        let cases = [
+            (401, &none, None, unauthorized.clone()),
+            (403, &none, body(r#"{"message":"Forbidden"}"#), unauthorized),
+            (402, &none, body(r#"{"message":"Pay up"}"#), AiError::QuotaExceeded("Pay up".to_string())),
+            (402, &none, None, AiError::QuotaExceeded("groq returned 402 Payment Required".to_string())),
+            // Quota errors come as 429s too, and aren't worth retrying
+            (
+                429,
+                &waiting,
+                body(r#"{"message":"No credits","code":"insufficient_quota"}"#),
+                AiError::QuotaExceeded("No credits".to_string()),
+            ),
+            (
+                400,
+                &none,
+                body(r#"{"message":"No credits","type":"insufficient_quota"}"#),
+                AiError::QuotaExceeded("No credits".to_string()),
+            ),
+            (429, &waiting, None, AiError::RateLimited { retry_after_secs: Some(7) }),
+            (429, &none, body(r#"{"message":"Slow down"}"#), AiError::RateLimited { retry_after_secs: None }),
+            (
+                500,
+                &none,
+                body(r#"{"message":"Overloaded","code":503}"#),
+                AiError::Provider { status: Some(500), message: "Overloaded".to_string() },
+            ),
+            (
+                404,
+                &none,
+                None,
+                AiError::Provider { status: Some(404), message: "groq returned 404 Not Found".to_string() },
+            ),
+            // An error body after a 200, e.g. mid-stream
+            (
+                200,
+                &none,
+                body(r#"{"message":"Model crashed"}"#),
+                AiError::Provider { status: None, message: "Model crashed".to_string() },
+            ),
+        ];
+        for (status, headers, error, expected) in cases {
+            let status = reqwest::StatusCode::from_u16(status).unwrap();
+            assert_eq!(AiError::from_response("groq", status, headers, error), expected, "{}", status);
+        }
+    }
+
+    #[test]
+    fn errors_in_the_stream_are_classified() {
+        let limited = apply(r#"{"error":{"message":"Too fast","code":"rate_limit_exceeded"}}"#);
+        assert_eq!(limited, Err(AiError::RateLimited { retry_after_secs: None }));
+        let failed = apply(r#"{"error":{"message":"Model crashed"}}"#);
+        assert_eq!(failed, Err(AiError::Provider { status: None, message: "Model crashed".to_string() }));
+    }
+
+    #[test]
+    fn reset_durations() {
+        assert_eq!(parse_reset_duration("1m30s"), Some(90));
+        assert_eq!(parse_reset_duration("2m59.56s"), Some(180));
+        assert_eq!(parse_reset_duration("7.66s"), Some(8));
+        assert_eq!(parse_reset_duration("1h"), Some(3600));
+        assert_eq!(parse_reset_duration(" 250ms "), Some(1));
+        assert_eq!(parse_reset_duration("0s"), Some(0));
+    }
+
+    #[test]
+    fn garbage_reset_durations_are_ignored() {
+        for value in ["", "  ", "2", "soon", "5x", "s", "1.2.3s", "-1s", "1m30"] {
+            assert_eq!(parse_reset_duration(value), None, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn retry_after_prefers_the_standard_header() {
+        assert_eq!(retry_after_secs(&headers(&[("retry-after", "2")])), Some(2));
+        assert_eq!(retry_after_secs(&headers(&[("retry-after", " 1.5 ")])), Some(2));
+        let both = headers(&[("retry-after", "3"), ("x-ratelimit-reset-tokens", "1m")]);
+        assert_eq!(retry_after_secs(&both), Some(3));
+    }
+
+    #[test]
+    fn retry_after_falls_back_to_the_longest_reset() {
+        let resets = headers(&[("x-ratelimit-reset-requests", "250ms"), ("x-ratelimit-reset-tokens", "1m30s")]);
+        assert_eq!(retry_after_secs(&resets), Some(90));
+        // An HTTP date isn't supported, so the reset headers decide
+        let dated = headers(&[("retry-after", "Wed, 21 Oct 2026 07:28:00 GMT"), ("x-ratelimit-reset-tokens", "4s")]);
+        assert_eq!(retry_after_secs(&dated), Some(4));
+    }
+
+    #[test]
+    fn garbage_retry_after_is_ignored() {
+        assert_eq!(retry_after_secs(&headers(&[])), None);
+        for value in ["soon", "-5", "NaN", "inf"] {
+            assert_eq!(retry_after_secs(&headers(&[("retry-after", value)])), None, "{:?}", value);
+        }
+        assert_eq!(retry_after_secs(&headers(&[("x-ratelimit-reset-tokens", "later")])), None);
+    }
+
+    /// Whether `delay` is in the jittered range for a backoff step of `step_ms`
+    fn is_backoff(delay: Duration, step_ms: u64) -> bool {
+        let ms = delay.as_millis() as u64;
+        ms >= step_ms / 2 && ms <= step_ms
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        for _ in 0..50 {
+            assert!(is_backoff(backoff_delay(1), BACKOFF_BASE_MS));
+            assert!(is_backoff(backoff_delay(2), BACKOFF_BASE_MS * 2));
+            assert!(is_backoff(backoff_delay(3), BACKOFF_BASE_MS * 4));
+            for attempt in [5, 6, 20, 64, u32::MAX] {
+                assert!(is_backoff(backoff_delay(attempt), BACKOFF_MAX_MS), "attempt {}", attempt);
+            }
+        }
+    }
+
+    #[test]
+    fn retry_after_takes_precedence_over_backoff() {
+        let limited = |secs| AiError::RateLimited { retry_after_secs: secs };
+        assert_eq!(retry_delay(&limited(Some(0)), 3), Some(Duration::ZERO));
+        assert_eq!(retry_delay(&limited(Some(12)), 1), Some(Duration::from_secs(12)));
+        let longest = Duration::from_secs(MAX_RETRY_AFTER_SECS);
+        assert_eq!(retry_delay(&limited(Some(MAX_RETRY_AFTER_SECS)), 1), Some(longest));
+        assert_eq!(retry_delay(&limited(Some(MAX_RETRY_AFTER_SECS + 1)), 1), None);
+        // Without a hint a rate limit backs off like any other transient error
+        assert!(is_backoff(retry_delay(&limited(None), 2).unwrap(), BACKOFF_BASE_MS * 2));
+        assert!(is_backoff(retry_delay(&AiError::Timeout, 1).unwrap(), BACKOFF_BASE_MS));
+    }
+
+    #[test]
+    fn sse_events_split_across_chunks() {
+        let mut decoder = SseDecoder::default();
+        assert!(decoder.push(b"da").is_empty());
+        assert!(decoder.push(b"ta: {\"a\":").is_empty());
+        assert!(decoder.push(b"1}\n").is_empty());
+        assert_eq!(decoder.push(b"\ndata: two\n\ndata: thr"), ["{\"a\":1}", "two"]);
+        assert_eq!(decoder.finish().as_deref(), Some("thr"));
+        assert_eq!(decoder.finish(), None);
+    }
+
+    #[test]
+    fn sse_multi_byte_characters_split_across_chunks() {
+        let bytes = "data: purr 🐱\n\n".as_bytes();
+        let mut decoder = SseDecoder::default();
+        let split = bytes.len() - 4;
+        assert!(decoder.push(&bytes[..split]).is_empty());
+        assert_eq!(decoder.push(&bytes[split..]), ["purr 🐱"]);
+    }
+
+    #[test]
+    fn sse_crlf_line_endings() {
+        let mut decoder = SseDecoder::default();
+        assert_eq!(decoder.push(b"data: one\r\n\r\ndata: two\r"), ["one"]);
+        assert_eq!(decoder.push(b"\n\r\n"), ["two"]);
+    }
+
+    #[test]
+    fn sse_multi_line_data_and_other_fields() {
+        let mut decoder = SseDecoder::default();
+        let events = decoder.push(b": keep-alive\nevent: message\nid: 7\ndata: first\ndata:second\n\n\n");
+        assert_eq!(events, ["first\nsecond"]);
+    }
+
+    #[test]
+    fn stream_events_add_text_until_done() {
+        let chunk = r#"{"choices":[{"delta":{"content":"Mrrp"}}]}"#;
+        assert_eq!(apply(chunk).unwrap(), (false, "Mrrp".to_string()));
+        assert_eq!(apply(r#"{"choices":[{"delta":{}}]}"#).unwrap(), (false, String::new()));
+        assert_eq!(apply("[DONE]").unwrap(), (true, String::new()));
+        assert_eq!(apply(" [DONE] ").unwrap(), (true, String::new()));
+    }
+
+    #[test]
+    fn malformed_stream_events_are_errors() {
+        assert!(matches!(apply(r#"{"choices":[{"delta":"#), Err(AiError::MalformedResponse(_))));
+        assert!(matches!(apply("not json"), Err(AiError::MalformedResponse(_))));
+    }
+}
@@ -0,0 +1,188 @@
+//! Chat state module - The overlay chat's state machine
+//! Replaces the old `CHAT_STATE` integer: every change goes through
+//! `transition`, which rejects moves the chat flow never makes and tells the
+//! frontend about the ones it accepts. Platform-neutral, no AppKit needed
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChatState {
+    /// Only the cat is shown
+    Idle,
+    /// The input box is open
+    InputOpen,
+    /// A question was sent, nothing has come back yet
+    Thinking,
+    /// The reply is streaming in
+    Streaming,
+    /// The full reply is shown (or still being typed out)
+    Responding,
+    /// An error message is shown instead of a reply
+    Error,
+    /// The user cancelled; the UI is being put back to idle
+    Cancelled,
+}
+
+const ALL_STATES: [ChatState; 7] = [
+    ChatState::Idle,
+    ChatState::InputOpen,
+    ChatState::Thinking,
+    ChatState::Streaming,
+    ChatState::Responding,
+    ChatState::Error,
+    ChatState::Cancelled,
+];
+
+impl ChatState {
+    /// Whether the chat flow ever moves from `self` straight to `next`
+    pub fn can_transition_to(self, next: ChatState) -> bool {
+        use ChatState::*;
+        matches!(
+            (self, next),
+            (Idle, InputOpen)
+                | (InputOpen, Idle | Thinking)
+                | (Thinking, Streaming | Error | Cancelled)
+                // An error after some tokens keeps the partial reply on screen
+                | (Streaming, Responding | Error | Cancelled)
+                | (Responding | Error, Idle | Cancelled)
+                | (Cancelled, Idle)
+        )
+    }
+
+    /// Waiting on the provider, so cancelling makes sense
+    pub fn is_busy(self) -> bool {
+        matches!(self, ChatState::Thinking | ChatState::Streaming)
+    }
+
+    /// The response box is up (reply or error)
+    pub fn shows_response(self) -> bool {
+        matches!(self, ChatState::Streaming | ChatState::Responding | ChatState::Error)
+    }
+
+    fn index(self) -> usize {
+        ALL_STATES.iter().position(|s| *s == self).unwrap_or(0)
+    }
+
+    fn from_index(index: usize) -> Self {
+        ALL_STATES.get(index).copied().unwrap_or(ChatState::Idle)
+    }
+}
+
+/// Payload of the `chat-state` event
+#[derive(Clone, Serialize)]
+pub struct ChatStateChange {
+    pub from: ChatState,
+    pub to: ChatState,
+}
+
+static STATE: AtomicUsize = AtomicUsize::new(0);
+
+/// The current chat state
+pub fn current() -> ChatState {
+    ChatState::from_index(STATE.load(Ordering::SeqCst))
+}
+
+/// Move to `next` if the current state allows it, returning the previous state
+/// Moving to the current state is a no-op and always succeeds
+pub fn transition(next: ChatState) -> Result<ChatState, String> {
+    let mut index = STATE.load(Ordering::SeqCst);
+    loop {
+        let from = ChatState::from_index(index);
+        if from == next {
+            return Ok(from);
+        }
+        if !from.can_transition_to(next) {
+            log::warn!("Rejected chat state transition {:?} -> {:?}", from, next);
+            return Err(format!("Invalid chat state transition {:?} -> {:?}", from, next));
+        }
+
+        // Another thread may have moved first; re-validate against what it left
+        match STATE.compare_exchange(index, next.index(), Ordering::SeqCst, Ordering::SeqCst) {
+            Ok(_) => {
+                log::info!("Chat state {:?} -> {:?}", from, next);
+                crate::events::emit("chat-state", ChatStateChange { from, to: next });
+                return Ok(from);
+            }
+            Err(actual) => index = actual,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ChatState::*;
+
+    fn set(state: ChatState) {
+        STATE.store(state.index(), Ordering::SeqCst);
+    }
+
+    #[test]
+    fn transition_table() {
+        let allowed = [
+            (Idle, InputOpen),
+            (InputOpen, Idle),
+            (InputOpen, Thinking),
+            (Thinking, Streaming),
+            (Thinking, Error),
+            (Thinking, Cancelled),
+            (Streaming, Responding),
+            (Streaming, Error),
+            (Streaming, Cancelled),
+            (Responding, Idle),
+            (Responding, Cancelled),
+            (Error, Idle),
+            (Error, Cancelled),
+            (Cancelled, Idle),
+        ];
+        for from in ALL_STATES {
+            for to in ALL_STATES {
+                assert_eq!(from.can_transition_to(to), allowed.contains(&(from, to)), "{:?} -> {:?}", from, to);
+            }
+        }
+    }
+
+    #[test]
+    fn rejected_transition_keeps_state() {
+        let _globals = crate::lock_globals();
+        set(Idle);
+        assert!(transition(Streaming).is_err());
+        assert_eq!(current(), Idle);
+        set(Thinking);
+        assert!(transition(Idle).is_err());
+        assert_eq!(current(), Thinking);
+        set(Idle);
+    }
+
+    #[test]
+    fn accepted_transition_moves() {
+        let _globals = crate::lock_globals();
+        set(Idle);
+        assert_eq!(transition(InputOpen), Ok(Idle));
+        assert_eq!(current(), InputOpen);
+        assert_eq!(transition(Thinking), Ok(InputOpen));
+        assert_eq!(current(), Thinking);
+        set(Idle);
+    }
+
+    #[test]
+    fn same_state_is_a_no_op() {
+        let _globals = crate::lock_globals();
+        for state in ALL_STATES {
+            set(state);
+            assert_eq!(transition(state), Ok(state));
+            assert_eq!(current(), state);
+        }
+        set(Idle);
+    }
+
+    #[test]
+    fn busy_and_response_flags() {
+        for state in ALL_STATES {
+            assert_eq!(state.is_busy(), matches!(state, Thinking | Streaming), "{:?}", state);
+            assert_eq!(state.shows_response(), matches!(state, Streaming | Responding | Error), "{:?}", state);
+        }
+    }
+}
@@ -0,0 +1,311 @@
+//! Conversation module - Multi-turn memory shared by the overlay chat and `ask_ai`
+//! History is bounded by a turn and token budget; turns that fall out of the
+//! budget are folded into a short running summary instead of being forgotten
+
+use crate::ai::ChatMessage;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+// Budget for the verbatim history sent with every request
+const MAX_TURNS: usize = 10;
+const MAX_HISTORY_TOKENS: usize = 1500;
+
+// Budget for the summary of older turns
+const MAX_SUMMARY_LINES: usize = 8;
+const SUMMARY_SNIPPET_CHARS: usize = 80;
+
+/// One question and the cat's answer to it
+#[derive(Clone, Debug, Serialize)]
+pub struct Turn {
+    pub user: String,
+    pub assistant: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Conversation {
+    turns: VecDeque<Turn>,
+    /// One line per turn that was dropped from the verbatim history
+    summary: VecDeque<String>,
+    dropped_turns: usize,
+    /// Persona picked for this conversation, `None` for the default one
+    persona_id: Option<String>,
+}
+
+impl Conversation {
+    pub const fn new() -> Self {
+        Self {
+            turns: VecDeque::new(),
+            summary: VecDeque::new(),
+            dropped_turns: 0,
+            persona_id: None,
+        }
+    }
+
+    /// Build the full message list for a new question
+    pub fn messages_for(&self, system_prompt: &str, question: &str) -> Vec<ChatMessage> {
+        let mut messages = vec![ChatMessage::system(system_prompt)];
+
+        if !self.summary.is_empty() {
+            let lines: Vec<&str> = self.summary.iter().map(String::as_str).collect();
+            messages.push(ChatMessage::system(&format!(
+                "Summary of earlier conversation:\n{}",
+                lines.join("\n")
+            )));
+        }
+
+        for turn in &self.turns {
+            messages.push(ChatMessage::user(&turn.user));
+            messages.push(ChatMessage::assistant(&turn.assistant));
+        }
+
+        messages.push(ChatMessage::user(question));
+        messages
+    }
+
+    /// Remember a completed turn, trimming history back into budget
+    pub fn record(&mut self, question: &str, answer: &str) {
+        self.turns.push_back(Turn {
+            user: question.to_string(),
+            assistant: answer.to_string(),
+        });
+
+        while self.turns.len() > 1
+            && (self.turns.len() > MAX_TURNS || self.history_tokens() > MAX_HISTORY_TOKENS)
+        {
+            if let Some(turn) = self.turns.pop_front() {
+                self.fold_into_summary(&turn);
+            }
+        }
+    }
+
+    /// Rough token count of the verbatim history
+    pub fn history_tokens(&self) -> usize {
+        self.turns
+            .iter()
+            .map(|t| estimate_tokens(&t.user) + estimate_tokens(&t.assistant))
+            .sum()
+    }
+
+    fn fold_into_summary(&mut self, turn: &Turn) {
+        self.dropped_turns += 1;
+        self.summary.push_back(format!(
+            "- User asked: {} / You answered: {}",
+            snippet(&turn.user),
+            snippet(&turn.assistant)
+        ));
+        while self.summary.len() > MAX_SUMMARY_LINES {
+            self.summary.pop_front();
+        }
+    }
+}
+
+/// ~4 characters per token is close enough for budgeting
+fn estimate_tokens(text: &str) -> usize {
+    text.chars().count() / 4 + 1
+}
+
+/// First line of `text`, cut to a summary-friendly length
+fn snippet(text: &str) -> String {
+    let line = text.lines().next().unwrap_or("").trim();
+    if line.chars().count() > SUMMARY_SNIPPET_CHARS {
+        let cut: String = line.chars().take(SUMMARY_SNIPPET_CHARS).collect();
+        format!("{}...", cut.trim_end())
+    } else {
+        line.to_string()
+    }
+}
+
+/// What `get_conversation` returns to the frontend
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationInfo {
+    pub turns: Vec<Turn>,
+    pub summary: Vec<String>,
+    pub dropped_turns: usize,
+    pub estimated_tokens: usize,
+    pub persona_id: String,
+}
+
+// The single conversation shared by the overlay and the main window
+static CONVERSATION: Mutex<Conversation> = Mutex::new(Conversation::new());
+
+/// Build the messages for a question against the shared conversation
+pub fn messages_for(system_prompt: &str, question: &str) -> Vec<ChatMessage> {
+    CONVERSATION.lock().unwrap().messages_for(system_prompt, question)
+}
+
+/// Record a completed turn in the shared conversation
+pub fn record(question: &str, answer: &str) {
+    CONVERSATION.lock().unwrap().record(question, answer);
+}
+
+/// Persona of the shared conversation: the one chosen for it, else the
+/// skin's if that exists, else the default in the settings
+pub fn persona_id() -> String {
+    let chosen = CONVERSATION.lock().unwrap().persona_id.clone();
+    chosen
+        .or_else(|| {
+            let skin_persona = crate::overlay::skin().manifest.default_persona.clone();
+            skin_persona.filter(|id| crate::personas::get(id).is_some())
+        })
+        .unwrap_or_else(|| crate::settings::current().default_persona)
+}
+
+/// Switch the shared conversation to another persona
+/// History is kept, so the new persona picks up where the old one left off
+pub fn set_persona(id: &str) -> Result<(), String> {
+    if crate::personas::get(id).is_none() {
+        return Err(format!("No persona with id {}", id));
+    }
+    CONVERSATION.lock().unwrap().persona_id = Some(id.to_string());
+    log::info!("Conversation persona set to {}", id);
+    Ok(())
+}
+
+/// Forget the shared conversation (a new one starts with the default persona)
+pub fn reset() {
+    let mut guard = CONVERSATION.lock().unwrap();
+    *guard = Conversation::new();
+    crate::history::end_session();
+    log::info!("Conversation reset");
+}
+
+/// Inspect the shared conversation
+pub fn info() -> ConversationInfo {
+    let persona_id = persona_id();
+    let guard = CONVERSATION.lock().unwrap();
+    ConversationInfo {
+        turns: guard.turns.iter().cloned().collect(),
+        summary: guard.summary.iter().cloned().collect(),
+        dropped_turns: guard.dropped_turns,
+        estimated_tokens: guard.history_tokens(),
+        persona_id,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A question and answer of about `tokens` tokens together
+    fn record_sized(conversation: &mut Conversation, n: usize, tokens: usize) {
+        let half = "x".repeat(tokens * 2);
+        conversation.record(&format!("Question {}\n{}", n, half), &format!("Answer {}\n{}", n, half));
+    }
+
+    fn questions(conversation: &Conversation) -> Vec<String> {
+        conversation.turns.iter().map(|t| t.user.lines().next().unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn turns_past_max_turns_are_folded_into_the_summary() {
+        let mut conversation = Conversation::new();
+        for n in 0..MAX_TURNS {
+            conversation.record(&format!("Question {}", n), &format!("Answer {}", n));
+        }
+        assert_eq!(conversation.turns.len(), MAX_TURNS);
+        assert!(conversation.summary.is_empty());
+
+        conversation.record("Question 10", "Answer 10");
+        assert_eq!(conversation.turns.len(), MAX_TURNS);
+        assert_eq!(conversation.turns[0].user, "Question 1");
+        assert_eq!(conversation.dropped_turns, 1);
+        assert_eq!(conversation.summary, ["- User asked: Question 0 / You answered: Answer 0"]);
+    }
+
+    #[test]
+    fn turns_past_the_token_budget_are_folded_into_the_summary() {
+        let mut conversation = Conversation::new();
+        let tokens = MAX_HISTORY_TOKENS / 3;
+        for n in 0..3 {
+            record_sized(&mut conversation, n, tokens);
+        }
+        // Two fit, the third pushed the first out
+        assert_eq!(questions(&conversation), ["Question 1", "Question 2"]);
+        assert!(conversation.history_tokens() <= MAX_HISTORY_TOKENS);
+        assert_eq!(conversation.summary, ["- User asked: Question 0 / You answered: Answer 0"]);
+
+        // The latest turn stays even if it's over budget on its own
+        record_sized(&mut conversation, 3, MAX_HISTORY_TOKENS * 2);
+        assert_eq!(questions(&conversation), ["Question 3"]);
+        assert_eq!(conversation.dropped_turns, 3);
+    }
+
+    #[test]
+    fn the_summary_keeps_the_latest_lines_cut_short() {
+        let mut conversation = Conversation::new();
+        let long = "y".repeat(SUMMARY_SNIPPET_CHARS + 20);
+        for n in 0..MAX_TURNS + MAX_SUMMARY_LINES + 2 {
+            conversation.record(&format!("Question {}", n), &long);
+        }
+        assert_eq!(conversation.summary.len(), MAX_SUMMARY_LINES);
+        assert_eq!(conversation.dropped_turns, MAX_SUMMARY_LINES + 2);
+        let cut = format!("{}...", "y".repeat(SUMMARY_SNIPPET_CHARS));
+        assert_eq!(conversation.summary[0], format!("- User asked: Question 2 / You answered: {}", cut));
+    }
+
+    #[test]
+    fn messages_put_the_summary_before_the_turns() {
+        let mut conversation = Conversation::new();
+        for n in 0..=MAX_TURNS {
+            conversation.record(&format!("Question {}", n), &format!("Answer {}", n));
+        }
+        let messages = conversation.messages_for("You are a cat.", "Still there?");
+        let roles: Vec<_> = messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles.len(), 2 + 2 * MAX_TURNS + 1);
+        assert_eq!(roles[..4], ["system", "system", "user", "assistant"]);
+        assert_eq!(messages[0].content, "You are a cat.");
+        assert!(messages[1].content.ends_with("- User asked: Question 0 / You answered: Answer 0"));
+        assert_eq!(messages[2].content, "Question 1");
+        assert_eq!(messages.last().unwrap().content, "Still there?");
+
+        let fresh = Conversation::new().messages_for("You are a cat.", "Hi");
+        assert_eq!(fresh.iter().map(|m| m.role.as_str()).collect::<Vec<_>>(), ["system", "user"]);
+    }
+
+    #[test]
+    fn reset_forgets_turns_and_persona() {
+        let _globals = crate::lock_globals();
+        reset();
+        record("Hi", "Mrrp");
+        set_persona(crate::personas::DEFAULT_PERSONA_ID).unwrap();
+        assert!(set_persona("no-such-persona").is_err());
+        assert_eq!(info().turns.len(), 1);
+
+        reset();
+        let info = info();
+        assert!(info.turns.is_empty() && info.summary.is_empty());
+        assert_eq!((info.dropped_turns, info.estimated_tokens), (0, 0));
+        assert_eq!(CONVERSATION.lock().unwrap().persona_id, None);
+    }
+
+    #[test]
+    fn persona_falls_back_to_the_skin_then_the_settings() {
+        let _globals = crate::lock_globals();
+        reset();
+        let settings = crate::settings::Settings { default_persona: "from-settings".to_string(), ..Default::default() };
+        crate::settings::set_current(settings);
+        let skin = crate::overlay::skin();
+        assert_eq!(skin.manifest.default_persona.as_deref(), Some(crate::personas::DEFAULT_PERSONA_ID));
+        assert_eq!(persona_id(), crate::personas::DEFAULT_PERSONA_ID);
+
+        // A skin persona that doesn't exist is skipped
+        let mut ghost = (*skin).clone();
+        ghost.manifest.default_persona = Some("ghost".to_string());
+        crate::overlay::set_skin(ghost.clone());
+        assert_eq!(persona_id(), "from-settings");
+        ghost.manifest.default_persona = None;
+        crate::overlay::set_skin(ghost);
+        assert_eq!(persona_id(), "from-settings");
+
+        // Chosen for the conversation wins over both
+        CONVERSATION.lock().unwrap().persona_id = Some("chosen".to_string());
+        assert_eq!(persona_id(), "chosen");
+
+        reset();
+        crate::overlay::set_skin((*skin).clone());
+        crate::settings::set_current(crate::settings::Settings::default());
+    }
+}
@@ -0,0 +1,36 @@
+//! Events module - Pushes backend events to the main window
+//! The app handle is captured once in `setup`, so code without access to
+//! Tauri state (overlay threads, providers) can still emit events and
+//! schedule work on the main thread
+
+use serde::Serialize;
+use std::sync::OnceLock;
+use tauri::{AppHandle, Emitter};
+
+static APP_HANDLE: OnceLock<AppHandle> = OnceLock::new();
+
+/// Store the app handle used for emitting events
+pub fn init(handle: AppHandle) {
+    let _ = APP_HANDLE.set(handle);
+}
+
+/// Emit an event to the frontend, logging (not failing) if it can't be delivered
+pub fn emit<S: Serialize + Clone>(event: &str, payload: S) {
+    if let Some(handle) = APP_HANDLE.get() {
+        if let Err(e) = handle.emit(event, payload) {
+            log::warn!("Failed to emit {}: {}", event, e);
+        }
+    }
+}
+
+/// Run `f` on the main (UI) thread, or inline if the app isn't running yet
+pub fn run_on_main_thread<F: FnOnce() + Send + 'static>(f: F) {
+    match APP_HANDLE.get() {
+        Some(handle) => {
+            if let Err(e) = handle.run_on_main_thread(f) {
+                log::warn!("Failed to schedule work on the main thread: {}", e);
+            }
+        }
+        None => f(),
+    }
+}
@@ -0,0 +1,87 @@
+//! Focus module - The user's current focus session
+//! Only tracked in memory; personas mention it through `{{focus_session}}`
+//! so the cat can cheer the user on (or keep chit-chat short)
+
+use serde::Serialize;
+use std::sync::Mutex;
+
+// Longest session we accept, in minutes
+const MAX_FOCUS_MINUTES: u32 = 8 * 60;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusSession {
+    /// What the user is focusing on, e.g. "Essay draft"
+    pub label: String,
+    /// Unix time in milliseconds
+    pub started_at: u64,
+    pub duration_minutes: u32,
+}
+
+impl FocusSession {
+    fn elapsed_minutes(&self) -> u64 {
+        now_millis().saturating_sub(self.started_at) / 60_000
+    }
+}
+
+static FOCUS_SESSION: Mutex<Option<FocusSession>> = Mutex::new(None);
+
+/// Start a focus session, replacing any running one
+pub fn start(label: &str, duration_minutes: u32) -> Result<FocusSession, String> {
+    if duration_minutes == 0 || duration_minutes > MAX_FOCUS_MINUTES {
+        return Err(format!(
+            "Focus session must be between 1 and {} minutes",
+            MAX_FOCUS_MINUTES
+        ));
+    }
+
+    let label = label.trim();
+    let session = FocusSession {
+        label: if label.is_empty() { "Focus".to_string() } else { label.to_string() },
+        started_at: now_millis(),
+        duration_minutes,
+    };
+    log::info!("Focus session started: {} ({} min)", session.label, duration_minutes);
+    *FOCUS_SESSION.lock().unwrap() = Some(session.clone());
+    crate::events::emit("focus-session", Some(session.clone()));
+    Ok(session)
+}
+
+/// End the running focus session, if any
+pub fn stop() {
+    if FOCUS_SESSION.lock().unwrap().take().is_some() {
+        log::info!("Focus session stopped");
+        crate::events::emit("focus-session", None::<FocusSession>);
+    }
+}
+
+/// The running focus session, dropping it once its time is up
+pub fn current() -> Option<FocusSession> {
+    let mut guard = FOCUS_SESSION.lock().unwrap();
+    if let Some(session) = guard.as_ref() {
+        if session.elapsed_minutes() >= session.duration_minutes as u64 {
+            *guard = None;
+        }
+    }
+    guard.clone()
+}
+
+/// One-line description for prompt templates
+pub fn describe() -> String {
+    match current() {
+        Some(session) => format!(
+            "\"{}\", {} of {} minutes done",
+            session.label,
+            session.elapsed_minutes(),
+            session.duration_minutes
+        ),
+        None => "none running".to_string(),
+    }
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
@@ -0,0 +1,409 @@
+//! History module - Persists chat sessions to disk as JSONL
+//! Each session is one `<id>.jsonl` file under `<app data>/history`, one
+//! message per line, so a crash loses at most the line being written
+
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+// Directory holding the session files, set once the app data dir is known
+static HISTORY_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+// Session new turns are appended to; a new one starts on first write after a reset
+static CURRENT_SESSION: Mutex<Option<String>> = Mutex::new(None);
+
+/// One message as stored on disk
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub role: String,
+    pub content: String,
+    /// Unix time in milliseconds
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionSummary {
+    pub id: String,
+    pub started_at: u64,
+    pub updated_at: u64,
+    pub message_count: usize,
+    /// First question asked in the session
+    pub preview: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub session_id: String,
+    pub entry: HistoryEntry,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+}
+
+/// Set the history directory, creating it if needed
+pub fn init(dir: PathBuf) {
+    if let Err(e) = fs::create_dir_all(&dir) {
+        log::error!("Failed to create history dir {:?}: {}", dir, e);
+        return;
+    }
+    log::info!("Chat history stored in {:?}", dir);
+    *HISTORY_DIR.lock().unwrap() = Some(dir);
+}
+
+/// Start a fresh session on the next recorded turn
+pub fn end_session() {
+    *CURRENT_SESSION.lock().unwrap() = None;
+}
+
+/// Append a completed turn to the current session
+pub fn append_turn(question: &str, answer: &str) {
+    let Some(dir) = HISTORY_DIR.lock().unwrap().clone() else {
+        return;
+    };
+
+    let now = now_millis();
+    let session_id = CURRENT_SESSION
+        .lock()
+        .unwrap()
+        .get_or_insert_with(|| now.to_string())
+        .clone();
+
+    let entries = [
+        HistoryEntry { role: "user".to_string(), content: question.to_string(), timestamp: now },
+        HistoryEntry { role: "assistant".to_string(), content: answer.to_string(), timestamp: now },
+    ];
+
+    let path = dir.join(format!("{}.jsonl", session_id));
+    let result = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| {
+            for entry in &entries {
+                let line = serde_json::to_string(entry)?;
+                writeln!(file, "{}", line)?;
+            }
+            Ok(())
+        });
+
+    if let Err(e) = result {
+        log::error!("Failed to write chat history to {:?}: {}", path, e);
+    }
+}
+
+/// List all stored sessions, newest first
+pub fn list_sessions() -> Result<Vec<SessionSummary>, String> {
+    let mut sessions = Vec::new();
+    for (id, entries) in readable_sessions()? {
+        let (Some(first), Some(last)) = (entries.first(), entries.last()) else {
+            continue;
+        };
+        let preview = entries
+            .iter()
+            .find(|e| e.role == "user")
+            .map(|e| e.content.clone())
+            .unwrap_or_default();
+        sessions.push(SessionSummary {
+            id,
+            started_at: first.timestamp,
+            updated_at: last.timestamp,
+            message_count: entries.len(),
+            preview,
+        });
+    }
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.updated_at));
+    Ok(sessions)
+}
+
+/// Read every message of one session
+pub fn read_session(id: &str) -> Result<Vec<HistoryEntry>, String> {
+    let path = session_path(id)?;
+    let file = fs::File::open(&path).map_err(|e| format!("Failed to open session {}: {}", id, e))?;
+
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| format!("Failed to read session {}: {}", id, e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        // Skip a torn last line rather than losing the whole session
+        match serde_json::from_str(&line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => log::warn!("Skipping bad line in session {}: {}", id, e),
+        }
+    }
+    Ok(entries)
+}
+
+/// Case-insensitive search over every stored message
+pub fn search(query: &str) -> Result<Vec<SearchHit>, String> {
+    let needle = query.trim().to_lowercase();
+    if needle.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut hits = Vec::new();
+    for (id, entries) in readable_sessions()? {
+        for entry in entries {
+            if entry.content.to_lowercase().contains(&needle) {
+                hits.push(SearchHit { session_id: id.clone(), entry });
+            }
+        }
+    }
+    hits.sort_by_key(|h| std::cmp::Reverse(h.entry.timestamp));
+    Ok(hits)
+}
+
+/// Delete one session from disk
+pub fn delete_session(id: &str) -> Result<(), String> {
+    let path = session_path(id)?;
+    fs::remove_file(&path).map_err(|e| format!("Failed to delete session {}: {}", id, e))?;
+
+    // Don't keep appending to a file that was just deleted
+    let mut current = CURRENT_SESSION.lock().unwrap();
+    if current.as_deref() == Some(id) {
+        *current = None;
+    }
+    Ok(())
+}
+
+/// Render one session as Markdown or pretty JSON
+pub fn export_session(id: &str, format: ExportFormat) -> Result<String, String> {
+    let entries = read_session(id)?;
+    match format {
+        ExportFormat::Json => serde_json::to_string_pretty(&entries)
+            .map_err(|e| format!("Failed to export session {}: {}", id, e)),
+        ExportFormat::Markdown => {
+            let started = entries.first().map(|e| format_time(e.timestamp)).unwrap_or_default();
+            let mut out = format!("# Chat with Mittens\n\n_Started {}_\n", started);
+            for entry in &entries {
+                let speaker = if entry.role == "user" { "You" } else { "Mittens" };
+                out.push_str(&format!(
+                    "\n**{}** ({}):\n\n{}\n",
+                    speaker,
+                    format_time(entry.timestamp),
+                    entry.content
+                ));
+            }
+            Ok(out)
+        }
+    }
+}
+
+fn session_ids() -> Result<Vec<String>, String> {
+    let Some(dir) = HISTORY_DIR.lock().unwrap().clone() else {
+        return Ok(Vec::new());
+    };
+
+    let read_dir = fs::read_dir(&dir).map_err(|e| format!("Failed to read history dir: {}", e))?;
+    Ok(read_dir
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name.strip_suffix(".jsonl").map(str::to_string)
+        })
+        .filter(|id| is_valid_id(id))
+        .collect())
+}
+
+/// Every session with its messages, skipping files that can't be read so one
+/// bad file doesn't hide the rest
+fn readable_sessions() -> Result<Vec<(String, Vec<HistoryEntry>)>, String> {
+    Ok(session_ids()?
+        .into_iter()
+        .filter_map(|id| match read_session(&id) {
+            Ok(entries) => Some((id, entries)),
+            Err(e) => {
+                log::warn!("Skipping unreadable chat history: {}", e);
+                None
+            }
+        })
+        .collect())
+}
+
+fn session_path(id: &str) -> Result<PathBuf, String> {
+    // Ids come from the frontend, so never let them escape the history dir
+    if !is_valid_id(id) {
+        return Err(format!("Invalid session id: {}", id));
+    }
+    let dir = HISTORY_DIR
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or_else(|| "Chat history is not available".to_string())?;
+    Ok(dir.join(format!("{}.jsonl", id)))
+}
+
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_digit())
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+fn format_time(millis: u64) -> String {
+    Local
+        .timestamp_millis_opt(millis as i64)
+        .single()
+        .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Point the history at an empty folder and start a fresh session
+    fn with_history_dir(test: &str) -> PathBuf {
+        let dir = crate::temp_dir(test);
+        init(dir.clone());
+        end_session();
+        dir
+    }
+
+    /// Record one turn as its own session; ids are start times in milliseconds
+    fn record_session(question: &str, answer: &str) {
+        end_session();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        append_turn(question, answer);
+    }
+
+    #[test]
+    fn turns_are_appended_to_the_current_session() {
+        let _globals = crate::lock_globals();
+        with_history_dir("history-record");
+        append_turn("Are you awake?", "Mrrp.");
+        append_turn("Want a treat?", "Always.");
+
+        let sessions = list_sessions().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].message_count, 4);
+        assert_eq!(sessions[0].preview, "Are you awake?");
+        let roles: Vec<_> = read_session(&sessions[0].id).unwrap().into_iter().map(|e| e.role).collect();
+        assert_eq!(roles, ["user", "assistant", "user", "assistant"]);
+    }
+
+    #[test]
+    fn sessions_are_listed_newest_first() {
+        let _globals = crate::lock_globals();
+        with_history_dir("history-list");
+        record_session("first", "one");
+        record_session("second", "two");
+
+        let previews: Vec<_> = list_sessions().unwrap().into_iter().map(|s| s.preview).collect();
+        assert_eq!(previews, ["second", "first"]);
+    }
+
+    #[test]
+    fn search_ignores_case_and_finds_both_roles() {
+        let _globals = crate::lock_globals();
+        with_history_dir("history-search");
+        record_session("Do you like TUNA?", "Tuna is the best.");
+        record_session("What about salmon?", "Also good.");
+
+        let hits = search("  tuna ").unwrap();
+        let contents: Vec<_> = hits.iter().map(|h| h.entry.content.as_str()).collect();
+        assert_eq!(contents.len(), 2);
+        assert!(contents.contains(&"Do you like TUNA?") && contents.contains(&"Tuna is the best."));
+        assert!(search("").unwrap().is_empty());
+        assert!(search("mice").unwrap().is_empty());
+    }
+
+    #[test]
+    fn unreadable_sessions_are_skipped() {
+        let _globals = crate::lock_globals();
+        let dir = with_history_dir("history-unreadable");
+        record_session("Still here?", "Yes.");
+        // Not UTF-8, so reading it fails outright
+        fs::write(dir.join("1.jsonl"), [0xff, 0xfe, b'\n']).unwrap();
+        // A torn line only loses that line
+        fs::write(dir.join("2.jsonl"), "{\"role\":\"user\",\"content\":\"Still?\",\"timestamp\":2}\n{\"ro").unwrap();
+
+        let sessions = list_sessions().unwrap();
+        let ids: Vec<_> = sessions.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids.len(), 2);
+        assert!(!ids.contains(&"1"));
+        assert_eq!(sessions.iter().find(|s| s.id == "2").unwrap().message_count, 1);
+        assert_eq!(search("still").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn deleting_the_current_session_starts_a_new_one() {
+        let _globals = crate::lock_globals();
+        let dir = with_history_dir("history-delete");
+        record_session("Old question", "Old answer");
+        let old = list_sessions().unwrap()[0].id.clone();
+
+        delete_session(&old).unwrap();
+        assert!(!dir.join(format!("{}.jsonl", old)).exists());
+        assert!(list_sessions().unwrap().is_empty());
+        assert!(delete_session(&old).is_err());
+
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        append_turn("New question", "New answer");
+        let sessions = list_sessions().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_ne!(sessions[0].id, old);
+        assert_eq!(sessions[0].preview, "New question");
+    }
+
+    #[test]
+    fn sessions_export_as_markdown_and_json() {
+        let _globals = crate::lock_globals();
+        let dir = with_history_dir("history-export");
+        let entry = |role: &str, content: &str, timestamp| HistoryEntry {
+            role: role.to_string(),
+            content: content.to_string(),
+            timestamp,
+        };
+        let entries = [entry("user", "Hungry?", 1_700_000_000_000), entry("assistant", "Always.", 1_700_000_060_000)];
+        let lines: Vec<_> = entries.iter().map(|e| serde_json::to_string(e).unwrap()).collect();
+        fs::write(dir.join("42.jsonl"), lines.join("\n") + "\n").unwrap();
+
+        let json = export_session("42", ExportFormat::Json).unwrap();
+        let json: Vec<HistoryEntry> = serde_json::from_str(&json).unwrap();
+        assert_eq!(json.len(), 2);
+        assert_eq!((json[1].role.as_str(), json[1].content.as_str()), ("assistant", "Always."));
+
+        let (asked, answered) = (format_time(entries[0].timestamp), format_time(entries[1].timestamp));
+        let markdown = export_session("42", ExportFormat::Markdown).unwrap();
+        assert_eq!(
+            markdown,
+            format!(
+                "# Chat with Mittens\n\n_Started {0}_\n\n**You** ({0}):\n\nHungry?\n\n**Mittens** ({1}):\n\nAlways.\n",
+                asked, answered
+            )
+        );
+        assert!(export_session("43", ExportFormat::Json).is_err());
+    }
+
+    #[test]
+    fn session_ids_cannot_leave_the_history_dir() {
+        let _globals = crate::lock_globals();
+        let dir = with_history_dir("history-ids");
+        fs::write(dir.join("x"), "").unwrap();
+        fs::write(dir.join("1.jsonl.jsonl"), "").unwrap();
+
+        for id in ["", "../x", "..", "/tmp/1", "1.jsonl", "1/2", "-1", " 1", "x"] {
+            assert!(session_path(id).is_err(), "{:?} was accepted", id);
+            assert!(read_session(id).is_err());
+            assert!(delete_session(id).is_err());
+            assert!(export_session(id, ExportFormat::Markdown).is_err());
+        }
+        assert!(dir.join("x").exists() && dir.join("1.jsonl.jsonl").exists());
+        assert_eq!(session_path("1700000000000").unwrap(), dir.join("1700000000000.jsonl"));
+    }
+}
@@ -1,107 +1,158 @@
+mod ai;
+mod chat_state;
+mod conversation;
+mod events;
+mod focus;
+mod history;
 mod overlay;
+mod personas;
+mod power;
+mod secrets;
+mod settings;
+mod skins;
+mod tasks;
 
-use serde::{Deserialize, Serialize};
+use tauri::Manager;
 
-// Groq API request/response structures
-#[derive(Serialize)]
-struct GroqRequest {
-    model: String,
-    messages: Vec<GroqMessage>,
+/// Serialise tests that share the app's global state (chat state, overlay)
+#[cfg(test)]
+fn lock_globals() -> std::sync::MutexGuard<'static, ()> {
+    static GLOBALS: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    // A failed test must not fail every test after it
+    GLOBALS.lock().unwrap_or_else(|e| e.into_inner())
 }
 
-#[derive(Serialize)]
-struct GroqMessage {
-    role: String,
-    content: String,
+/// An empty folder for `test` in the system's temp dir
+#[cfg(test)]
+fn temp_dir(test: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("catpanion-{}-{}", test, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
 }
 
-#[derive(Deserialize)]
-struct GroqResponse {
-    choices: Option<Vec<GroqChoice>>,
-    error: Option<GroqError>,
+#[tauri::command]
+async fn ask_ai(question: String) -> Result<String, ai::AiError> {
+    // Tokens are streamed to the main window as `chat-token` events
+    let text = ai::ask("main", question, Box::new(|token| ai::emit_token("main", token))).await?;
+    ai::emit_done("main", &text);
+    Ok(text)
 }
 
-#[derive(Deserialize)]
-struct GroqChoice {
-    message: GroqMessageResponse,
+#[tauri::command]
+fn reset_conversation() {
+    conversation::reset();
+}
+
+#[tauri::command]
+fn get_conversation() -> conversation::ConversationInfo {
+    conversation::info()
+}
+
+#[tauri::command]
+fn list_personas() -> Vec<personas::PersonaEntry> {
+    personas::list()
+}
+
+#[tauri::command]
+fn save_persona(persona: personas::Persona) -> Result<Vec<personas::PersonaEntry>, String> {
+    personas::save_persona(persona)
+}
+
+#[tauri::command]
+fn delete_persona(id: String) -> Result<Vec<personas::PersonaEntry>, String> {
+    personas::delete_persona(&id)
+}
+
+#[tauri::command]
+fn get_persona_greeting() -> String {
+    personas::greeting()
 }
 
-#[derive(Deserialize)]
-struct GroqMessageResponse {
-    content: String,
+#[tauri::command]
+fn set_conversation_persona(id: String) -> Result<(), String> {
+    conversation::set_persona(&id)
 }
 
-#[derive(Deserialize)]
-struct GroqError {
-    message: String,
+#[tauri::command]
+fn list_skins() -> Vec<skins::SkinEntry> {
+    skins::list()
 }
 
 #[tauri::command]
-async fn ask_ai(question: String, api_key: String) -> Result<String, String> {
-    let url = "https://api.groq.com/openai/v1/chat/completions";
+fn refresh_skins() -> Vec<skins::SkinEntry> {
+    skins::refresh()
+}
 
-    let system_prompt = r#"You are an adorable cat AI companion. Your personality:
-- Very cute and babyish tone, like a sweet little kitten
-- Keep responses SHORT and precise (1-3 sentences max)
-- Use *meow* *purr* *mrrp* as actions at the end of sentences sometimes
-- Start with cat sounds like "Meow!" or "Mrrp!" occasionally
-- NO slang (no "ngl", "wsp", "yo", "fr", etc.)
-- NO emojis at all
-- Be a friendly companion, not a productivity assistant
-- For ANY math expressions, fractions, or formulas, ALWAYS use LaTeX format with \( \) delimiters
-- Example math: "The answer is \(\frac{\sqrt{2}}{2}\)" NOT "sqrt(2)/2"
-- Example: "Meow! That equals \(\frac{1}{2}\)! *purr*"
-- Examples of good responses:
-  "Meow! Hello there!! How can I help? *meow*"
-  "Ooh that sounds fun! *purr*"
-  "Mrrp! The answer is \(\frac{3}{4}\)! *meow*"
-- You are the user's cute cat companion and friend"#;
+#[tauri::command]
+fn set_skin(id: String) -> Result<Vec<skins::SkinEntry>, String> {
+    skins::set_active(&id)
+}
 
-    let request_body = GroqRequest {
-        model: "meta-llama/llama-4-maverick-17b-128e-instruct".to_string(),
-        messages: vec![
-            GroqMessage {
-                role: "system".to_string(),
-                content: system_prompt.to_string(),
-            },
-            GroqMessage {
-                role: "user".to_string(),
-                content: question,
-            },
-        ],
-    };
+#[tauri::command]
+fn start_focus_session(label: String, minutes: u32) -> Result<focus::FocusSession, String> {
+    focus::start(&label, minutes)
+}
 
-    let client = reqwest::Client::new();
-    let response = client
-        .post(url)
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .json(&request_body)
-        .send()
-        .await
-        .map_err(|e| format!("Request failed: {}", e))?;
+#[tauri::command]
+fn stop_focus_session() {
+    focus::stop();
+}
 
-    let groq_response: GroqResponse = response
-        .json()
-        .await
-        .map_err(|e| format!("Failed to parse response: {}", e))?;
+#[tauri::command]
+fn get_focus_session() -> Option<focus::FocusSession> {
+    focus::current()
+}
 
-    if let Some(error) = groq_response.error {
-        return Err(error.message);
-    }
+#[tauri::command]
+fn list_chat_sessions() -> Result<Vec<history::SessionSummary>, String> {
+    history::list_sessions()
+}
 
-    if let Some(choices) = groq_response.choices {
-        if let Some(choice) = choices.first() {
-            return Ok(choice.message.content.clone());
-        }
-    }
+#[tauri::command]
+fn get_chat_session(id: String) -> Result<Vec<history::HistoryEntry>, String> {
+    history::read_session(&id)
+}
 
-    Err("No response from Groq".to_string())
+#[tauri::command]
+fn search_chat_history(query: String) -> Result<Vec<history::SearchHit>, String> {
+    history::search(&query)
 }
 
 #[tauri::command]
-fn create_overlay(width: f64, height: f64) {
-    overlay::create_overlay(width, height);
+fn delete_chat_session(id: String) -> Result<(), String> {
+    history::delete_session(&id)
+}
+
+#[tauri::command]
+fn export_chat_session(id: String, format: history::ExportFormat) -> Result<String, String> {
+    history::export_session(&id, format)
+}
+
+#[tauri::command]
+fn get_settings() -> settings::Settings {
+    settings::current()
+}
+
+#[tauri::command]
+fn update_settings(settings: settings::Settings) -> Result<settings::Settings, String> {
+    settings::replace(settings)
+}
+
+#[tauri::command]
+fn get_chat_provider() -> ai::ProviderConfig {
+    ai::provider_config()
+}
+
+#[tauri::command]
+fn set_chat_provider(config: ai::ProviderConfig) -> Result<(), String> {
+    ai::set_provider_config(config)
+}
+
+#[tauri::command]
+fn create_overlay() {
+    let settings = settings::current();
+    overlay::create_overlay(settings.overlay_width, settings.overlay_height);
     // Start screen monitor to follow active screen
     overlay::start_screen_monitor();
 }
@@ -134,13 +185,28 @@ fn move_overlay_to_active() {
 }
 
 #[tauri::command]
-fn set_groq_api_key(key: String) {
-    overlay::set_groq_api_key(&key);
+fn set_api_key(key: String) -> Result<secrets::ApiKeyStatus, String> {
+    secrets::set_api_key(&key)
 }
 
 #[tauri::command]
-fn submit_chat() {
-    overlay::submit_chat_input();
+fn clear_api_key() -> Result<secrets::ApiKeyStatus, String> {
+    secrets::clear_api_key()
+}
+
+#[tauri::command]
+fn get_api_key_status() -> secrets::ApiKeyStatus {
+    secrets::status()
+}
+
+#[tauri::command]
+async fn validate_api_key() -> Result<(), ai::AiError> {
+    ai::validate_api_key().await
+}
+
+#[tauri::command]
+fn submit_chat() -> Result<(), String> {
+    overlay::submit_chat_input()
 }
 
 #[tauri::command]
@@ -148,6 +214,18 @@ fn show_chat() {
     overlay::show_chat_input();
 }
 
+#[tauri::command]
+fn get_chat_state() -> chat_state::ChatState {
+    chat_state::current()
+}
+
+#[tauri::command]
+fn cancel_chat() {
+    // Abandon whatever either chat is waiting on
+    overlay::cancel_chat();
+    ai::cancel("main");
+}
+
 #[tauri::command]
 fn hide_chat() {
     overlay::hide_chat_input();
@@ -157,6 +235,14 @@ fn hide_chat() {
 pub fn run() {
     tauri::Builder::default()
         .setup(|app| {
+            events::init(app.handle().clone());
+            history::init(app.path().app_data_dir()?.join("history"));
+            skins::init(app.path().app_data_dir()?.join("skins"));
+            overlay::cache::init(app.path().app_cache_dir()?.join("frames"));
+            settings::init(app.path().app_config_dir()?);
+            personas::init(app.path().app_config_dir()?);
+            secrets::init(app.path().app_config_dir()?);
+            power::start_monitor();
             if cfg!(debug_assertions) {
                 app.handle().plugin(
                     tauri_plugin_log::Builder::default()
@@ -174,11 +260,43 @@ pub fn run() {
             get_overlay_visible,
             move_overlay_to_active,
             ask_ai,
-            set_groq_api_key,
+            get_settings,
+            update_settings,
+            get_chat_provider,
+            set_chat_provider,
+            reset_conversation,
+            get_conversation,
+            list_personas,
+            save_persona,
+            delete_persona,
+            get_persona_greeting,
+            set_conversation_persona,
+            list_skins,
+            refresh_skins,
+            set_skin,
+            start_focus_session,
+            stop_focus_session,
+            get_focus_session,
+            list_chat_sessions,
+            get_chat_session,
+            search_chat_history,
+            delete_chat_session,
+            export_chat_session,
+            set_api_key,
+            clear_api_key,
+            get_api_key_status,
+            validate_api_key,
             submit_chat,
             show_chat,
-            hide_chat
+            hide_chat,
+            cancel_chat,
+            get_chat_state
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_app, event| {
+            if let tauri::RunEvent::Exit = event {
+                tasks::shutdown();
+            }
+        });
 }
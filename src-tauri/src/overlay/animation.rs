@@ -0,0 +1,565 @@
+//! Animation module - The cat's clips and what moves it between them
+//! Clips and transitions come from the active skin's `skin.json`, so a new
+//! clip (sleeping, excited, ...) is an animation file and a few lines of
+//! JSON
+
+use crate::chat_state::ChatState;
+use crate::settings::Settings;
+use crate::skins::Skin;
+use super::frames::{self, Frame};
+use serde::Deserialize;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+// Numbered frames start at 1; probing stops at the first gap or here
+const MAX_SEQUENCE_FRAMES: usize = 100;
+
+// Frame delay of a clip when neither it, its file nor a setting gives one
+const DEFAULT_FRAME_MS: u64 = 80;
+
+// Longest cross-fade a transition may ask for
+const MAX_BLEND_MS: u64 = 2000;
+
+// Placeholder in a clip's `frames` pattern replaced by the frame number
+const FRAME_NUMBER: &str = "{n}";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ClipMode {
+    /// Played through once, then on to `next`
+    Once,
+    /// Played over and over until a transition fires
+    Loop,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Clip {
+    pub name: String,
+    /// Relative to the skin's folder: an animated GIF, APNG or WebP, a
+    /// sprite sheet when `atlas` is set, or numbered images with `{n}` for
+    /// the frame number, e.g. `frames/idle_frame{n}.png`
+    pub frames: String,
+    /// JSON atlas of the sprite sheet in `frames`
+    #[serde(default)]
+    pub atlas: Option<String>,
+    /// Delay between frames, overriding the durations in the file
+    #[serde(default)]
+    pub frame_ms: Option<u64>,
+    /// The setting the delay between frames follows instead, overriding the
+    /// durations in the file so changing it always shows
+    #[serde(default)]
+    pub delay_setting: Option<DelaySetting>,
+    pub mode: ClipMode,
+    /// The clip a `once` clip hands over to
+    #[serde(default)]
+    pub next: Option<String>,
+}
+
+/// A frame delay the user can change, named by a clip's `delaySetting`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum DelaySetting {
+    #[serde(rename = "spawnDelayMs")]
+    Spawn,
+    #[serde(rename = "yawnDelayMs")]
+    Yawn,
+    #[serde(rename = "idleDelayMs")]
+    Idle,
+}
+
+impl DelaySetting {
+    fn ms(self, settings: &Settings) -> u64 {
+        match self {
+            DelaySetting::Spawn => settings.spawn_delay_ms,
+            DelaySetting::Yawn => settings.yawn_delay_ms,
+            DelaySetting::Idle => settings.idle_delay_ms,
+        }
+    }
+}
+
+/// Something that happened to the cat, see `Transition`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Trigger {
+    /// The cat was clicked
+    Click,
+    /// The chat input opened
+    Listening,
+    /// A question was sent
+    Thinking,
+    /// The reply started coming in
+    Responding,
+    /// The question failed and the error is shown
+    Error,
+    /// The chat closed, back to just the cat
+    ChatIdle,
+    /// Nothing else happened for `afterMs` in the current clip
+    IdleTimeout,
+}
+
+impl Trigger {
+    /// What entering a chat state tells the animation, if anything
+    pub fn for_chat_state(state: ChatState) -> Option<Trigger> {
+        match state {
+            ChatState::Idle => Some(Trigger::ChatIdle),
+            ChatState::InputOpen => Some(Trigger::Listening),
+            ChatState::Thinking => Some(Trigger::Thinking),
+            ChatState::Streaming => Some(Trigger::Responding),
+            ChatState::Error => Some(Trigger::Error),
+            // Still talking while the reply is typed out; cancelling is
+            // followed straight away by idle
+            ChatState::Responding | ChatState::Cancelled => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Transition {
+    pub on: Trigger,
+    /// Clips it applies in; any clip if empty
+    #[serde(default)]
+    pub from: Vec<String>,
+    pub to: String,
+    /// Required for `idleTimeout`, unused otherwise
+    #[serde(default)]
+    pub after_ms: Option<u64>,
+    /// Cross-fade into the new clip over this long instead of cutting to it
+    #[serde(default)]
+    pub blend_ms: u64,
+}
+
+/// A transition that fired
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Switch {
+    pub to: usize,
+    /// 0 to cut straight to the new clip
+    pub blend_ms: u64,
+}
+
+/// The animation part of a skin's manifest
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub start: String,
+    pub clips: Vec<Clip>,
+    #[serde(default)]
+    pub transitions: Vec<Transition>,
+}
+
+impl Manifest {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.clips.is_empty() {
+            return Err("No clips".to_string());
+        }
+        let exists = |name: &str| self.clips.iter().any(|c| c.name == name);
+        let check_clip = |name: &str, what: &str| {
+            if exists(name) {
+                Ok(())
+            } else {
+                Err(format!("Unknown clip {:?} in {}", name, what))
+            }
+        };
+
+        for (i, clip) in self.clips.iter().enumerate() {
+            if clip.name.is_empty() || !clip.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                return Err(format!("Clip name must be letters, digits, '-' or '_': {:?}", clip.name));
+            }
+            if self.clips[..i].iter().any(|c| c.name == clip.name) {
+                return Err(format!("Duplicate clip {:?}", clip.name));
+            }
+            if clip.frames.is_empty() {
+                return Err(format!("Clip {:?} has no frames", clip.name));
+            }
+            if let Some(file) = std::iter::once(&clip.frames).chain(&clip.atlas).find(|file| !stays_inside(file)) {
+                return Err(format!("Clip {:?} names {:?}, which is outside the skin's folder", clip.name, file));
+            }
+            if clip.atlas.is_some() && clip.frames.contains(FRAME_NUMBER) {
+                return Err(format!("Clip {:?} has an atlas, so its frames are one sprite sheet, not {}", clip.name, FRAME_NUMBER));
+            }
+            if let Some(ms) = clip.frame_ms {
+                if !(10..=1000).contains(&ms) {
+                    return Err(format!("frameMs of clip {:?} must be between 10 and 1000 (got {})", clip.name, ms));
+                }
+                if clip.delay_setting.is_some() {
+                    return Err(format!("Clip {:?} has a frameMs and a delaySetting; give it one", clip.name));
+                }
+            }
+            match (clip.mode, &clip.next) {
+                (ClipMode::Once, Some(next)) => check_clip(next, &format!("next of clip {:?}", clip.name))?,
+                (ClipMode::Once, None) => return Err(format!("Clip {:?} plays once but has no next", clip.name)),
+                (ClipMode::Loop, Some(_)) => return Err(format!("Clip {:?} loops, so next is never used", clip.name)),
+                (ClipMode::Loop, None) => {}
+            }
+        }
+        check_clip(&self.start, "start")?;
+
+        for transition in &self.transitions {
+            let what = format!("{:?} transition", transition.on);
+            for from in &transition.from {
+                check_clip(from, &what)?;
+            }
+            check_clip(&transition.to, &what)?;
+            match (transition.on, transition.after_ms) {
+                (Trigger::IdleTimeout, Some(ms)) if ms > 0 => {}
+                (Trigger::IdleTimeout, _) => return Err("idleTimeout transitions need a positive afterMs".to_string()),
+                (_, Some(_)) => return Err(format!("afterMs is only used by idleTimeout, not {}", what)),
+                (_, None) => {}
+            }
+            if transition.blend_ms > MAX_BLEND_MS {
+                return Err(format!("blendMs of {} must be at most {} (got {})", what, MAX_BLEND_MS, transition.blend_ms));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check every clip's files are in `root`, without decoding them
+    pub fn check_files(&self, root: &Path) -> Result<(), String> {
+        for clip in &self.clips {
+            let mut files = vec![clip.frames.replace(FRAME_NUMBER, "1")];
+            files.extend(clip.atlas.clone());
+            if let Some(missing) = files.iter().find(|file| !root.join(file).is_file()) {
+                return Err(format!("Clip {:?} needs {}, which is missing", clip.name, missing));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `file` is relative and can't climb out of the folder it's in
+fn stays_inside(file: &str) -> bool {
+    Path::new(file).components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+/// A validated manifest, with clips referred to by their index
+pub struct AnimationGraph {
+    clips: Vec<Clip>,
+    start: usize,
+    transitions: Vec<Transition>,
+    /// Where the clip files are
+    root: PathBuf,
+}
+
+impl AnimationGraph {
+    /// The graph of a skin, whose manifest has already been validated
+    pub fn new(manifest: Manifest, root: PathBuf) -> Self {
+        let start = manifest.clips.iter().position(|c| c.name == manifest.start).unwrap_or(0);
+        AnimationGraph { clips: manifest.clips, start, transitions: manifest.transitions, root }
+    }
+
+    fn index(&self, name: &str) -> Option<usize> {
+        self.clips.iter().position(|c| c.name == name)
+    }
+
+    pub fn clips(&self) -> &[Clip] {
+        &self.clips
+    }
+
+    /// The clip played when the overlay appears
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn name(&self, clip: usize) -> &str {
+        &self.clips[clip].name
+    }
+
+    /// The first clip drawn from the same files as `clip`; clips sharing a
+    /// file share its frames, which are only loaded for this one
+    pub fn source(&self, clip: usize) -> usize {
+        let Clip { frames, atlas, .. } = &self.clips[clip];
+        self.clips.iter().position(|c| c.frames == *frames && c.atlas == *atlas).unwrap_or(clip)
+    }
+
+    /// Clips whose frames need loading, the start clip and the clips it
+    /// hands over to first so the cat can play while the rest load
+    pub fn load_order(&self) -> Vec<usize> {
+        let mut order = Vec::new();
+        let mut clip = Some(self.start);
+        while let Some(current) = clip.filter(|c| !order.contains(&self.source(*c))) {
+            order.push(self.source(current));
+            clip = self.after_last_frame(current);
+        }
+        for clip in 0..self.clips.len() {
+            if !order.contains(&self.source(clip)) {
+                order.push(self.source(clip));
+            }
+        }
+        order
+    }
+
+    /// Every file a clip's frames come from
+    pub fn files(&self, clip: usize) -> Vec<PathBuf> {
+        let clip = &self.clips[clip];
+        if clip.frames.contains(FRAME_NUMBER) {
+            (1..=MAX_SEQUENCE_FRAMES)
+                .map(|i| self.root.join(clip.frames.replace(FRAME_NUMBER, &i.to_string())))
+                .take_while(|path| path.exists())
+                .collect()
+        } else {
+            let mut files = vec![self.root.join(&clip.frames)];
+            files.extend(clip.atlas.as_ref().map(|atlas| self.root.join(atlas)));
+            files
+        }
+    }
+
+    /// Decode a clip's frames, passing each to `each` as it is ready
+    /// A file that can't be decoded ends the clip early with an error
+    pub fn decode(&self, index: usize, each: impl FnMut(Frame)) -> Result<(), String> {
+        let clip = &self.clips[index];
+        if clip.frames.contains(FRAME_NUMBER) {
+            frames::load_sequence(&self.files(index), each);
+            Ok(())
+        } else if let Some(atlas) = &clip.atlas {
+            frames::load_sprite_sheet(&self.root.join(&clip.frames), &self.root.join(atlas), each).map(|_| ())
+        } else {
+            frames::load_animation(&self.root.join(&clip.frames), each).map(|_| ())
+        }
+    }
+
+    /// Delay after a frame whose file asked for `file_ms`: the clip's
+    /// `delaySetting`, else its `frameMs`, else `file_ms`, else
+    /// `DEFAULT_FRAME_MS` (read every frame so settings apply live)
+    pub fn frame_ms(&self, clip: usize, file_ms: Option<u64>) -> u64 {
+        let clip = &self.clips[clip];
+        match clip.delay_setting {
+            Some(setting) => setting.ms(&crate::settings::current()),
+            None => clip.frame_ms.or(file_ms).unwrap_or(DEFAULT_FRAME_MS),
+        }
+    }
+
+    /// Where a clip goes after its last frame; `None` to start it over
+    pub fn after_last_frame(&self, clip: usize) -> Option<usize> {
+        self.clips[clip].next.as_deref().and_then(|next| self.index(next))
+    }
+
+    /// Where `trigger` takes the cat from `clip`, if anywhere
+    /// The first matching transition in the file wins
+    pub fn on_trigger(&self, clip: usize, trigger: Trigger) -> Option<Switch> {
+        self.first_transition(clip, |t| t.on == trigger && trigger != Trigger::IdleTimeout)
+    }
+
+    /// Where the cat goes after `idle_ms` in `clip` with nothing happening
+    pub fn on_idle(&self, clip: usize, idle_ms: u64) -> Option<Switch> {
+        self.first_transition(clip, |t| {
+            t.on == Trigger::IdleTimeout && t.after_ms.is_some_and(|after| idle_ms >= after)
+        })
+    }
+
+    fn first_transition(&self, clip: usize, matches: impl Fn(&Transition) -> bool) -> Option<Switch> {
+        let name = self.name(clip);
+        let transition = self
+            .transitions
+            .iter()
+            .filter(|t| t.from.is_empty() || t.from.iter().any(|from| from == name))
+            .find(|t| matches(t))?;
+        Some(Switch { to: self.index(&transition.to)?, blend_ms: transition.blend_ms })
+    }
+}
+
+// The graph in use, set by `load`
+static GRAPH: Mutex<Option<Arc<AnimationGraph>>> = Mutex::new(None);
+
+/// Build the graph of `skin` and use it from now on
+pub fn load(skin: &Skin) -> Arc<AnimationGraph> {
+    let graph = Arc::new(AnimationGraph::new(skin.manifest.animations.clone(), skin.root.clone()));
+    log::info!("Loaded {} animation clips of the {} skin", graph.clips.len(), skin.manifest.id);
+    *GRAPH.lock().unwrap() = Some(graph.clone());
+    graph
+}
+
+/// The graph in use, loading the overlay's skin on first use
+pub fn graph() -> Arc<AnimationGraph> {
+    let loaded = GRAPH.lock().unwrap().clone();
+    loaded.unwrap_or_else(|| load(&super::skin()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest() -> Manifest {
+        serde_json::from_str(
+            r#"{
+                "start": "spawn",
+                "clips": [
+                    { "name": "spawn", "frames": "clips/spawn.gif", "mode": "once", "next": "idle" },
+                    { "name": "idle", "frames": "clips/idle.gif", "mode": "loop" },
+                    { "name": "listening", "frames": "clips/idle.gif", "frameMs": 110, "mode": "loop" },
+                    { "name": "sad", "frames": "sad/frame{n}.png", "mode": "loop" }
+                ],
+                "transitions": [
+                    { "on": "listening", "from": ["idle"], "to": "listening", "blendMs": 200 },
+                    { "on": "idleTimeout", "afterMs": 1000, "from": ["idle"], "to": "spawn" }
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    /// A graph whose clips are listed out of playing order, some sharing files
+    fn sample_graph() -> AnimationGraph {
+        let manifest: Manifest = serde_json::from_str(
+            r#"{
+                "start": "spawn",
+                "clips": [
+                    { "name": "sad", "frames": "sad.gif", "mode": "loop" },
+                    { "name": "idle", "frames": "idle.gif", "delaySetting": "idleDelayMs", "mode": "loop" },
+                    { "name": "listening", "frames": "idle.gif", "frameMs": 110, "mode": "loop" },
+                    { "name": "thinking", "frames": "yawn.gif", "mode": "loop" },
+                    { "name": "spawn", "frames": "spawn.gif", "mode": "once", "next": "yawn" },
+                    { "name": "yawn", "frames": "yawn.gif", "mode": "once", "next": "idle" }
+                ],
+                "transitions": [
+                    { "on": "click", "from": ["idle"], "to": "listening", "blendMs": 100 },
+                    { "on": "click", "to": "sad" },
+                    { "on": "error", "to": "sad", "blendMs": 250 },
+                    { "on": "idleTimeout", "afterMs": 1000, "from": ["idle"], "to": "yawn" },
+                    { "on": "idleTimeout", "afterMs": 3000, "to": "spawn" }
+                ]
+            }"#,
+        )
+        .unwrap();
+        manifest.validate().unwrap();
+        AnimationGraph::new(manifest, PathBuf::new())
+    }
+
+    #[test]
+    fn triggers() {
+        let graph = sample_graph();
+        let clip = |name| graph.index(name).unwrap();
+        let switch = |to, blend_ms| Some(Switch { to: clip(to), blend_ms });
+        let cases = [
+            // The first transition that matches wins
+            ("idle", Trigger::Click, switch("listening", 100)),
+            // No `from` matches any clip
+            ("spawn", Trigger::Click, switch("sad", 0)),
+            ("sad", Trigger::Click, switch("sad", 0)),
+            ("listening", Trigger::Error, switch("sad", 250)),
+            ("idle", Trigger::Thinking, None),
+            // Idle timeouts only fire through `on_idle`
+            ("idle", Trigger::IdleTimeout, None),
+        ];
+        for (from, trigger, expected) in cases {
+            assert_eq!(graph.on_trigger(clip(from), trigger), expected, "{:?} in {}", trigger, from);
+        }
+    }
+
+    #[test]
+    fn idle_timeouts() {
+        let graph = sample_graph();
+        let clip = |name| graph.index(name).unwrap();
+        let switch = |to| Some(Switch { to: clip(to), blend_ms: 0 });
+        let cases = [
+            ("idle", 0, None),
+            ("idle", 999, None),
+            ("idle", 1000, switch("yawn")),
+            // Past the later timeout too, but the first one listed wins
+            ("idle", 5000, switch("yawn")),
+            ("sad", 2999, None),
+            ("sad", 3000, switch("spawn")),
+        ];
+        for (from, idle_ms, expected) in cases {
+            assert_eq!(graph.on_idle(clip(from), idle_ms), expected, "{}ms in {}", idle_ms, from);
+        }
+    }
+
+    #[test]
+    fn clips_share_the_frames_of_the_first_with_their_files() {
+        let graph = sample_graph();
+        let clip = |name| graph.index(name).unwrap();
+        assert_eq!(graph.source(clip("listening")), clip("idle"));
+        assert_eq!(graph.source(clip("thinking")), clip("thinking"));
+        assert_eq!(graph.source(clip("yawn")), clip("thinking"));
+        assert_eq!(graph.after_last_frame(clip("spawn")), Some(clip("yawn")));
+        assert_eq!(graph.after_last_frame(clip("idle")), None);
+    }
+
+    #[test]
+    fn frame_delays_prefer_the_setting_then_the_clip_then_the_file() {
+        let _globals = crate::lock_globals();
+        crate::settings::set_current(Settings { idle_delay_ms: 120, ..Settings::default() });
+        let graph = sample_graph();
+        let clip = |name| graph.index(name).unwrap();
+        let cases = [
+            ("idle", Some(40), 120),
+            ("idle", None, 120),
+            ("listening", Some(40), 110),
+            ("sad", Some(40), 40),
+            ("sad", None, DEFAULT_FRAME_MS),
+        ];
+        for (name, file_ms, expected) in cases {
+            assert_eq!(graph.frame_ms(clip(name), file_ms), expected, "{} with {:?} from the file", name, file_ms);
+        }
+
+        // Read live
+        crate::settings::set_current(Settings::default());
+        assert_eq!(graph.frame_ms(clip("idle"), Some(40)), Settings::default().idle_delay_ms);
+    }
+
+    #[test]
+    fn load_order_starts_with_the_start_chain() {
+        let graph = sample_graph();
+        let names: Vec<_> = graph.load_order().into_iter().map(|clip| graph.name(clip)).collect();
+        // Yawn's frames are thinking's; each file is loaded once
+        assert_eq!(names, ["spawn", "thinking", "idle", "sad"]);
+    }
+
+    /// Why `manifest` with `change` made to it is invalid
+    fn invalid(change: impl FnOnce(&mut Manifest)) -> String {
+        let mut manifest = manifest();
+        change(&mut manifest);
+        manifest.validate().expect_err("manifest should be invalid")
+    }
+
+    #[test]
+    fn valid_manifest() {
+        manifest().validate().unwrap();
+    }
+
+    #[test]
+    fn frame_ms_and_a_delay_setting_are_exclusive() {
+        let error = invalid(|m| m.clips[2].delay_setting = Some(DelaySetting::Idle));
+        assert_eq!(error, "Clip \"listening\" has a frameMs and a delaySetting; give it one");
+    }
+
+    #[test]
+    fn duplicate_clips_are_rejected() {
+        let error = invalid(|m| m.clips.push(m.clips[1].clone()));
+        assert_eq!(error, "Duplicate clip \"idle\"");
+    }
+
+    #[test]
+    fn unknown_clips_are_rejected() {
+        let error = invalid(|m| m.clips[0].next = Some("nap".to_string()));
+        assert_eq!(error, "Unknown clip \"nap\" in next of clip \"spawn\"");
+        let error = invalid(|m| m.transitions[0].to = "nap".to_string());
+        assert_eq!(error, "Unknown clip \"nap\" in Listening transition");
+        let error = invalid(|m| m.transitions[0].from.push("nap".to_string()));
+        assert_eq!(error, "Unknown clip \"nap\" in Listening transition");
+        let error = invalid(|m| m.start = "nap".to_string());
+        assert_eq!(error, "Unknown clip \"nap\" in start");
+    }
+
+    #[test]
+    fn after_ms_is_only_for_idle_timeouts() {
+        let error = invalid(|m| m.transitions[0].after_ms = Some(500));
+        assert_eq!(error, "afterMs is only used by idleTimeout, not Listening transition");
+        let error = invalid(|m| m.transitions[1].after_ms = None);
+        assert_eq!(error, "idleTimeout transitions need a positive afterMs");
+    }
+
+    #[test]
+    fn files_must_stay_in_the_skin_folder() {
+        for file in ["../../x.png", "/etc/x.png", "clips/../../x.png"] {
+            let error = invalid(|m| m.clips[1].frames = file.to_string());
+            assert!(error.contains("outside the skin's folder"), "{}: {}", file, error);
+        }
+        let error = invalid(|m| m.clips[1].atlas = Some("../atlas.json".to_string()));
+        assert!(error.contains("outside the skin's folder"), "{}", error);
+
+        let mut manifest = manifest();
+        manifest.clips[1].frames = "./clips/idle.gif".to_string();
+        manifest.validate().unwrap();
+    }
+}
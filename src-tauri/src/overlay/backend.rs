@@ -0,0 +1,144 @@
+//! Overlay backend - What the overlay core needs from a window system
+//! Coordinates are screen points with the origin at the bottom-left of the
+//! primary screen and y growing upwards, as in AppKit; backends for window
+//! systems with a top-left origin flip them at the boundary
+
+use super::frames::Frame;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Point {
+    pub fn new(x: f64, y: f64) -> Self {
+        Point { x, y }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    pub fn new(x: f64, y: f64, width: f64, height: f64) -> Self {
+        Rect { x, y, width, height }
+    }
+
+    /// Whether `point` is inside, edges included
+    pub fn contains(&self, point: Point) -> bool {
+        point.x >= self.x
+            && point.x <= self.x + self.width
+            && point.y >= self.y
+            && point.y <= self.y + self.height
+    }
+}
+
+/// Keys the overlay reacts to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverlayKey {
+    Enter,
+    Escape,
+}
+
+/// Window-system changes that may put the overlay on another screen
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScreenEvent {
+    /// Screens were added, removed, resized or rearranged, or another space
+    /// became active; the panel is placed afresh
+    Rearranged,
+    /// Focus moved to another window
+    FocusMoved,
+}
+
+/// A window system the overlay can be drawn with
+/// Methods may be called from any thread; a backend whose toolkit needs the
+/// main thread for some calls hops there itself
+pub trait OverlayBackend: Send + Sync {
+    /// Short name for logs
+    fn name(&self) -> &'static str;
+
+    /// Frame of the screen the pointer is on
+    fn active_screen(&self) -> Rect;
+
+    /// Frame of the screen the focused window is mostly on, `None` if the
+    /// window system doesn't say
+    fn focused_screen(&self) -> Option<Rect>;
+
+    /// Pixels per point of the sharpest screen; frames come scaled to the
+    /// sprite size times this
+    fn scale_factor(&self) -> f64;
+
+    /// Append frames to a clip, already scaled (see `scale_factor`),
+    /// stopping at the first the backend can't use
+    /// Clips are numbered by their place in the animation graph; clips drawn
+    /// from the same files only get frames under the first of them
+    /// Returns how many frames the clip has now
+    fn add_frames(&self, clip: usize, frames: &[Frame]) -> usize;
+
+    /// How many frames of `clip` are loaded
+    fn frame_count(&self, clip: usize) -> usize;
+
+    /// Create and show the panel at `frame`, with no sprite until the first
+    /// `show_frame`, the chat widgets hidden and clicks passing through
+    fn create_panel(&self, frame: Rect) -> Result<(), String>;
+
+    /// Close the panel and drop the loaded frames
+    fn close_panel(&self);
+
+    fn show_panel(&self);
+    fn hide_panel(&self);
+    fn is_panel_visible(&self) -> bool;
+
+    /// Whether the shown panel can't be seen at all, e.g. covered by a
+    /// full-screen window or on a locked or sleeping display
+    fn is_panel_occluded(&self) -> bool;
+
+    /// The panel's frame, `None` if there is no panel
+    fn panel_frame(&self) -> Option<Rect>;
+
+    /// Move the panel's bottom-left corner to `origin`, keeping it on top
+    fn move_panel(&self, origin: Point);
+
+    /// Display frame `index` of `clip` (always below `frame_count`)
+    fn show_frame(&self, clip: usize, index: usize);
+
+    /// Cross-fade from the frame shown now to the ones that follow over
+    /// `duration_ms`, for a smooth switch between clips
+    fn fade_frames(&self, duration_ms: u64);
+
+    /// Quick bounce of the cat when it is clicked
+    fn play_click_feedback(&self);
+
+    /// Switch between the pointing hand (over the cat) and the arrow
+    fn set_hand_cursor(&self, hand: bool);
+
+    /// Open and focus the chat input, showing `placeholder` while it is empty
+    fn show_chat_input(&self, placeholder: &str);
+    fn hide_chat_input(&self);
+
+    /// The typed question, clearing the input; empty if nothing was typed
+    fn take_chat_input(&self) -> String;
+
+    fn show_thinking(&self);
+    fn hide_thinking(&self);
+
+    /// Show the response box, empty
+    fn show_response(&self);
+    fn set_response_text(&self, text: &str);
+
+    /// Hide the response box and let clicks pass through the panel again
+    fn hide_response(&self);
+
+    /// Start delivering clicks, pointer moves, key presses and screen changes
+    /// to `overlay::handle_click`, `handle_pointer_move`, `handle_key` and
+    /// `handle_screen_event`
+    fn start_input_monitor(&self);
+    fn stop_input_monitor(&self);
+}
+
@@ -0,0 +1,256 @@
+//! Frame cache - Clips decoded and scaled once, kept on disk
+//! Decoding a clip and scaling it to the sprite size is most of the
+//! overlay's startup. The scaled frames are stored in the app's cache dir
+//! under a hash of the clip's files and the pixel size, so later starts (and
+//! skins switched back to) only read raw pixels. A changed file hashes
+//! differently, so a stale entry is never read, just pruned once unused.
+
+use super::frames::Frame;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+// Bumped whenever the file layout or the scaling changes
+const CACHE_VERSION: u32 = 1;
+const MAGIC: &[u8; 8] = b"CATFRAME";
+const EXTENSION: &str = "frames";
+// Entries being written, renamed to `EXTENSION` when complete
+const PARTIAL_EXTENSION: &str = "partial";
+
+// Entries not read for this long are deleted at startup
+const MAX_UNUSED: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+// Where entries are stored, set by `init`; nothing is cached without it
+static CACHE_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Cache frames in `dir`, deleting entries that haven't been used in a while
+pub fn init(dir: PathBuf) {
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        log::warn!("Failed to create {:?}, frames won't be cached: {}", dir, e);
+        return;
+    }
+    prune(&dir);
+    *CACHE_DIR.lock().unwrap() = Some(dir);
+}
+
+fn prune(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let now = SystemTime::now();
+    for path in entries.flatten().map(|entry| entry.path()) {
+        let unused = std::fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok());
+        if path.extension().is_some_and(|ext| ext == PARTIAL_EXTENSION) {
+            // Left by a crash while writing; never read
+            log::info!("Removing unfinished frame cache {:?}", path);
+            let _ = std::fs::remove_file(&path);
+        } else if path.extension().is_some_and(|ext| ext == EXTENSION) && unused.is_some_and(|age| age > MAX_UNUSED) {
+            log::info!("Removing unused frame cache {:?}", path);
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+}
+
+/// Key of the frames decoded from `files` and scaled to `size` pixels;
+/// `None` if a file can't be read
+pub fn key(files: &[PathBuf], size: (usize, usize)) -> Option<String> {
+    let mut hasher = Sha256::new();
+    hasher.update(CACHE_VERSION.to_le_bytes());
+    hasher.update((size.0 as u64).to_le_bytes());
+    hasher.update((size.1 as u64).to_le_bytes());
+    for file in files {
+        let contents = std::fs::read(file).ok()?;
+        hasher.update((contents.len() as u64).to_le_bytes());
+        hasher.update(&contents);
+    }
+    Some(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+fn entry_path(key: &str) -> Option<PathBuf> {
+    let dir = CACHE_DIR.lock().unwrap().clone()?;
+    Some(dir.join(key).with_extension(EXTENSION))
+}
+
+/// The frames stored under `key`, `None` if there are none or the entry is
+/// unreadable
+pub fn read(key: &str) -> Option<Vec<Frame>> {
+    let path = entry_path(key)?;
+    let contents = std::fs::read(&path).ok()?;
+    let frames = parse(&contents);
+    if frames.is_none() {
+        log::warn!("Ignoring corrupt frame cache {:?}", path);
+        let _ = std::fs::remove_file(&path);
+        return None;
+    }
+    // Mark it used so pruning keeps it
+    let _ = std::fs::File::options()
+        .write(true)
+        .open(&path)
+        .and_then(|file| file.set_modified(SystemTime::now()));
+    frames
+}
+
+/// Store `frames` under `key`, replacing any entry there
+pub fn write(key: &str, frames: &[Frame]) {
+    let Some(path) = entry_path(key) else {
+        return;
+    };
+    let mut contents = Vec::with_capacity(16 + frames.iter().map(|f| 16 + f.rgba.len()).sum::<usize>());
+    contents.extend_from_slice(MAGIC);
+    contents.extend_from_slice(&CACHE_VERSION.to_le_bytes());
+    contents.extend_from_slice(&(frames.len() as u32).to_le_bytes());
+    for frame in frames {
+        contents.extend_from_slice(&(frame.width as u32).to_le_bytes());
+        contents.extend_from_slice(&(frame.height as u32).to_le_bytes());
+        contents.extend_from_slice(&frame.duration_ms.unwrap_or(0).to_le_bytes());
+        contents.extend_from_slice(&frame.rgba);
+    }
+
+    // Written aside and renamed, so a crash never leaves half an entry
+    let partial = path.with_extension(PARTIAL_EXTENSION);
+    let written = std::fs::write(&partial, &contents).and_then(|_| std::fs::rename(&partial, &path));
+    if let Err(e) = written {
+        log::warn!("Failed to write frame cache {:?}: {}", path, e);
+        let _ = std::fs::remove_file(&partial);
+    }
+}
+
+fn parse(contents: &[u8]) -> Option<Vec<Frame>> {
+    let mut rest = contents.strip_prefix(MAGIC.as_slice())?;
+    let mut take = |n: usize| {
+        if rest.len() < n {
+            return None;
+        }
+        let (head, tail) = rest.split_at(n);
+        rest = tail;
+        Some(head)
+    };
+    let u32_at = |bytes: &[u8]| u32::from_le_bytes(bytes.try_into().unwrap());
+
+    if u32_at(take(4)?) != CACHE_VERSION {
+        return None;
+    }
+    let count = u32_at(take(4)?) as usize;
+    let mut frames = Vec::with_capacity(count.min(128));
+    for _ in 0..count {
+        let width = u32_at(take(4)?) as usize;
+        let height = u32_at(take(4)?) as usize;
+        let duration_ms = u64::from_le_bytes(take(8)?.try_into().unwrap());
+        let rgba = take(width.checked_mul(height)?.checked_mul(4)?)?.to_vec();
+        frames.push(Frame { width, height, rgba, duration_ms: (duration_ms > 0).then_some(duration_ms) });
+    }
+    Some(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir;
+
+    fn frames() -> Vec<Frame> {
+        vec![
+            Frame { width: 2, height: 1, rgba: (0..8).collect(), duration_ms: Some(120) },
+            Frame { width: 1, height: 2, rgba: (8..16).collect(), duration_ms: None },
+        ]
+    }
+
+    fn summary(frames: &[Frame]) -> Vec<(usize, usize, &[u8], Option<u64>)> {
+        frames.iter().map(|f| (f.width, f.height, f.rgba.as_slice(), f.duration_ms)).collect()
+    }
+
+    /// Run `test` with the cache in an empty folder of its own
+    fn with_cache_dir(name: &str, test: impl FnOnce(&Path)) {
+        let _globals = crate::lock_globals();
+        let dir = temp_dir(name);
+        init(dir.clone());
+        test(&dir);
+        *CACHE_DIR.lock().unwrap() = None;
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn written_frames_read_back_the_same() {
+        with_cache_dir("cache-round-trip", |dir| {
+            assert!(read("clip").is_none());
+            write("clip", &frames());
+            assert_eq!(summary(&read("clip").unwrap()), summary(&frames()));
+            assert!(dir.join("clip.frames").is_file());
+            assert!(!dir.join("clip.partial").exists());
+        });
+    }
+
+    #[test]
+    fn truncated_or_corrupt_entries_are_rejected() {
+        with_cache_dir("cache-corrupt", |dir| {
+            write("clip", &frames());
+            let contents = std::fs::read(dir.join("clip.frames")).unwrap();
+            assert!(parse(&contents).is_some());
+            for len in 0..contents.len() {
+                assert!(parse(&contents[..len]).is_none(), "cut to {} bytes", len);
+            }
+
+            let mut bad_magic = contents.clone();
+            bad_magic[0] = b'D';
+            assert!(parse(&bad_magic).is_none());
+
+            // A frame claiming more pixels than can exist
+            let mut huge = contents.clone();
+            huge[16..24].copy_from_slice(&[0xff; 8]);
+            assert!(parse(&huge).is_none());
+
+            // A corrupt entry is dropped on read
+            std::fs::write(dir.join("clip.frames"), &contents[..20]).unwrap();
+            assert!(read("clip").is_none());
+            assert!(!dir.join("clip.frames").exists());
+        });
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        with_cache_dir("cache-version", |dir| {
+            write("clip", &frames());
+            let mut contents = std::fs::read(dir.join("clip.frames")).unwrap();
+            contents[8..12].copy_from_slice(&(CACHE_VERSION + 1).to_le_bytes());
+            assert!(parse(&contents).is_none());
+        });
+    }
+
+    #[test]
+    fn key_follows_the_files_and_size() {
+        let dir = temp_dir("cache-key");
+        let files = [dir.join("a.gif"), dir.join("b.gif")];
+        std::fs::write(&files[0], b"first").unwrap();
+        std::fs::write(&files[1], b"second").unwrap();
+
+        let first = key(&files, (64, 64)).unwrap();
+        assert_eq!(key(&files, (64, 64)).unwrap(), first);
+        assert_ne!(key(&files, (64, 32)).unwrap(), first);
+        assert_ne!(key(&files[..1], (64, 64)).unwrap(), first);
+
+        std::fs::write(&files[1], b"secont").unwrap();
+        assert_ne!(key(&files, (64, 64)).unwrap(), first);
+        // The same bytes split differently between the files
+        std::fs::write(&files[0], b"firsts").unwrap();
+        std::fs::write(&files[1], b"econd").unwrap();
+        assert_ne!(key(&files, (64, 64)).unwrap(), first);
+
+        std::fs::remove_file(&files[1]).unwrap();
+        assert_eq!(key(&files, (64, 64)), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn pruning_removes_unfinished_entries() {
+        with_cache_dir("cache-prune", |dir| {
+            write("kept", &frames());
+            std::fs::write(dir.join("crashed.partial"), b"CATFRAME").unwrap();
+            prune(dir);
+            assert!(dir.join("kept.frames").is_file());
+            assert!(!dir.join("crashed.partial").exists());
+        });
+    }
+}
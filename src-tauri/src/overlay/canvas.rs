@@ -0,0 +1,276 @@
+//! Canvas - Software drawing for backends that push whole pixel buffers
+//! Pixels are premultiplied ARGB in native-endian `u32`s, the layout both
+//! 32-bit X11 visuals and Wayland ARGB8888 shm buffers expect. The panel
+//! layout lives here too, so every software-drawn backend looks the same;
+//! only text is left to the backend's own font rendering.
+
+use super::backend::Rect;
+use super::frames::Frame;
+use std::time::{Duration, Instant};
+
+// Widget sizes, matching the Cocoa backend
+const THINKING_SIZE: (f64, f64) = (120.0, 35.0);
+const RESPONSE_SIZE: (f64, f64) = (300.0, 120.0);
+const SEND_BUTTON_SIZE: f64 = 40.0;
+pub const RESPONSE_PADDING: f64 = 12.0;
+pub const CHAT_SIZE: (f64, f64) = (400.0, 56.0);
+pub const CHAT_PADDING: f64 = 24.0;
+pub const THINKING_TEXT: &str = "Thinking...";
+
+/// How long the cat stays squashed after a click
+pub const CLICK_FEEDBACK: Duration = Duration::from_millis(150);
+const CLICK_FEEDBACK_SCALE: f64 = 0.92;
+
+const WHITE: u32 = 0xffff_ffff;
+const BLACK: u32 = 0xff00_0000;
+const BORDER_GREY: u32 = 0xffe6_e6e6;
+
+/// Premultiplied ARGB pixel from straight 0-255 components
+fn argb(a: u8, r: u8, g: u8, b: u8) -> u32 {
+    let premultiply = |c: u8| (c as u32 * a as u32 + 127) / 255;
+    (a as u32) << 24 | premultiply(r) << 16 | premultiply(g) << 8 | premultiply(b)
+}
+
+/// Composite `src` over `dst`, with `src` weakened by `coverage` (0-255)
+fn blend(dst: u32, src: u32, coverage: u32) -> u32 {
+    let scale = |c: u32| (c * coverage + 127) / 255;
+    let channel = |shift: u32| {
+        let s = scale((src >> shift) & 0xff);
+        let d = (dst >> shift) & 0xff;
+        s + (d * (255 - scale(src >> 24)) + 127) / 255
+    };
+    channel(24) << 24 | channel(16) << 16 | channel(8) << 8 | channel(0)
+}
+
+/// A cross-fade from the clip and frame shown when it started to whatever
+/// is shown since
+pub struct Fade {
+    pub from: (usize, usize),
+    started: Instant,
+    duration: Duration,
+}
+
+impl Fade {
+    pub fn new(from: (usize, usize), duration_ms: u64) -> Self {
+        Fade { from, started: Instant::now(), duration: Duration::from_millis(duration_ms) }
+    }
+
+    /// How far along it is (0-1), `None` once it is over
+    pub fn progress(&self) -> Option<f64> {
+        let amount = self.started.elapsed().as_secs_f64() / self.duration.as_secs_f64();
+        (amount < 1.0).then_some(amount)
+    }
+}
+
+/// An image ready to be drawn onto a `Canvas`
+pub struct Sprite {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl Sprite {
+    /// A decoded frame, drawn at its own size
+    pub fn from_frame(frame: &Frame) -> Sprite {
+        let pixels = frame.rgba.chunks_exact(4).map(|p| argb(p[3], p[0], p[1], p[2])).collect();
+        Sprite { width: frame.width, height: frame.height, pixels }
+    }
+
+    /// `amount` (0-1) of the way from `self` to `other`; `None` if their
+    /// sizes differ
+    pub fn mix(&self, other: &Sprite, amount: f64) -> Option<Sprite> {
+        if (self.width, self.height) != (other.width, other.height) {
+            return None;
+        }
+        let weight = (amount.clamp(0.0, 1.0) * 255.0).round() as u32;
+        let channel = |from: u32, to: u32, shift: u32| {
+            let (from, to) = ((from >> shift) & 0xff, (to >> shift) & 0xff);
+            ((from * (255 - weight) + to * weight + 127) / 255) << shift
+        };
+        let pixels = self
+            .pixels
+            .iter()
+            .zip(&other.pixels)
+            .map(|(&from, &to)| channel(from, to, 24) | channel(from, to, 16) | channel(from, to, 8) | channel(from, to, 0))
+            .collect();
+        Some(Sprite { width: self.width, height: self.height, pixels })
+    }
+}
+
+/// Where the panel's widgets sit, top-left origin, in a panel of the given size
+pub struct PanelLayout {
+    pub cat: Rect,
+    /// Where clicks count as clicking the cat (the skin's hit-box)
+    pub hit_box: Rect,
+    pub thinking: Rect,
+    pub response: Rect,
+}
+
+impl PanelLayout {
+    pub fn new(width: f64, height: f64) -> Self {
+        // The core's frame has a bottom-left origin
+        let cat = super::cat_frame(width);
+        let cat_top = cat.y + cat.height;
+        let hit_box = super::skin().manifest.hit_box();
+        // Centred, `bottom` points above the panel's bottom edge
+        let widget = |size: (f64, f64), bottom: f64| {
+            Rect::new((width - size.0) / 2.0, height - bottom - size.1, size.0, size.1)
+        };
+        PanelLayout {
+            cat: Rect::new(cat.x, height - cat_top, cat.width, cat.height),
+            hit_box: Rect::new(cat.x + hit_box.x, height - cat_top + hit_box.y, hit_box.width, hit_box.height),
+            thinking: widget(THINKING_SIZE, cat_top + 10.0),
+            response: widget(RESPONSE_SIZE, cat_top + 10.0),
+        }
+    }
+}
+
+/// The chat input's send button, in the chat window
+pub fn send_button() -> Rect {
+    Rect::new(
+        CHAT_SIZE.0 - SEND_BUTTON_SIZE - 8.0,
+        (CHAT_SIZE.1 - SEND_BUTTON_SIZE) / 2.0,
+        SEND_BUTTON_SIZE,
+        SEND_BUTTON_SIZE,
+    )
+}
+
+/// Width of the chat input's text, left of the send button
+pub fn chat_field_width() -> f64 {
+    CHAT_SIZE.0 - CHAT_PADDING * 2.0 - (SEND_BUTTON_SIZE + 16.0)
+}
+
+/// Split `text` into lines no wider than `width`, breaking at spaces
+pub fn wrap_text(text: &str, width: f64, measure: impl Fn(&str) -> f64) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        for word in paragraph.split(' ') {
+            let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+            if !line.is_empty() && measure(&candidate) > width {
+                lines.push(std::mem::replace(&mut line, word.to_string()));
+            } else {
+                line = candidate;
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// The end of `text` that fits in `width`, like a text field scrolled to the caret
+pub fn tail_that_fits(text: &str, width: f64, measure: impl Fn(&str) -> f64) -> &str {
+    let mut shown = text;
+    while measure(shown) > width {
+        let mut chars = shown.chars();
+        chars.next();
+        shown = chars.as_str();
+    }
+    shown
+}
+
+/// A transparent pixel buffer to draw a window's contents into
+pub struct Canvas {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl Canvas {
+    pub fn new(width: usize, height: usize) -> Self {
+        Canvas { width, height, pixels: vec![0; width * height] }
+    }
+
+    /// The panel without its text: the cat (squashed while `pressed`) and
+    /// the response box's background when shown
+    pub fn panel(width: f64, height: f64, sprite: Option<&Sprite>, pressed: bool, response: bool) -> Self {
+        let mut canvas = Canvas::new(width.round() as usize, height.round() as usize);
+        let layout = PanelLayout::new(width, height);
+        if let Some(sprite) = sprite {
+            let scale = if pressed { CLICK_FEEDBACK_SCALE } else { 1.0 };
+            canvas.draw_sprite(sprite, layout.cat.x as i64, layout.cat.y as i64, scale);
+        }
+        if response {
+            canvas.fill_bordered_rect(layout.response, 20.0, WHITE, BORDER_GREY);
+        }
+        canvas
+    }
+
+    /// The chat input without its text: a white pill with a round send button
+    pub fn chat() -> Self {
+        let (width, height) = CHAT_SIZE;
+        let mut canvas = Canvas::new(width as usize, height as usize);
+        canvas.fill_bordered_rect(Rect::new(0.0, 0.0, width, height), height / 2.0, WHITE, BORDER_GREY);
+        let button = send_button();
+        canvas.fill_rounded_rect(button, button.width / 2.0, BLACK);
+        let arrow_top = (button.y + button.height / 4.0) as i64;
+        canvas.fill_up_arrow((button.x + button.width / 2.0) as i64, arrow_top, (button.height / 2.0) as i64, WHITE);
+        canvas
+    }
+
+    fn blend_at(&mut self, x: i64, y: i64, color: u32, coverage: u32) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 || coverage == 0 {
+            return;
+        }
+        let index = y as usize * self.width + x as usize;
+        self.pixels[index] = blend(self.pixels[index], color, coverage);
+    }
+
+    /// Draw `sprite` with its top-left corner at (`x`, `y`), scaled by `scale`
+    /// around its bottom centre (nearest neighbour, for the click bounce)
+    pub fn draw_sprite(&mut self, sprite: &Sprite, x: i64, y: i64, scale: f64) {
+        let width = (sprite.width as f64 * scale).round() as i64;
+        let height = (sprite.height as f64 * scale).round() as i64;
+        let left = x + (sprite.width as i64 - width) / 2;
+        let top = y + sprite.height as i64 - height;
+        for dy in 0..height {
+            let sy = (dy as f64 / scale) as usize;
+            for dx in 0..width {
+                let sx = (dx as f64 / scale) as usize;
+                if let Some(&pixel) = sprite.pixels.get(sy.min(sprite.height - 1) * sprite.width + sx.min(sprite.width - 1)) {
+                    self.blend_at(left + dx, top + dy, pixel, 255);
+                }
+            }
+        }
+    }
+
+    /// Fill `rect` (top-left origin) with corners rounded to `radius`, anti-aliased
+    pub fn fill_rounded_rect(&mut self, rect: Rect, radius: f64, color: u32) {
+        let (x, y) = (rect.x.round() as i64, rect.y.round() as i64);
+        let (width, height) = (rect.width.round() as i64, rect.height.round() as i64);
+        let radius = radius.min(width as f64 / 2.0).min(height as f64 / 2.0);
+        for py in y..y + height {
+            for px in x..x + width {
+                // Distance past the nearest corner circle's edge, 0 inside the straight parts
+                let cx = (px as f64 + 0.5).clamp(x as f64 + radius, (x + width) as f64 - radius);
+                let cy = (py as f64 + 0.5).clamp(y as f64 + radius, (y + height) as f64 - radius);
+                let distance = ((px as f64 + 0.5 - cx).powi(2) + (py as f64 + 0.5 - cy).powi(2)).sqrt();
+                let coverage = (radius + 0.5 - distance).clamp(0.0, 1.0);
+                self.blend_at(px, py, color, (coverage * 255.0) as u32);
+            }
+        }
+    }
+
+    /// Fill a rounded rectangle with a one pixel `border` around it
+    pub fn fill_bordered_rect(&mut self, rect: Rect, radius: f64, fill: u32, border: u32) {
+        self.fill_rounded_rect(rect, radius, border);
+        let inner = Rect::new(rect.x + 1.0, rect.y + 1.0, rect.width - 2.0, rect.height - 2.0);
+        self.fill_rounded_rect(inner, radius - 1.0, fill);
+    }
+
+    /// Fill an upward-pointing arrow centred on `cx`, `size` pixels tall
+    pub fn fill_up_arrow(&mut self, cx: i64, top: i64, size: i64, color: u32) {
+        let head = size / 2;
+        for dy in 0..head {
+            for dx in -dy..=dy {
+                self.blend_at(cx + dx, top + dy, color, 255);
+            }
+        }
+        let stem = (size / 8).max(1);
+        for dy in head..size {
+            for dx in -stem..=stem {
+                self.blend_at(cx + dx, top + dy, color, 255);
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! Clock module - Time as the animation sees it
+//! The animation works out which frame should be up from the time elapsed
+//! on this clock rather than counting sleeps, so late wake-ups never add up
+//! to drift. It is monotonic, and tests can swap in a `FakeClock` they
+//! advance by hand to step through clips deterministically.
+
+#[cfg(test)]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+pub trait Clock: Send + Sync {
+    /// Milliseconds since the clock started; never goes backwards
+    fn now_ms(&self) -> u64;
+}
+
+/// The real, monotonic time
+pub struct SystemClock {
+    started: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        SystemClock { started: Instant::now() }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+}
+
+/// A clock that only moves when told to
+#[cfg(test)]
+pub struct FakeClock {
+    now_ms: AtomicU64,
+}
+
+#[cfg(test)]
+impl FakeClock {
+    pub fn new() -> Self {
+        FakeClock { now_ms: AtomicU64::new(0) }
+    }
+
+    pub fn advance(&self, ms: u64) {
+        self.now_ms.fetch_add(ms, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now_ms(&self) -> u64 {
+        self.now_ms.load(Ordering::SeqCst)
+    }
+}
+
+// The clock in use, a `SystemClock` unless one was set
+static CLOCK: Mutex<Option<Arc<dyn Clock>>> = Mutex::new(None);
+
+fn clock() -> Arc<dyn Clock> {
+    CLOCK.lock().unwrap().get_or_insert_with(|| Arc::new(SystemClock::new())).clone()
+}
+
+/// Time the animation by `clock` from now on, e.g. a `FakeClock` a test
+/// keeps a handle to; call before `create_overlay`
+#[cfg(test)]
+pub fn set_clock(clock: Arc<dyn Clock>) {
+    *CLOCK.lock().unwrap() = Some(clock);
+}
+
+/// The current time on the clock in use
+pub fn now_ms() -> u64 {
+    clock().now_ms()
+}
@@ -0,0 +1,1379 @@
+//! Cocoa overlay backend - Draws the overlay with AppKit panels on macOS
+//! Supports fullscreen overlay via LSUIElement agent mode
+//! AppKit objects live in statics here; the core only sees `OverlayBackend`
+
+#![allow(unexpected_cfgs)]
+
+#[allow(deprecated)]
+use cocoa::appkit::{
+    NSBackingStoreType, NSWindowCollectionBehavior,
+    NSWindowStyleMask,
+};
+#[allow(deprecated)]
+use cocoa::base::{id, nil, BOOL, YES, NO};
+#[allow(deprecated)]
+use cocoa::foundation::{NSAutoreleasePool, NSPoint, NSRect, NSSize, NSString};
+use objc::runtime::{Class, Object, Sel};
+use objc::{msg_send, sel, sel_impl};
+use objc::declare::ClassDecl;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use super::backend::{OverlayBackend, OverlayKey, Point, Rect, ScreenEvent};
+use super::frames::Frame;
+
+#[allow(deprecated)]
+struct SafeId(id);
+unsafe impl Send for SafeId {}
+unsafe impl Sync for SafeId {}
+
+static OVERLAY_PANEL: Mutex<Option<SafeId>> = Mutex::new(None);
+
+static IMAGE_VIEW: Mutex<Option<SafeId>> = Mutex::new(None);
+
+// Chat UI elements
+static CHAT_PANEL: Mutex<Option<SafeId>> = Mutex::new(None); // Separate centered panel for chat
+static CHAT_CONTAINER: Mutex<Option<SafeId>> = Mutex::new(None);
+static CHAT_INPUT: Mutex<Option<SafeId>> = Mutex::new(None);
+static SEND_BUTTON: Mutex<Option<SafeId>> = Mutex::new(None);
+static RESPONSE_BOX: Mutex<Option<SafeId>> = Mutex::new(None);
+static THINKING_LABEL: Mutex<Option<SafeId>> = Mutex::new(None);
+
+// Loaded frames per clip, indexed like the animation graph's clips
+static CLIP_FRAMES: Mutex<Vec<Vec<SafeId>>> = Mutex::new(Vec::new());
+
+// Cross-fade length for the next frame shown, 0 to cut to it, see `fade_frames`
+static PENDING_FADE_MS: AtomicU64 = AtomicU64::new(0);
+
+// The newest frame waiting for the main thread; only one hop there is queued
+// at a time, so frames a busy main thread has no time for are dropped
+// instead of piling up
+static PENDING_FRAME: Mutex<Option<SafeId>> = Mutex::new(None);
+
+// Track if click monitor is running
+static CLICK_MONITOR_RUNNING: AtomicBool = AtomicBool::new(false);
+
+// Window levels - use maximum level to appear above fullscreen apps
+const KCGMAXIMUM_WINDOW_LEVEL: i64 = 2147483631;
+
+// NSBitmapFormatAlphaNonpremultiplied, decoded frames are straight RGBA
+const NS_BITMAP_FORMAT_ALPHA_NONPREMULTIPLIED: u64 = 1 << 1;
+
+// NSWindowOcclusionStateVisible, set while any part of a window can be seen
+const NS_WINDOW_OCCLUSION_STATE_VISIBLE: u64 = 1 << 1;
+
+/// The AppKit overlay
+pub struct CocoaBackend;
+
+#[allow(deprecated)]
+fn to_rect(rect: NSRect) -> Rect {
+    Rect::new(rect.origin.x, rect.origin.y, rect.size.width, rect.size.height)
+}
+
+#[allow(deprecated)]
+fn to_ns_rect(rect: Rect) -> NSRect {
+    NSRect::new(NSPoint::new(rect.x, rect.y), NSSize::new(rect.width, rect.height))
+}
+
+/// Current pointer location in screen coordinates
+#[allow(deprecated)]
+fn mouse_location() -> Point {
+    unsafe {
+        let location: NSPoint = msg_send![Class::get("NSEvent").unwrap(), mouseLocation];
+        Point::new(location.x, location.y)
+    }
+}
+
+/// Map the Return, keypad Enter and Escape key codes
+fn overlay_key(key_code: u16) -> Option<OverlayKey> {
+    match key_code {
+        36 | 76 => Some(OverlayKey::Enter),
+        53 => Some(OverlayKey::Escape),
+        _ => None,
+    }
+}
+
+/// Helper to create NSColor from RGB values (0-255)
+#[allow(deprecated)]
+unsafe fn create_color(r: f64, g: f64, b: f64, a: f64) -> id {
+    let color_class = Class::get("NSColor").unwrap();
+    msg_send![color_class, colorWithRed: r/255.0 green: g/255.0 blue: b/255.0 alpha: a]
+}
+
+/// Helper to load custom font
+#[allow(deprecated)]
+unsafe fn load_chicle_font(size: f64) -> id {
+    use std::sync::Once;
+    static FONT_REGISTERED: Once = Once::new();
+
+    let font_path = super::resource_path("fonts/Chicle-Regular.ttf");
+    if !font_path.exists() {
+        log::warn!("Chicle font file not found in any path");
+        return msg_send![Class::get("NSFont").unwrap(), systemFontOfSize: size];
+    }
+
+    log::info!("Trying to load font from: {:?}", font_path);
+
+    // Register font only once using NSFontManager
+    FONT_REGISTERED.call_once(|| {
+        let path_str = font_path.to_str().unwrap();
+        let ns_path = NSString::alloc(nil).init_str(path_str);
+        let url: id = msg_send![Class::get("NSURL").unwrap(), fileURLWithPath: ns_path];
+
+        // Use CTFontManagerRegisterFontsForURL C function
+        #[link(name = "CoreText", kind = "framework")]
+        extern "C" {
+            fn CTFontManagerRegisterFontsForURL(fontURL: id, scope: u32, error: *mut id) -> bool;
+        }
+
+        let result = CTFontManagerRegisterFontsForURL(url, 1, std::ptr::null_mut());
+        log::info!("Font registration result: {}", result);
+    });
+
+    // Try to load Chicle font by name
+    let font_name = NSString::alloc(nil).init_str("Chicle");
+    let font: id = msg_send![Class::get("NSFont").unwrap(), fontWithName: font_name size: size];
+
+    if font != nil {
+        log::info!("Successfully loaded Chicle font at size {}", size);
+        font
+    } else {
+        // Try with -Regular suffix
+        let font_name2 = NSString::alloc(nil).init_str("Chicle-Regular");
+        let font2: id = msg_send![Class::get("NSFont").unwrap(), fontWithName: font_name2 size: size];
+
+        if font2 != nil {
+            log::info!("Successfully loaded Chicle-Regular font at size {}", size);
+            font2
+        } else {
+            // Fallback to system font
+            log::warn!("Could not load Chicle font, using system font");
+            msg_send![Class::get("NSFont").unwrap(), systemFontOfSize: size]
+        }
+    }
+}
+
+/// Creates a custom NSPanel subclass that can become key window for text input
+fn get_or_create_key_panel_class() -> &'static Class {
+    static REGISTER: std::sync::Once = std::sync::Once::new();
+
+    REGISTER.call_once(|| {
+        let superclass = Class::get("NSPanel").unwrap();
+        let mut decl = ClassDecl::new("KeyablePanel", superclass).unwrap();
+
+        // Override canBecomeKeyWindow to return YES
+        extern "C" fn can_become_key_window(_this: &Object, _sel: Sel) -> bool {
+            true
+        }
+
+        unsafe {
+            decl.add_method(
+                sel!(canBecomeKeyWindow),
+                can_become_key_window as extern "C" fn(&Object, Sel) -> bool,
+            );
+        }
+
+        decl.register();
+    });
+
+    Class::get("KeyablePanel").unwrap()
+}
+
+/// Create a custom NSImageView subclass with hand cursor on hover
+#[allow(deprecated)]
+fn get_or_create_cursor_image_view_class() -> &'static Class {
+    static REGISTER: std::sync::Once = std::sync::Once::new();
+
+    REGISTER.call_once(|| {
+        let superclass = Class::get("NSImageView").unwrap();
+        let mut decl = ClassDecl::new("CursorImageView", superclass).unwrap();
+
+        // Override mouseEntered to show hand cursor
+        extern "C" fn mouse_entered(_this: &Object, _sel: Sel, _event: id) {
+            unsafe {
+                let hand_cursor: id = msg_send![Class::get("NSCursor").unwrap(), pointingHandCursor];
+                let _: () = msg_send![hand_cursor, push];
+            }
+        }
+
+        // Override mouseExited to restore arrow cursor
+        extern "C" fn mouse_exited(_this: &Object, _sel: Sel, _event: id) {
+            unsafe {
+                let _: () = msg_send![Class::get("NSCursor").unwrap(), pop];
+            }
+        }
+
+        // Override updateTrackingAreas to add tracking area on view setup
+        extern "C" fn update_tracking_areas(this: &Object, _sel: Sel) {
+            unsafe {
+                // Call super
+                let superclass = Class::get("NSImageView").unwrap();
+                let _: () = msg_send![super(this, superclass), updateTrackingAreas];
+
+                // Remove old tracking areas
+                let tracking_areas: id = msg_send![this, trackingAreas];
+                let count: usize = msg_send![tracking_areas, count];
+                for i in 0..count {
+                    let area: id = msg_send![tracking_areas, objectAtIndex: i];
+                    let _: () = msg_send![this, removeTrackingArea: area];
+                }
+
+                // Add new tracking area
+                let bounds: NSRect = msg_send![this, bounds];
+                // NSTrackingMouseEnteredAndExited | NSTrackingActiveAlways | NSTrackingInVisibleRect
+                let options: u64 = 0x01 | 0x80 | 0x200;
+                let tracking_area: id = msg_send![Class::get("NSTrackingArea").unwrap(), alloc];
+                let tracking_area: id = msg_send![tracking_area,
+                    initWithRect: bounds
+                    options: options
+                    owner: this
+                    userInfo: nil
+                ];
+                let _: () = msg_send![this, addTrackingArea: tracking_area];
+            }
+        }
+
+        // Set the newest pending frame, cross-fading to it if asked to
+        extern "C" fn show_pending_frame(this: &Object, _sel: Sel) {
+            let Some(image) = PENDING_FRAME.lock().unwrap().take() else {
+                return;
+            };
+            unsafe {
+                let duration_ms = PENDING_FADE_MS.swap(0, Ordering::SeqCst);
+                let layer: id = msg_send![this, layer];
+                if layer != nil && duration_ms > 0 {
+                    let transition: id = msg_send![Class::get("CATransition").unwrap(), animation];
+                    // kCATransitionFade
+                    let _: () = msg_send![transition, setType: NSString::alloc(nil).init_str("fade")];
+                    let _: () = msg_send![transition, setDuration: duration_ms as f64 / 1000.0];
+                    let _: () = msg_send![layer, addAnimation: transition forKey: NSString::alloc(nil).init_str("clipFade")];
+                }
+                let _: () = msg_send![this, setImage: image.0];
+            }
+        }
+
+        unsafe {
+            decl.add_method(
+                sel!(mouseEntered:),
+                mouse_entered as extern "C" fn(&Object, Sel, id),
+            );
+            decl.add_method(
+                sel!(mouseExited:),
+                mouse_exited as extern "C" fn(&Object, Sel, id),
+            );
+            decl.add_method(
+                sel!(updateTrackingAreas),
+                update_tracking_areas as extern "C" fn(&Object, Sel),
+            );
+            decl.add_method(
+                sel!(showPendingFrame),
+                show_pending_frame as extern "C" fn(&Object, Sel),
+            );
+        }
+
+        decl.register();
+    });
+
+    Class::get("CursorImageView").unwrap()
+}
+
+/// Turn a frame, already scaled to the sprite size times the backing scale
+/// factor, into an image of the sprite's size in points
+#[allow(deprecated)]
+unsafe fn load_frame(frame: &Frame) -> Option<SafeId> {
+    // A bitmap that owns its pixels (no planes passed in), filled with a copy
+    let bitmap: id = msg_send![Class::get("NSBitmapImageRep").unwrap(), alloc];
+    let no_planes: *mut *mut u8 = std::ptr::null_mut();
+    let bitmap: id = msg_send![bitmap,
+        initWithBitmapDataPlanes: no_planes
+        pixelsWide: frame.width as i64
+        pixelsHigh: frame.height as i64
+        bitsPerSample: 8_i64
+        samplesPerPixel: 4_i64
+        hasAlpha: YES
+        isPlanar: NO
+        colorSpaceName: NSString::alloc(nil).init_str("NSDeviceRGBColorSpace")
+        bitmapFormat: NS_BITMAP_FORMAT_ALPHA_NONPREMULTIPLIED
+        bytesPerRow: (frame.width * 4) as i64
+        bitsPerPixel: 32_i64];
+    if bitmap == nil {
+        log::warn!("Failed to create a {}x{} bitmap", frame.width, frame.height);
+        return None;
+    }
+    let pixels: *mut u8 = msg_send![bitmap, bitmapData];
+    std::ptr::copy_nonoverlapping(frame.rgba.as_ptr(), pixels, frame.rgba.len());
+
+    // More pixels than points, so Retina screens get every one of them
+    let (width, height) = super::sprite_size();
+    let _: () = msg_send![bitmap, setSize: NSSize::new(width, height)];
+    let image: id = msg_send![Class::get("NSImage").unwrap(), alloc];
+    let image: id = msg_send![image, initWithSize: NSSize::new(width, height)];
+    let _: () = msg_send![image, addRepresentation: bitmap];
+    Some(SafeId(image))
+}
+
+/// Send a message to the object in `slot`, if it exists
+#[allow(deprecated)]
+fn with_view(slot: &Mutex<Option<SafeId>>, f: impl FnOnce(id)) {
+    let guard = slot.lock().unwrap();
+    if let Some(ref view) = *guard {
+        f(view.0);
+    }
+}
+
+impl OverlayBackend for CocoaBackend {
+    fn name(&self) -> &'static str {
+        "cocoa"
+    }
+
+    #[allow(deprecated)]
+    fn active_screen(&self) -> Rect {
+        unsafe {
+            // Get the screen with the mouse cursor (active screen)
+            let mouse_location: NSPoint = msg_send![Class::get("NSEvent").unwrap(), mouseLocation];
+            let screens: id = msg_send![Class::get("NSScreen").unwrap(), screens];
+            let screen_count: usize = msg_send![screens, count];
+            let mut screen: id = msg_send![Class::get("NSScreen").unwrap(), mainScreen];
+
+            // Find the screen containing the mouse
+            for i in 0..screen_count {
+                let s: id = msg_send![screens, objectAtIndex: i];
+                let s_frame: NSRect = msg_send![s, frame];
+                if mouse_location.x >= s_frame.origin.x
+                    && mouse_location.x < s_frame.origin.x + s_frame.size.width
+                    && mouse_location.y >= s_frame.origin.y
+                    && mouse_location.y < s_frame.origin.y + s_frame.size.height {
+                    screen = s;
+                    break;
+                }
+            }
+
+            let screen_frame: NSRect = msg_send![screen, frame];
+            to_rect(screen_frame)
+        }
+    }
+
+    /// The screen whose menu bar is active, which follows the focused
+    /// window while displays have separate spaces (the default)
+    #[allow(deprecated)]
+    fn focused_screen(&self) -> Option<Rect> {
+        unsafe {
+            let screen: id = msg_send![Class::get("NSScreen").unwrap(), mainScreen];
+            if screen == nil {
+                return None;
+            }
+            let frame: NSRect = msg_send![screen, frame];
+            Some(to_rect(frame))
+        }
+    }
+
+    #[allow(deprecated)]
+    fn scale_factor(&self) -> f64 {
+        unsafe {
+            let pool = NSAutoreleasePool::new(nil);
+            // The panel can move to any screen, so frames suit the sharpest
+            let screens: id = msg_send![Class::get("NSScreen").unwrap(), screens];
+            let screen_count: usize = msg_send![screens, count];
+            let mut scale: f64 = 1.0;
+            for i in 0..screen_count {
+                let screen: id = msg_send![screens, objectAtIndex: i];
+                let backing: f64 = msg_send![screen, backingScaleFactor];
+                scale = scale.max(backing);
+            }
+            pool.drain();
+            scale
+        }
+    }
+
+    #[allow(deprecated)]
+    fn add_frames(&self, clip: usize, decoded: &[Frame]) -> usize {
+        // Called from the frame loader's thread, which has no pool of its own
+        let pool = unsafe { NSAutoreleasePool::new(nil) };
+        let mut frames: Vec<SafeId> = Vec::new();
+        for frame in decoded {
+            match unsafe { load_frame(frame) } {
+                Some(frame) => frames.push(frame),
+                None => break, // Stop at the first frame that won't load
+            }
+        }
+        unsafe { pool.drain() };
+        let mut clips = CLIP_FRAMES.lock().unwrap();
+        if clips.len() <= clip {
+            clips.resize_with(clip + 1, Vec::new);
+        }
+        clips[clip].extend(frames);
+        clips[clip].len()
+    }
+
+    fn frame_count(&self, clip: usize) -> usize {
+        CLIP_FRAMES.lock().unwrap().get(clip).map_or(0, Vec::len)
+    }
+
+    #[allow(deprecated)]
+    fn create_panel(&self, panel_frame: Rect) -> Result<(), String> {
+        let (width, height) = (panel_frame.width, panel_frame.height);
+
+        unsafe {
+            // Set application to accessory mode (no dock icon, can overlay fullscreen)
+            // NSApplicationActivationPolicyAccessory = 1
+            let app: id = msg_send![Class::get("NSApplication").unwrap(), sharedApplication];
+            let _: () = msg_send![app, setActivationPolicy: 1_i64];
+
+            // Use custom panel class that can become key window
+            let panel_class = get_or_create_key_panel_class();
+            let frame = to_ns_rect(panel_frame);
+
+            let style = NSWindowStyleMask::NSBorderlessWindowMask;
+            let panel: id = msg_send![panel_class, alloc];
+            let panel: id = msg_send![panel, initWithContentRect:frame
+                                            styleMask:style
+                                            backing:NSBackingStoreType::NSBackingStoreBuffered
+                                            defer:NO];
+
+            // Use maximum window level to appear above everything including fullscreen
+            let _: () = msg_send![panel, setLevel: KCGMAXIMUM_WINDOW_LEVEL];
+
+            // Enhanced collection behavior for fullscreen support
+            // NSWindowCollectionBehaviorMoveToActiveSpace = 1 << 1 (2)
+            let behavior = NSWindowCollectionBehavior::NSWindowCollectionBehaviorCanJoinAllSpaces
+                | NSWindowCollectionBehavior::NSWindowCollectionBehaviorFullScreenAuxiliary
+                | NSWindowCollectionBehavior::NSWindowCollectionBehaviorStationary
+                | NSWindowCollectionBehavior::NSWindowCollectionBehaviorIgnoresCycle
+                | NSWindowCollectionBehavior::NSWindowCollectionBehaviorTransient;
+            let _: () = msg_send![panel, setCollectionBehavior: behavior];
+
+            let _: () = msg_send![panel, setOpaque: NO];
+            let clear_color: id = msg_send![Class::get("NSColor").unwrap(), clearColor];
+            let _: () = msg_send![panel, setBackgroundColor: clear_color];
+            let _: () = msg_send![panel, setHasShadow: NO]; // No shadow - clean oval only
+            let _: () = msg_send![panel, setIgnoresMouseEvents: YES];
+            let _: () = msg_send![panel, setFloatingPanel: YES];
+            let _: () = msg_send![panel, setHidesOnDeactivate: NO];
+            let _: () = msg_send![panel, setWorksWhenModal: YES];
+            let _: () = msg_send![panel, setCanHide: NO];
+            let _: () = msg_send![panel, setReleasedWhenClosed: NO];
+            // Additional settings for screen recording level
+            let _: () = msg_send![panel, setStyleMask: 0_i64]; // Non-activating
+            let _: () = msg_send![panel, setAnimationBehavior: 0_i64]; // None
+
+            // Transparent container view - just for the red panda, no background
+            let content_frame = NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(width, height));
+            let container_view: id = msg_send![Class::get("NSView").unwrap(), alloc];
+            let container_view: id = msg_send![container_view, initWithFrame: content_frame];
+            let _: () = msg_send![container_view, setWantsLayer: YES];
+
+            // Cat sprite, standing on its anchor at the bottom of the view
+            // (macOS y=0 is at bottom); frames are pre-scaled to its size
+            // This leaves room above the cat for chat elements
+            let cat = super::cat_frame(width);
+            let (img_x, img_y, img_width, img_height) = (cat.x, cat.y, cat.width, cat.height);
+            let img_frame = NSRect::new(
+                NSPoint::new(img_x, img_y),
+                NSSize::new(img_width, img_height),
+            );
+
+            // Use custom CursorImageView class for hand cursor on hover
+            let cursor_image_class = get_or_create_cursor_image_view_class();
+            let image_view: id = msg_send![cursor_image_class, alloc];
+            let image_view: id = msg_send![image_view, initWithFrame: img_frame];
+            // Use NSImageScaleNone (0) - images are pre-scaled with high quality
+            let _: () = msg_send![image_view, setImageScaling: 0_i64];
+
+            let _: () = msg_send![container_view, addSubview: image_view];
+
+            // Trigger tracking area setup
+            let _: () = msg_send![image_view, updateTrackingAreas];
+
+            // Store image view reference for animation
+            {
+                let mut iv_guard = IMAGE_VIEW.lock().unwrap();
+                *iv_guard = Some(SafeId(image_view));
+            }
+
+            // ========== CHAT UI CREATION ==========
+            // Get screen dimensions for center positioning
+            let screen: id = msg_send![Class::get("NSScreen").unwrap(), mainScreen];
+            let screen_frame: NSRect = msg_send![screen, frame];
+            let screen_center_x = screen_frame.size.width / 2.0;
+            let screen_center_y = screen_frame.size.height / 2.0;
+
+            // Chat box - sleek modern design positioned at CENTER OF SCREEN
+            let chat_box_width = 320.0;
+            let chat_box_height = 56.0;
+            // Position in screen coordinates (will be converted when showing)
+            // Store these for later use
+            let _screen_chat_x = screen_center_x - chat_box_width / 2.0;
+            let _screen_chat_y = screen_center_y - chat_box_height / 2.0;
+
+            // For now, position relative to panel (will reposition when showing)
+            let chat_box_x = (width - chat_box_width) / 2.0;
+            let chat_box_y = img_y + img_height + 20.0;
+            let chat_frame = NSRect::new(
+                NSPoint::new(chat_box_x, chat_box_y),
+                NSSize::new(chat_box_width, chat_box_height),
+            );
+
+            // Create chat container view
+            let chat_container: id = msg_send![Class::get("NSView").unwrap(), alloc];
+            let chat_container: id = msg_send![chat_container, initWithFrame: chat_frame];
+            let _: () = msg_send![chat_container, setWantsLayer: YES];
+
+            // Style: WHITE background with BLACK text, sleek curved border
+            let chat_layer: id = msg_send![chat_container, layer];
+            let _: () = msg_send![chat_layer, setCornerRadius: 28.0_f64]; // Pill shape
+            let _: () = msg_send![chat_layer, setMasksToBounds: NO];
+
+            // White background
+            let white: id = msg_send![Class::get("NSColor").unwrap(), whiteColor];
+            let cg_white: id = msg_send![white, CGColor];
+            let _: () = msg_send![chat_layer, setBackgroundColor: cg_white];
+
+            // Subtle gray border for sleek look
+            let border_color = create_color(220.0, 220.0, 220.0, 1.0);
+            let cg_border_color: id = msg_send![border_color, CGColor];
+            let _: () = msg_send![chat_layer, setBorderColor: cg_border_color];
+            let _: () = msg_send![chat_layer, setBorderWidth: 1.0_f64];
+
+            // Elegant shadow for depth
+            let _: () = msg_send![chat_layer, setShadowOpacity: 0.15_f32];
+            let _: () = msg_send![chat_layer, setShadowRadius: 20.0_f64];
+            let _: () = msg_send![chat_layer, setShadowOffset: NSSize::new(0.0, -5.0)];
+            let black: id = msg_send![Class::get("NSColor").unwrap(), blackColor];
+            let cg_black: id = msg_send![black, CGColor];
+            let _: () = msg_send![chat_layer, setShadowColor: cg_black];
+
+            // Create text input field (sleek inline style)
+            let input_padding = 20.0;
+            let btn_space = 50.0;
+            let input_width = chat_box_width - input_padding * 2.0 - btn_space;
+            let input_height = 30.0;
+            let input_x = input_padding;
+            let input_y = (chat_box_height - input_height) / 2.0;
+            let input_frame = NSRect::new(
+                NSPoint::new(input_x, input_y),
+                NSSize::new(input_width, input_height),
+            );
+
+            let chat_input: id = msg_send![Class::get("NSTextField").unwrap(), alloc];
+            let chat_input: id = msg_send![chat_input, initWithFrame: input_frame];
+            let _: () = msg_send![chat_input, setEditable: YES];
+            let _: () = msg_send![chat_input, setSelectable: YES];
+            let _: () = msg_send![chat_input, setBordered: NO];
+            let _: () = msg_send![chat_input, setDrawsBackground: NO];
+            let _: () = msg_send![chat_input, setWantsLayer: YES];
+            let _: () = msg_send![chat_input, setFocusRingType: 0_i64];
+            let _: () = msg_send![chat_input, setAllowsEditingTextAttributes: NO];
+
+            // BLACK text color
+            let black_text: id = msg_send![Class::get("NSColor").unwrap(), blackColor];
+            let _: () = msg_send![chat_input, setTextColor: black_text];
+
+            // Persona greeting as placeholder, in gray
+            let placeholder_str = NSString::alloc(nil).init_str(&crate::personas::greeting());
+            let placeholder_color = create_color(150.0, 150.0, 150.0, 1.0);
+            let placeholder_font: id = msg_send![Class::get("NSFont").unwrap(), systemFontOfSize: 15.0_f64 weight: 0.0_f64];
+
+            let foreground_color_key = NSString::alloc(nil).init_str("NSColor");
+            let font_key = NSString::alloc(nil).init_str("NSFont");
+
+            let keys: [id; 2] = [foreground_color_key, font_key];
+            let objects: [id; 2] = [placeholder_color, placeholder_font];
+            let placeholder_dict: id = msg_send![Class::get("NSDictionary").unwrap(),
+                dictionaryWithObjects: objects.as_ptr()
+                forKeys: keys.as_ptr()
+                count: 2_usize
+            ];
+            let attributed_placeholder: id = msg_send![Class::get("NSAttributedString").unwrap(), alloc];
+            let attributed_placeholder: id = msg_send![attributed_placeholder, initWithString:placeholder_str attributes:placeholder_dict];
+            let _: () = msg_send![chat_input, setPlaceholderAttributedString: attributed_placeholder];
+
+            // Modern system font for input
+            let input_font: id = msg_send![Class::get("NSFont").unwrap(), systemFontOfSize: 15.0_f64 weight: 0.0_f64];
+            let _: () = msg_send![chat_input, setFont: input_font];
+
+            let _: () = msg_send![chat_container, addSubview: chat_input];
+
+            // Create send button (pill-shaped on right side)
+            let btn_width = 40.0;
+            let btn_height = 40.0;
+            let btn_x = chat_box_width - btn_width - 8.0;
+            let btn_y = (chat_box_height - btn_height) / 2.0;
+            let btn_frame = NSRect::new(
+                NSPoint::new(btn_x, btn_y),
+                NSSize::new(btn_width, btn_height),
+            );
+
+            let send_btn: id = msg_send![Class::get("NSButton").unwrap(), alloc];
+            let send_btn: id = msg_send![send_btn, initWithFrame: btn_frame];
+            let _: () = msg_send![send_btn, setWantsLayer: YES];
+            let _: () = msg_send![send_btn, setBordered: NO];
+            let _: () = msg_send![send_btn, setTitle: NSString::alloc(nil).init_str("↑")];
+
+            // Set font for send icon (bold arrow)
+            let send_font: id = msg_send![Class::get("NSFont").unwrap(), boldSystemFontOfSize: 18.0_f64];
+            let _: () = msg_send![send_btn, setFont: send_font];
+
+            // Sleek black circular button
+            let btn_layer: id = msg_send![send_btn, layer];
+            let _: () = msg_send![btn_layer, setCornerRadius: 20.0_f64];
+            let black_bg: id = msg_send![Class::get("NSColor").unwrap(), blackColor];
+            let cg_black_bg: id = msg_send![black_bg, CGColor];
+            let _: () = msg_send![btn_layer, setBackgroundColor: cg_black_bg];
+
+            // White text on black button
+            let white_text: id = msg_send![Class::get("NSColor").unwrap(), whiteColor];
+            // Use attributed string for button title
+            let btn_title = NSString::alloc(nil).init_str("↑");
+            let btn_font: id = msg_send![Class::get("NSFont").unwrap(), boldSystemFontOfSize: 18.0_f64];
+            let btn_keys: [id; 2] = [foreground_color_key, font_key];
+            let btn_objects: [id; 2] = [white_text, btn_font];
+            let btn_dict: id = msg_send![Class::get("NSDictionary").unwrap(),
+                dictionaryWithObjects: btn_objects.as_ptr()
+                forKeys: btn_keys.as_ptr()
+                count: 2_usize
+            ];
+            let btn_attr_title: id = msg_send![Class::get("NSAttributedString").unwrap(), alloc];
+            let btn_attr_title: id = msg_send![btn_attr_title, initWithString:btn_title attributes:btn_dict];
+            let _: () = msg_send![send_btn, setAttributedTitle: btn_attr_title];
+
+            let _: () = msg_send![chat_container, addSubview: send_btn];
+
+            // Initially hidden
+            let _: () = msg_send![chat_container, setHidden: YES];
+            let _: () = msg_send![chat_container, setAlphaValue: 0.0_f64];
+
+            let _: () = msg_send![container_view, addSubview: chat_container];
+
+            // Store chat UI references
+            {
+                let mut guard = CHAT_CONTAINER.lock().unwrap();
+                *guard = Some(SafeId(chat_container));
+            }
+            {
+                let mut guard = CHAT_INPUT.lock().unwrap();
+                *guard = Some(SafeId(chat_input));
+            }
+            {
+                let mut guard = SEND_BUTTON.lock().unwrap();
+                *guard = Some(SafeId(send_btn));
+            }
+
+            // Create "Thinking..." label
+            let thinking_width = 120.0;
+            let thinking_height = 35.0;
+            let thinking_x = (width - thinking_width) / 2.0;
+            let thinking_y = img_y + img_height + 10.0;
+            let thinking_frame = NSRect::new(
+                NSPoint::new(thinking_x, thinking_y),
+                NSSize::new(thinking_width, thinking_height),
+            );
+
+            let thinking_label: id = msg_send![Class::get("NSTextField").unwrap(), alloc];
+            let thinking_label: id = msg_send![thinking_label, initWithFrame: thinking_frame];
+            let _: () = msg_send![thinking_label, setEditable: NO];
+            let _: () = msg_send![thinking_label, setBordered: NO];
+            let _: () = msg_send![thinking_label, setDrawsBackground: NO];
+            let _: () = msg_send![thinking_label, setWantsLayer: YES];
+            let _: () = msg_send![thinking_label, setAlignment: 1_i64]; // Center
+
+            // White text with black stroke effect (use shadow for stroke effect)
+            let white: id = msg_send![Class::get("NSColor").unwrap(), whiteColor];
+            let _: () = msg_send![thinking_label, setTextColor: white];
+            let thinking_font = load_chicle_font(26.0);
+            let _: () = msg_send![thinking_label, setFont: thinking_font];
+            let _: () = msg_send![thinking_label, setStringValue: NSString::alloc(nil).init_str("Thinking...")];
+
+            // Add shadow for stroke effect
+            let thinking_layer: id = msg_send![thinking_label, layer];
+            let shadow: id = msg_send![Class::get("NSShadow").unwrap(), alloc];
+            let shadow: id = msg_send![shadow, init];
+            let black: id = msg_send![Class::get("NSColor").unwrap(), blackColor];
+            let _: () = msg_send![shadow, setShadowColor: black];
+            let _: () = msg_send![shadow, setShadowBlurRadius: 2.0_f64];
+            let _: () = msg_send![shadow, setShadowOffset: NSSize::new(0.0, 0.0)];
+            let _: () = msg_send![thinking_label, setShadow: shadow];
+            let _: () = msg_send![thinking_layer, setShadowOpacity: 1.0_f32];
+            let _: () = msg_send![thinking_layer, setShadowRadius: 1.5_f64];
+
+            // Initially hidden
+            let _: () = msg_send![thinking_label, setHidden: YES];
+            let _: () = msg_send![thinking_label, setAlphaValue: 0.0_f64];
+
+            let _: () = msg_send![container_view, addSubview: thinking_label];
+
+            {
+                let mut guard = THINKING_LABEL.lock().unwrap();
+                *guard = Some(SafeId(thinking_label));
+            }
+
+            // Create response box (similar to chat input but larger, for showing response)
+            let response_width = 300.0;
+            let response_height = 120.0;
+            let response_x = (width - response_width) / 2.0;
+            let response_y = img_y + img_height + 10.0;
+            let response_frame = NSRect::new(
+                NSPoint::new(response_x, response_y),
+                NSSize::new(response_width, response_height),
+            );
+
+            let response_box: id = msg_send![Class::get("NSTextField").unwrap(), alloc];
+            let response_box: id = msg_send![response_box, initWithFrame: response_frame];
+            let _: () = msg_send![response_box, setEditable: NO];
+            let _: () = msg_send![response_box, setBordered: NO];
+            let _: () = msg_send![response_box, setDrawsBackground: YES];
+            let _: () = msg_send![response_box, setWantsLayer: YES];
+
+            // Style response box - white background with black text
+            let response_layer: id = msg_send![response_box, layer];
+            let _: () = msg_send![response_layer, setCornerRadius: 20.0_f64];
+            let _: () = msg_send![response_layer, setMasksToBounds: NO];
+
+            let white: id = msg_send![Class::get("NSColor").unwrap(), whiteColor];
+            let _: () = msg_send![response_box, setBackgroundColor: white];
+            let cg_white: id = msg_send![white, CGColor];
+            let _: () = msg_send![response_layer, setBackgroundColor: cg_white];
+
+            // Subtle border
+            let resp_border_color = create_color(230.0, 230.0, 230.0, 1.0);
+            let cg_resp_border: id = msg_send![resp_border_color, CGColor];
+            let _: () = msg_send![response_layer, setBorderColor: cg_resp_border];
+            let _: () = msg_send![response_layer, setBorderWidth: 1.0_f64];
+
+            // Shadow
+            let _: () = msg_send![response_layer, setShadowOpacity: 0.15_f32];
+            let _: () = msg_send![response_layer, setShadowRadius: 15.0_f64];
+            let _: () = msg_send![response_layer, setShadowOffset: NSSize::new(0.0, -5.0)];
+            let black_shadow: id = msg_send![Class::get("NSColor").unwrap(), blackColor];
+            let cg_black_shadow: id = msg_send![black_shadow, CGColor];
+            let _: () = msg_send![response_layer, setShadowColor: cg_black_shadow];
+
+            let black_text: id = msg_send![Class::get("NSColor").unwrap(), blackColor];
+            let _: () = msg_send![response_box, setTextColor: black_text];
+            let response_font: id = msg_send![Class::get("NSFont").unwrap(), systemFontOfSize: 15.0_f64 weight: 0.0_f64];
+            let _: () = msg_send![response_box, setFont: response_font];
+            let _: () = msg_send![response_box, setAlignment: 0_i64]; // Left align
+
+            // Enable word wrapping
+            let cell: id = msg_send![response_box, cell];
+            let _: () = msg_send![cell, setWraps: YES];
+            let _: () = msg_send![cell, setLineBreakMode: 0_i64]; // NSLineBreakByWordWrapping
+
+            // Initially hidden
+            let _: () = msg_send![response_box, setHidden: YES];
+            let _: () = msg_send![response_box, setAlphaValue: 0.0_f64];
+
+            let _: () = msg_send![container_view, addSubview: response_box];
+
+            {
+                let mut guard = RESPONSE_BOX.lock().unwrap();
+                *guard = Some(SafeId(response_box));
+            }
+
+            // ========== END CHAT UI CREATION ==========
+
+            let _: () = msg_send![panel, setContentView: container_view];
+            let _: () = msg_send![panel, orderFrontRegardless];
+
+            let mut guard = OVERLAY_PANEL.lock().unwrap();
+            *guard = Some(SafeId(panel));
+        }
+        Ok(())
+    }
+
+    #[allow(deprecated)]
+    fn close_panel(&self) {
+        let mut guard = OVERLAY_PANEL.lock().unwrap();
+        if let Some(ref safe_panel) = *guard {
+            unsafe {
+                let _: () = msg_send![safe_panel.0, close];
+            }
+        }
+        *guard = None;
+
+        // Clear image view and frames
+        *IMAGE_VIEW.lock().unwrap() = None;
+        *PENDING_FRAME.lock().unwrap() = None;
+        CLIP_FRAMES.lock().unwrap().clear();
+    }
+
+    #[allow(deprecated)]
+    fn show_panel(&self) {
+        with_view(&OVERLAY_PANEL, |panel| unsafe {
+            let _: () = msg_send![panel, orderFrontRegardless];
+        });
+    }
+
+    #[allow(deprecated)]
+    fn hide_panel(&self) {
+        with_view(&OVERLAY_PANEL, |panel| unsafe {
+            let _: () = msg_send![panel, orderOut: nil];
+        });
+    }
+
+    #[allow(deprecated)]
+    fn is_panel_visible(&self) -> bool {
+        let guard = OVERLAY_PANEL.lock().unwrap();
+        if let Some(ref safe_panel) = *guard {
+            unsafe { msg_send![safe_panel.0, isVisible] }
+        } else {
+            false
+        }
+    }
+
+    #[allow(deprecated)]
+    fn is_panel_occluded(&self) -> bool {
+        let guard = OVERLAY_PANEL.lock().unwrap();
+        guard.as_ref().is_some_and(|panel| unsafe {
+            let visible: BOOL = msg_send![panel.0, isVisible];
+            let occlusion: u64 = msg_send![panel.0, occlusionState];
+            visible == YES && occlusion & NS_WINDOW_OCCLUSION_STATE_VISIBLE == 0
+        })
+    }
+
+    #[allow(deprecated)]
+    fn panel_frame(&self) -> Option<Rect> {
+        let guard = OVERLAY_PANEL.lock().unwrap();
+        guard.as_ref().map(|panel| unsafe {
+            let frame: NSRect = msg_send![panel.0, frame];
+            to_rect(frame)
+        })
+    }
+
+    #[allow(deprecated)]
+    fn move_panel(&self, origin: Point) {
+        with_view(&OVERLAY_PANEL, |panel| unsafe {
+            let _: () = msg_send![panel, setFrameOrigin: NSPoint::new(origin.x, origin.y)];
+
+            // Ensure it's still on top
+            let _: () = msg_send![panel, setLevel: KCGMAXIMUM_WINDOW_LEVEL];
+            let _: () = msg_send![panel, orderFrontRegardless];
+        });
+    }
+
+    #[allow(deprecated)]
+    fn show_frame(&self, clip: usize, index: usize) {
+        let clips = CLIP_FRAMES.lock().unwrap();
+        let Some(frame) = clips.get(clip).and_then(|frames| frames.get(index)) else {
+            return;
+        };
+        let image = SafeId(frame.0);
+        drop(clips);
+        if PENDING_FRAME.lock().unwrap().replace(image).is_some() {
+            return; // The hop already queued shows this one instead
+        }
+
+        // Usually called from the animation task, so hop to the main thread
+        // to draw; on the main thread it is set right away
+        with_view(&IMAGE_VIEW, |image_view| unsafe {
+            let on_main_thread: BOOL = msg_send![Class::get("NSThread").unwrap(), isMainThread];
+            let _: () = msg_send![image_view,
+                performSelectorOnMainThread: sel!(showPendingFrame)
+                withObject: nil
+                waitUntilDone: on_main_thread];
+        });
+    }
+
+    /// The fade is attached to the next `show_frame`, on the main thread
+    fn fade_frames(&self, duration_ms: u64) {
+        PENDING_FADE_MS.store(duration_ms, Ordering::SeqCst);
+    }
+
+    /// Scale bounce effect on the cat image
+    #[allow(deprecated)]
+    fn play_click_feedback(&self) {
+        unsafe {
+            let guard = IMAGE_VIEW.lock().unwrap();
+            if let Some(ref img_view) = *guard {
+                // Quick scale-down then scale-up animation for click feedback
+                // Use NSAnimationContext with completion handler
+                let _: () = msg_send![Class::get("NSAnimationContext").unwrap(), beginGrouping];
+                let context: id = msg_send![Class::get("NSAnimationContext").unwrap(), currentContext];
+                let _: () = msg_send![context, setDuration: 0.08_f64];
+
+                // Get the layer for transform
+                let layer: id = msg_send![img_view.0, layer];
+                if layer != nil {
+                    // Create CABasicAnimation for transform.scale
+                    let animation: id = msg_send![Class::get("CABasicAnimation").unwrap(),
+                        animationWithKeyPath: NSString::alloc(nil).init_str("transform.scale")];
+                    let _: () = msg_send![animation, setFromValue: {
+                        let num: id = msg_send![Class::get("NSNumber").unwrap(), numberWithFloat: 1.0_f32];
+                        num
+                    }];
+                    let _: () = msg_send![animation, setToValue: {
+                        let num: id = msg_send![Class::get("NSNumber").unwrap(), numberWithFloat: 0.92_f32];
+                        num
+                    }];
+                    let _: () = msg_send![animation, setDuration: 0.08_f64];
+                    let _: () = msg_send![animation, setAutoreverses: YES];
+                    let _: () = msg_send![animation, setTimingFunction: {
+                        let timing: id = msg_send![Class::get("CAMediaTimingFunction").unwrap(),
+                            functionWithName: NSString::alloc(nil).init_str("easeInEaseOut")];
+                        timing
+                    }];
+                    let _: () = msg_send![layer, addAnimation: animation forKey: NSString::alloc(nil).init_str("clickBounce")];
+                }
+
+                let _: () = msg_send![Class::get("NSAnimationContext").unwrap(), endGrouping];
+            }
+        }
+    }
+
+    #[allow(deprecated)]
+    fn set_hand_cursor(&self, hand: bool) {
+        unsafe {
+            let cursor: id = if hand {
+                msg_send![Class::get("NSCursor").unwrap(), pointingHandCursor]
+            } else {
+                msg_send![Class::get("NSCursor").unwrap(), arrowCursor]
+            };
+            let _: () = msg_send![cursor, set];
+        }
+    }
+
+    /// Chat input box in its own panel, centered on screen
+    #[allow(deprecated)]
+    fn show_chat_input(&self, placeholder: &str) {
+        unsafe {
+            // Check if chat panel exists, create if not
+            let panel_exists = CHAT_PANEL.lock().unwrap().is_some();
+
+            if !panel_exists {
+                // Create a new centered panel for chat
+                let screen: id = msg_send![Class::get("NSScreen").unwrap(), mainScreen];
+                let screen_frame: NSRect = msg_send![screen, frame];
+
+                let chat_width = 400.0;
+                let chat_height = 56.0;
+                let chat_x = (screen_frame.size.width - chat_width) / 2.0;
+                let chat_y = (screen_frame.size.height - chat_height) / 2.0;
+
+                let panel_frame = NSRect::new(
+                    NSPoint::new(chat_x, chat_y),
+                    NSSize::new(chat_width, chat_height),
+                );
+
+                // Create custom panel class for keyboard input
+                let panel_class = get_or_create_key_panel_class();
+                let chat_panel: id = msg_send![panel_class, alloc];
+
+                // NSBorderlessWindowMask (0) | NSNonactivatingPanelMask
+                let style_mask: u64 = 1 << 7;
+                let chat_panel: id = msg_send![chat_panel, initWithContentRect:panel_frame
+                    styleMask:style_mask
+                    backing:2_i64 // NSBackingStoreBuffered
+                    defer:NO];
+
+                // Make panel float above everything
+                let _: () = msg_send![chat_panel, setLevel: KCGMAXIMUM_WINDOW_LEVEL];
+                let _: () = msg_send![chat_panel, setOpaque: NO];
+                let clear: id = msg_send![Class::get("NSColor").unwrap(), clearColor];
+                let _: () = msg_send![chat_panel, setBackgroundColor: clear];
+                let _: () = msg_send![chat_panel, setHasShadow: NO];
+                let _: () = msg_send![chat_panel, setCollectionBehavior: 1_u64 | 16_u64];
+
+                // Get content view
+                let content_view: id = msg_send![chat_panel, contentView];
+                let _: () = msg_send![content_view, setWantsLayer: YES];
+
+                // Create sleek chat container
+                let container_frame = NSRect::new(
+                    NSPoint::new(0.0, 0.0),
+                    NSSize::new(chat_width, chat_height),
+                );
+
+                let chat_container: id = msg_send![Class::get("NSView").unwrap(), alloc];
+                let chat_container: id = msg_send![chat_container, initWithFrame: container_frame];
+                let _: () = msg_send![chat_container, setWantsLayer: YES];
+
+                // Style: WHITE background, sleek pill shape
+                let chat_layer: id = msg_send![chat_container, layer];
+                let _: () = msg_send![chat_layer, setCornerRadius: 28.0_f64];
+                let _: () = msg_send![chat_layer, setMasksToBounds: NO];
+
+                let white: id = msg_send![Class::get("NSColor").unwrap(), whiteColor];
+                let cg_white: id = msg_send![white, CGColor];
+                let _: () = msg_send![chat_layer, setBackgroundColor: cg_white];
+
+                // Subtle border
+                let border_color = create_color(230.0, 230.0, 230.0, 1.0);
+                let cg_border: id = msg_send![border_color, CGColor];
+                let _: () = msg_send![chat_layer, setBorderColor: cg_border];
+                let _: () = msg_send![chat_layer, setBorderWidth: 1.0_f64];
+
+                // Elegant shadow
+                let _: () = msg_send![chat_layer, setShadowOpacity: 0.2_f32];
+                let _: () = msg_send![chat_layer, setShadowRadius: 25.0_f64];
+                let _: () = msg_send![chat_layer, setShadowOffset: NSSize::new(0.0, -8.0)];
+                let black: id = msg_send![Class::get("NSColor").unwrap(), blackColor];
+                let cg_black: id = msg_send![black, CGColor];
+                let _: () = msg_send![chat_layer, setShadowColor: cg_black];
+
+                // Create text input
+                let input_padding = 24.0;
+                let btn_space = 56.0;
+                let input_width = chat_width - input_padding * 2.0 - btn_space;
+                let input_height = 30.0;
+                let input_frame = NSRect::new(
+                    NSPoint::new(input_padding, (chat_height - input_height) / 2.0),
+                    NSSize::new(input_width, input_height),
+                );
+
+                let chat_input: id = msg_send![Class::get("NSTextField").unwrap(), alloc];
+                let chat_input: id = msg_send![chat_input, initWithFrame: input_frame];
+                let _: () = msg_send![chat_input, setEditable: YES];
+                let _: () = msg_send![chat_input, setSelectable: YES];
+                let _: () = msg_send![chat_input, setBordered: NO];
+                let _: () = msg_send![chat_input, setDrawsBackground: NO];
+                let _: () = msg_send![chat_input, setWantsLayer: YES];
+                let _: () = msg_send![chat_input, setFocusRingType: 0_i64];
+
+                // Black text
+                let black_text: id = msg_send![Class::get("NSColor").unwrap(), blackColor];
+                let _: () = msg_send![chat_input, setTextColor: black_text];
+
+                // Modern system font
+                let input_font: id = msg_send![Class::get("NSFont").unwrap(), systemFontOfSize: 16.0_f64 weight: 0.0_f64];
+                let _: () = msg_send![chat_input, setFont: input_font];
+
+                // Gray placeholder
+                let placeholder_str = NSString::alloc(nil).init_str(placeholder);
+                let placeholder_color = create_color(160.0, 160.0, 160.0, 1.0);
+                let fg_key = NSString::alloc(nil).init_str("NSColor");
+                let font_key = NSString::alloc(nil).init_str("NSFont");
+                let keys: [id; 2] = [fg_key, font_key];
+                let objects: [id; 2] = [placeholder_color, input_font];
+                let placeholder_dict: id = msg_send![Class::get("NSDictionary").unwrap(),
+                    dictionaryWithObjects: objects.as_ptr()
+                    forKeys: keys.as_ptr()
+                    count: 2_usize
+                ];
+                let attr_placeholder: id = msg_send![Class::get("NSAttributedString").unwrap(), alloc];
+                let attr_placeholder: id = msg_send![attr_placeholder, initWithString:placeholder_str attributes:placeholder_dict];
+                let _: () = msg_send![chat_input, setPlaceholderAttributedString: attr_placeholder];
+
+                let _: () = msg_send![chat_container, addSubview: chat_input];
+
+                // Create send button
+                let btn_size = 40.0;
+                let btn_frame = NSRect::new(
+                    NSPoint::new(chat_width - btn_size - 8.0, (chat_height - btn_size) / 2.0),
+                    NSSize::new(btn_size, btn_size),
+                );
+
+                let send_btn: id = msg_send![Class::get("NSButton").unwrap(), alloc];
+                let send_btn: id = msg_send![send_btn, initWithFrame: btn_frame];
+                let _: () = msg_send![send_btn, setWantsLayer: YES];
+                let _: () = msg_send![send_btn, setBordered: NO];
+
+                // Black circular button
+                let btn_layer: id = msg_send![send_btn, layer];
+                let _: () = msg_send![btn_layer, setCornerRadius: 20.0_f64];
+                let cg_black_bg: id = msg_send![black, CGColor];
+                let _: () = msg_send![btn_layer, setBackgroundColor: cg_black_bg];
+
+                // White arrow on button
+                let btn_title = NSString::alloc(nil).init_str("↑");
+                let btn_font: id = msg_send![Class::get("NSFont").unwrap(), boldSystemFontOfSize: 18.0_f64];
+                let white_text: id = msg_send![Class::get("NSColor").unwrap(), whiteColor];
+                let btn_keys: [id; 2] = [fg_key, font_key];
+                let btn_objects: [id; 2] = [white_text, btn_font];
+                let btn_dict: id = msg_send![Class::get("NSDictionary").unwrap(),
+                    dictionaryWithObjects: btn_objects.as_ptr()
+                    forKeys: btn_keys.as_ptr()
+                    count: 2_usize
+                ];
+                let btn_attr: id = msg_send![Class::get("NSAttributedString").unwrap(), alloc];
+                let btn_attr: id = msg_send![btn_attr, initWithString:btn_title attributes:btn_dict];
+                let _: () = msg_send![send_btn, setAttributedTitle: btn_attr];
+
+                let _: () = msg_send![chat_container, addSubview: send_btn];
+                let _: () = msg_send![content_view, addSubview: chat_container];
+
+                // Store references
+                {
+                    let mut guard = CHAT_PANEL.lock().unwrap();
+                    *guard = Some(SafeId(chat_panel));
+                }
+                {
+                    let mut guard = CHAT_CONTAINER.lock().unwrap();
+                    *guard = Some(SafeId(chat_container));
+                }
+                {
+                    let mut guard = CHAT_INPUT.lock().unwrap();
+                    *guard = Some(SafeId(chat_input));
+                }
+                {
+                    let mut guard = SEND_BUTTON.lock().unwrap();
+                    *guard = Some(SafeId(send_btn));
+                }
+            }
+
+            // Show the chat panel - get raw pointer first to avoid holding lock
+            let (panel_ptr, input_ptr) = {
+                let guard = CHAT_PANEL.lock().unwrap();
+                let input_guard = CHAT_INPUT.lock().unwrap();
+                let panel = guard.as_ref().map(|p| p.0);
+                let input = input_guard.as_ref().map(|i| i.0);
+                (panel, input)
+            };
+
+            if let Some(panel) = panel_ptr {
+                log::info!("Showing chat panel at center of screen");
+
+                let frame: NSRect = msg_send![panel, frame];
+                log::debug!("Chat panel frame: x={}, y={}, w={}, h={}",
+                    frame.origin.x, frame.origin.y, frame.size.width, frame.size.height);
+
+                // Ensure panel is visible
+                let _: () = msg_send![panel, setAlphaValue: 1.0_f64];
+                let _: () = msg_send![panel, setIsVisible: YES];
+                let _: () = msg_send![panel, orderFrontRegardless];
+
+                // Make key window and focus input
+                let app: id = msg_send![Class::get("NSApplication").unwrap(), sharedApplication];
+                let _: () = msg_send![app, activateIgnoringOtherApps: YES];
+                let _: () = msg_send![panel, makeKeyAndOrderFront: nil];
+
+                if let Some(input) = input_ptr {
+                    let _: () = msg_send![input, selectText: nil];
+                    let _: () = msg_send![panel, makeFirstResponder: input];
+                }
+                log::info!("Chat panel shown and focused");
+            } else {
+                log::warn!("No chat panel to show");
+            }
+        }
+    }
+
+    #[allow(deprecated)]
+    fn hide_chat_input(&self) {
+        with_view(&CHAT_PANEL, |panel| unsafe {
+            // Simply hide immediately without animation to avoid thread issues
+            let _: () = msg_send![panel, setAlphaValue: 0.0_f64];
+            let _: () = msg_send![panel, orderOut: nil];
+        });
+    }
+
+    #[allow(deprecated)]
+    fn take_chat_input(&self) -> String {
+        let input_guard = CHAT_INPUT.lock().unwrap();
+        if let Some(ref input) = *input_guard {
+            unsafe {
+                let text: id = msg_send![input.0, stringValue];
+                let c_str: *const i8 = msg_send![text, UTF8String];
+                if !c_str.is_null() {
+                    let message = std::ffi::CStr::from_ptr(c_str).to_string_lossy().to_string();
+                    if !message.is_empty() {
+                        // Clear input
+                        let _: () = msg_send![input.0, setStringValue: NSString::alloc(nil).init_str("")];
+                    }
+                    return message;
+                }
+            }
+        }
+        String::new()
+    }
+
+    #[allow(deprecated)]
+    fn show_thinking(&self) {
+        with_view(&THINKING_LABEL, |label| unsafe {
+            let _: () = msg_send![label, setHidden: NO];
+            let _: () = msg_send![label, setAlphaValue: 1.0_f64];
+        });
+    }
+
+    #[allow(deprecated)]
+    fn hide_thinking(&self) {
+        with_view(&THINKING_LABEL, |label| unsafe {
+            let _: () = msg_send![label, setAlphaValue: 0.0_f64];
+            let _: () = msg_send![label, setHidden: YES];
+        });
+    }
+
+    #[allow(deprecated)]
+    fn show_response(&self) {
+        with_view(&RESPONSE_BOX, |box_| unsafe {
+            let ns_str = NSString::alloc(nil).init_str("");
+            let _: () = msg_send![box_, setStringValue: ns_str];
+            let _: () = msg_send![box_, setHidden: NO];
+            let _: () = msg_send![box_, setAlphaValue: 1.0_f64];
+        });
+    }
+
+    /// Safe from any thread, the text is set on the main thread
+    #[allow(deprecated)]
+    fn set_response_text(&self, text: &str) {
+        with_view(&RESPONSE_BOX, |box_| unsafe {
+            let ns_str = NSString::alloc(nil).init_str(text);
+            let sel = sel!(setStringValue:);
+            let _: () = msg_send![box_,
+                performSelectorOnMainThread: sel
+                withObject: ns_str
+                waitUntilDone: NO
+            ];
+        });
+    }
+
+    #[allow(deprecated)]
+    fn hide_response(&self) {
+        // Hide response box immediately
+        with_view(&RESPONSE_BOX, |box_| unsafe {
+            let _: () = msg_send![box_, setAlphaValue: 0.0_f64];
+            let _: () = msg_send![box_, setHidden: YES];
+        });
+
+        // Disable mouse events on panel (pass through)
+        with_view(&OVERLAY_PANEL, |panel| unsafe {
+            let _: () = msg_send![panel, setIgnoresMouseEvents: YES];
+        });
+    }
+
+    /// Global and local NSEvent monitors for clicks, pointer moves and keys
+    #[allow(deprecated)]
+    fn start_input_monitor(&self) {
+        if CLICK_MONITOR_RUNNING.swap(true, Ordering::SeqCst) {
+            log::info!("Click monitor already running");
+            return;
+        }
+
+        log::info!("Starting click monitor");
+
+        std::thread::spawn(|| {
+            use block::ConcreteBlock;
+
+            unsafe {
+                // NSEventMaskLeftMouseDown = 1 << 1
+                let mouse_mask: u64 = 1 << 1;
+
+                // Global monitor - catches clicks when OTHER apps are focused
+                let global_handler = ConcreteBlock::new(move |_event: id| {
+                    super::handle_click(mouse_location());
+                });
+                let global_handler = global_handler.copy();
+
+                let global_monitor: id = msg_send![Class::get("NSEvent").unwrap(),
+                    addGlobalMonitorForEventsMatchingMask: mouse_mask
+                    handler: &*global_handler
+                ];
+                log::info!("Global mouse monitor registered: {:?}", global_monitor);
+                // Removed again when the thread exits, so a restart doesn't double up
+                let mut monitors: Vec<id> = vec![global_monitor];
+
+                // NOTE: Local monitor removed - global monitor should catch all clicks
+                // Local monitors cause double-click issues when both are registered
+
+                // Add mouse move monitor for hover cursor effect (both global and local)
+                let move_mask: u64 = 1 << 5; // NSEventMaskMouseMoved
+                let move_handler = ConcreteBlock::new(move |_event: id| {
+                    super::handle_pointer_move(mouse_location());
+                });
+                let move_handler = move_handler.copy();
+
+                let monitor: id = msg_send![Class::get("NSEvent").unwrap(),
+                    addGlobalMonitorForEventsMatchingMask: move_mask
+                    handler: &*move_handler
+                ];
+                monitors.push(monitor);
+
+                let move_handler_local = ConcreteBlock::new(move |event: id| -> id {
+                    super::handle_pointer_move(mouse_location());
+                    event
+                });
+                let move_handler_local = move_handler_local.copy();
+
+                let monitor: id = msg_send![Class::get("NSEvent").unwrap(),
+                    addLocalMonitorForEventsMatchingMask: move_mask
+                    handler: &*move_handler_local
+                ];
+                monitors.push(monitor);
+                log::info!("Mouse move monitors registered for hover cursor");
+
+                // Add key event monitors for Enter (submit) and Escape (close/cancel)
+                let key_mask: u64 = 1 << 10; // NSEventMaskKeyDown
+
+                let global_key_handler = ConcreteBlock::new(move |event: id| {
+                    let key_code: u16 = msg_send![event, keyCode];
+                    if let Some(key) = overlay_key(key_code) {
+                        super::handle_key(key);
+                    }
+                });
+                let global_key_handler = global_key_handler.copy();
+
+                let monitor: id = msg_send![Class::get("NSEvent").unwrap(),
+                    addGlobalMonitorForEventsMatchingMask: key_mask
+                    handler: &*global_key_handler
+                ];
+                monitors.push(monitor);
+
+                let local_key_handler = ConcreteBlock::new(move |event: id| -> id {
+                    let key_code: u16 = msg_send![event, keyCode];
+                    if let Some(key) = overlay_key(key_code) {
+                        super::handle_key(key);
+                    }
+                    event
+                });
+                let local_key_handler = local_key_handler.copy();
+
+                let monitor: id = msg_send![Class::get("NSEvent").unwrap(),
+                    addLocalMonitorForEventsMatchingMask: key_mask
+                    handler: &*local_key_handler
+                ];
+                monitors.push(monitor);
+                log::info!("Key monitors registered");
+
+                // Screens plugged in or rearranged, spaces switched and apps
+                // activated; posted on the main thread, handled right there
+                let rearranged_handler = ConcreteBlock::new(move |_notification: id| {
+                    super::handle_screen_event(ScreenEvent::Rearranged);
+                });
+                let rearranged_handler = rearranged_handler.copy();
+                let focus_handler = ConcreteBlock::new(move |_notification: id| {
+                    super::handle_screen_event(ScreenEvent::FocusMoved);
+                });
+                let focus_handler = focus_handler.copy();
+
+                let app_center: id = msg_send![Class::get("NSNotificationCenter").unwrap(), defaultCenter];
+                let workspace: id = msg_send![Class::get("NSWorkspace").unwrap(), sharedWorkspace];
+                let workspace_center: id = msg_send![workspace, notificationCenter];
+                let observers = [
+                    (app_center, "NSApplicationDidChangeScreenParametersNotification", &rearranged_handler),
+                    (workspace_center, "NSWorkspaceActiveSpaceDidChangeNotification", &rearranged_handler),
+                    (workspace_center, "NSWorkspaceScreensDidWakeNotification", &rearranged_handler),
+                    (workspace_center, "NSWorkspaceDidActivateApplicationNotification", &focus_handler),
+                ];
+                let mut tokens: Vec<(id, id)> = Vec::new();
+                for (center, name, handler) in observers {
+                    let name = NSString::alloc(nil).init_str(name);
+                    let token: id = msg_send![center,
+                        addObserverForName: name
+                        object: nil
+                        queue: nil
+                        usingBlock: &**handler
+                    ];
+                    tokens.push((center, token));
+                }
+                log::info!("Screen change observers registered");
+
+                // Keep the thread alive with proper run loop
+                loop {
+                    if !CLICK_MONITOR_RUNNING.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    std::thread::sleep(std::time::Duration::from_millis(100));
+                }
+                for monitor in monitors {
+                    let _: () = msg_send![Class::get("NSEvent").unwrap(), removeMonitor: monitor];
+                }
+                for (center, token) in tokens {
+                    let _: () = msg_send![center, removeObserver: token];
+                }
+                log::info!("Click monitor stopped");
+            }
+        });
+    }
+
+    fn stop_input_monitor(&self) {
+        CLICK_MONITOR_RUNNING.store(false, Ordering::SeqCst);
+    }
+}
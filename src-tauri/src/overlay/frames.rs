@@ -0,0 +1,184 @@
+//! Frames module - Decoding a clip's frames from its files
+//! A clip is a numbered image sequence, one animated GIF/APNG/WebP, or a
+//! sprite sheet with a JSON atlas (the array format Aseprite and
+//! TexturePacker export). Durations stored in the files are kept so the
+//! animation can play frames at their own pace. Frames are handed over one at
+//! a time as they decode, so a clip can start playing before it is complete.
+//! Platform-neutral; backends turn the decoded pixels into whatever their
+//! toolkit draws.
+
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::imageops::FilterType;
+use image::{AnimationDecoder, ImageFormat, RgbaImage};
+use serde::Deserialize;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+// Clips longer than this are cut short
+const MAX_CLIP_FRAMES: usize = 100;
+
+// Shorter delays are ignored, as browsers do: old GIFs use 0 to mean "default"
+const MIN_FRAME_MS: u64 = 10;
+
+/// One decoded frame
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    /// Straight (not premultiplied) RGBA, row by row from the top
+    pub rgba: Vec<u8>,
+    /// How long the file says to show it, if it says
+    pub duration_ms: Option<u64>,
+}
+
+impl Frame {
+    fn new(image: RgbaImage, duration_ms: Option<u64>) -> Self {
+        Frame {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            rgba: image.into_raw(),
+            duration_ms: duration_ms.filter(|ms| *ms >= MIN_FRAME_MS),
+        }
+    }
+
+    /// The frame resized to `width`x`height` pixels
+    /// Filtered premultiplied, so transparent pixels don't darken the edges
+    pub fn scaled(self, width: usize, height: usize) -> Frame {
+        if (self.width, self.height) == (width, height) {
+            return self;
+        }
+        let mut rgba = self.rgba;
+        for pixel in rgba.chunks_exact_mut(4) {
+            let alpha = pixel[3] as u32;
+            for c in &mut pixel[..3] {
+                *c = ((*c as u32 * alpha + 127) / 255) as u8;
+            }
+        }
+        let image = RgbaImage::from_raw(self.width as u32, self.height as u32, rgba).expect("frame is width x height");
+        let mut resized = image::imageops::resize(&image, width as u32, height as u32, FilterType::Triangle).into_raw();
+        for pixel in resized.chunks_exact_mut(4) {
+            let alpha = pixel[3] as u32;
+            for c in &mut pixel[..3] {
+                // Fully transparent pixels stay black
+                if let Some(straight) = (*c as u32 * 255 + alpha / 2).checked_div(alpha) {
+                    *c = straight.min(255) as u8;
+                }
+            }
+        }
+        Frame { width, height, rgba: resized, duration_ms: self.duration_ms }
+    }
+}
+
+/// Sprite sheet atlas
+#[derive(Deserialize)]
+struct Atlas {
+    frames: Vec<AtlasFrame>,
+}
+
+#[derive(Deserialize)]
+struct AtlasFrame {
+    frame: AtlasRect,
+    #[serde(default)]
+    duration: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct AtlasRect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+/// Frames of a numbered sequence, `paths` in order, up to the first that
+/// fails, each passed to `each`; the sequence carries no durations
+/// Returns how many frames there were
+pub fn load_sequence(paths: &[PathBuf], mut each: impl FnMut(Frame)) -> usize {
+    let mut count = 0;
+    for path in paths.iter().take(MAX_CLIP_FRAMES) {
+        match image::open(path) {
+            Ok(image) => each(Frame::new(image.into_rgba8(), None)),
+            Err(e) => {
+                log::warn!("Failed to decode {:?}: {}", path, e);
+                break;
+            }
+        }
+        count += 1;
+    }
+    count
+}
+
+/// Every frame of an animated GIF, APNG or WebP (a still image is one
+/// frame), each passed to `each`; returns how many there were
+pub fn load_animation(path: &Path, mut each: impl FnMut(Frame)) -> Result<usize, String> {
+    let open = || File::open(path).map(BufReader::new).map_err(|e| format!("Failed to open {:?}: {}", path, e));
+    let invalid = |e: image::ImageError| format!("Failed to decode {:?}: {}", path, e);
+    let format = ImageFormat::from_path(path).map_err(invalid)?;
+
+    let frames = match format {
+        ImageFormat::Gif => GifDecoder::new(open()?).map_err(invalid)?.into_frames(),
+        ImageFormat::Png => {
+            let decoder = PngDecoder::new(open()?).map_err(invalid)?;
+            if !decoder.is_apng().map_err(invalid)? {
+                return load_still(path, each);
+            }
+            decoder.apng().map_err(invalid)?.into_frames()
+        }
+        ImageFormat::WebP => {
+            let decoder = WebPDecoder::new(open()?).map_err(invalid)?;
+            if !decoder.has_animation() {
+                return load_still(path, each);
+            }
+            decoder.into_frames()
+        }
+        _ => return load_still(path, each),
+    };
+
+    let mut count = 0;
+    for frame in frames.take(MAX_CLIP_FRAMES) {
+        let frame = frame.map_err(invalid)?;
+        let (numerator, denominator) = frame.delay().numer_denom_ms();
+        let duration_ms = (numerator as u64).checked_div(denominator as u64);
+        each(Frame::new(frame.into_buffer(), duration_ms));
+        count += 1;
+    }
+    if count == 0 {
+        return Err(format!("No frames in {:?}", path));
+    }
+    Ok(count)
+}
+
+fn load_still(path: &Path, mut each: impl FnMut(Frame)) -> Result<usize, String> {
+    let image = image::open(path).map_err(|e| format!("Failed to decode {:?}: {}", path, e))?;
+    each(Frame::new(image.into_rgba8(), None));
+    Ok(1)
+}
+
+/// The frames an atlas cuts out of a sprite sheet, in the atlas's order,
+/// each passed to `each`; returns how many there were
+pub fn load_sprite_sheet(sheet: &Path, atlas: &Path, mut each: impl FnMut(Frame)) -> Result<usize, String> {
+    let atlas: Atlas = std::fs::read_to_string(atlas)
+        .map_err(|e| format!("Failed to read {:?}: {}", atlas, e))
+        .and_then(|contents| serde_json::from_str(&contents).map_err(|e| format!("Invalid atlas {:?}: {}", atlas, e)))?;
+    let image = image::open(sheet)
+        .map_err(|e| format!("Failed to decode {:?}: {}", sheet, e))?
+        .into_rgba8();
+
+    // Checked up front so a bad atlas hands over no frames at all
+    let rects = atlas.frames.into_iter().take(MAX_CLIP_FRAMES).collect::<Vec<_>>();
+    if let Some(rect) = rects.iter().map(|f| &f.frame).find(|rect| {
+        rect.w == 0 || rect.h == 0 || rect.x + rect.w > image.width() || rect.y + rect.h > image.height()
+    }) {
+        return Err(format!("Atlas frame at ({}, {}) is outside {:?}", rect.x, rect.y, sheet));
+    }
+    if rects.is_empty() {
+        return Err(format!("No frames in the atlas of {:?}", sheet));
+    }
+    for AtlasFrame { frame: rect, duration } in &rects {
+        let cut = image::imageops::crop_imm(&image, rect.x, rect.y, rect.w, rect.h).to_image();
+        each(Frame::new(cut, *duration));
+    }
+    Ok(rects.len())
+}
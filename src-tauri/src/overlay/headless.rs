@@ -0,0 +1,275 @@
+//! Headless overlay backend - The overlay's window kept in memory
+//! Nothing is drawn, but the core runs exactly as it does on screen, so the
+//! chat flow and animation work where there is no native backend. Every
+//! operation is recorded, and the window's current state can be read back,
+//! so tests can drive the core (`handle_click`, `handle_key`, ...) and check
+//! what the user would have seen. `CATPANION_OVERLAY_BACKEND=headless`
+//! selects it on any platform, e.g. for CI.
+
+use super::backend::{OverlayBackend, Point, Rect};
+use super::frames::Frame;
+use std::sync::Mutex;
+
+// Pretend screen, a common laptop resolution
+const SCREEN_WIDTH: f64 = 1920.0;
+const SCREEN_HEIGHT: f64 = 1080.0;
+
+/// One thing the core asked the window system to do, in call order
+#[derive(Clone, Debug, PartialEq)]
+pub enum HeadlessEvent {
+    PanelCreated(Rect),
+    PanelClosed,
+    PanelShown,
+    PanelHidden,
+    PanelMoved(Point),
+    /// Clip and frame index
+    FrameShown(usize, usize),
+    /// Cross-fade length in milliseconds
+    FramesFaded(u64),
+    ClickFeedback,
+    HandCursor(bool),
+    /// With the placeholder shown
+    ChatInputShown(String),
+    ChatInputHidden,
+    /// The question the core took from the input
+    ChatInputTaken(String),
+    ThinkingShown,
+    ThinkingHidden,
+    ResponseShown,
+    ResponseText(String),
+    ResponseHidden,
+}
+
+/// What is on the pretend screen right now
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HeadlessWindow {
+    /// Frames loaded per clip, indexed like the animation graph's clips
+    pub frame_counts: Vec<usize>,
+    pub panel: Option<Rect>,
+    pub visible: bool,
+    /// Covered up, see `set_occluded`
+    pub occluded: bool,
+    /// The clip and frame last shown
+    pub frame: Option<(usize, usize)>,
+    pub hand_cursor: bool,
+    /// `Some` with the typed text while the chat input is open
+    pub chat_input: Option<String>,
+    pub placeholder: String,
+    pub thinking: bool,
+    /// `Some` with the text typed out so far while the response box is up
+    pub response: Option<String>,
+}
+
+#[derive(Default)]
+struct Recorder {
+    window: HeadlessWindow,
+    events: Vec<HeadlessEvent>,
+}
+
+pub struct HeadlessBackend {
+    screen: Mutex<Rect>,
+    focused_screen: Mutex<Option<Rect>>,
+    recorder: Mutex<Recorder>,
+}
+
+impl HeadlessBackend {
+    pub fn new() -> Self {
+        HeadlessBackend {
+            screen: Mutex::new(Rect::new(0.0, 0.0, SCREEN_WIDTH, SCREEN_HEIGHT)),
+            focused_screen: Mutex::new(None),
+            recorder: Mutex::new(Recorder::default()),
+        }
+    }
+
+    /// Apply `f` to the window and record `event`
+    fn record(&self, event: HeadlessEvent, f: impl FnOnce(&mut HeadlessWindow)) {
+        let mut recorder = self.recorder.lock().unwrap();
+        f(&mut recorder.window);
+        recorder.events.push(event);
+    }
+}
+
+// Read back by tests; the app itself never looks
+#[cfg(test)]
+impl HeadlessBackend {
+    /// The window as it is now
+    pub fn window(&self) -> HeadlessWindow {
+        self.recorder.lock().unwrap().window.clone()
+    }
+
+    /// Everything recorded so far
+    pub fn events(&self) -> Vec<HeadlessEvent> {
+        self.recorder.lock().unwrap().events.clone()
+    }
+
+    /// Everything recorded since the last call, clearing the record
+    pub fn take_events(&self) -> Vec<HeadlessEvent> {
+        std::mem::take(&mut self.recorder.lock().unwrap().events)
+    }
+
+    /// Type into the chat input, as a user would; ignored while it is closed
+    pub fn type_text(&self, text: &str) {
+        if let Some(input) = self.recorder.lock().unwrap().window.chat_input.as_mut() {
+            input.push_str(text);
+        }
+    }
+
+    /// Pretend the pointer moved to a screen at `frame`
+    pub fn set_active_screen(&self, frame: Rect) {
+        *self.screen.lock().unwrap() = frame;
+    }
+
+    /// Pretend the focused window is on a screen at `frame`, `None` to not say
+    pub fn set_focused_screen(&self, frame: Option<Rect>) {
+        *self.focused_screen.lock().unwrap() = frame;
+    }
+
+    /// Pretend something covered the panel or stopped covering it
+    pub fn set_occluded(&self, occluded: bool) {
+        self.recorder.lock().unwrap().window.occluded = occluded;
+    }
+}
+
+impl OverlayBackend for HeadlessBackend {
+    fn name(&self) -> &'static str {
+        "headless"
+    }
+
+    fn active_screen(&self) -> Rect {
+        *self.screen.lock().unwrap()
+    }
+
+    fn focused_screen(&self) -> Option<Rect> {
+        *self.focused_screen.lock().unwrap()
+    }
+
+    fn scale_factor(&self) -> f64 {
+        1.0
+    }
+
+    fn add_frames(&self, clip: usize, frames: &[Frame]) -> usize {
+        // Nothing is drawn, so the pixels are only counted
+        let frame_counts = &mut self.recorder.lock().unwrap().window.frame_counts;
+        if frame_counts.len() <= clip {
+            frame_counts.resize(clip + 1, 0);
+        }
+        frame_counts[clip] += frames.len();
+        frame_counts[clip]
+    }
+
+    fn frame_count(&self, clip: usize) -> usize {
+        let recorder = self.recorder.lock().unwrap();
+        recorder.window.frame_counts.get(clip).copied().unwrap_or(0)
+    }
+
+    fn create_panel(&self, frame: Rect) -> Result<(), String> {
+        self.record(HeadlessEvent::PanelCreated(frame), |window| {
+            *window = HeadlessWindow {
+                frame_counts: std::mem::take(&mut window.frame_counts),
+                panel: Some(frame),
+                visible: true,
+                ..HeadlessWindow::default()
+            };
+        });
+        Ok(())
+    }
+
+    fn close_panel(&self) {
+        self.record(HeadlessEvent::PanelClosed, |window| *window = HeadlessWindow::default());
+    }
+
+    fn show_panel(&self) {
+        self.record(HeadlessEvent::PanelShown, |window| window.visible = window.panel.is_some());
+    }
+
+    fn hide_panel(&self) {
+        self.record(HeadlessEvent::PanelHidden, |window| window.visible = false);
+    }
+
+    fn is_panel_visible(&self) -> bool {
+        self.recorder.lock().unwrap().window.visible
+    }
+
+    fn is_panel_occluded(&self) -> bool {
+        self.recorder.lock().unwrap().window.occluded
+    }
+
+    fn panel_frame(&self) -> Option<Rect> {
+        self.recorder.lock().unwrap().window.panel
+    }
+
+    fn move_panel(&self, origin: Point) {
+        self.record(HeadlessEvent::PanelMoved(origin), |window| {
+            if let Some(panel) = window.panel.as_mut() {
+                panel.x = origin.x;
+                panel.y = origin.y;
+            }
+        });
+    }
+
+    fn show_frame(&self, clip: usize, index: usize) {
+        self.record(HeadlessEvent::FrameShown(clip, index), |window| window.frame = Some((clip, index)));
+    }
+
+    fn fade_frames(&self, duration_ms: u64) {
+        self.record(HeadlessEvent::FramesFaded(duration_ms), |_| {});
+    }
+
+    fn play_click_feedback(&self) {
+        self.record(HeadlessEvent::ClickFeedback, |_| {});
+    }
+
+    fn set_hand_cursor(&self, hand: bool) {
+        self.record(HeadlessEvent::HandCursor(hand), |window| window.hand_cursor = hand);
+    }
+
+    fn show_chat_input(&self, placeholder: &str) {
+        self.record(HeadlessEvent::ChatInputShown(placeholder.to_string()), |window| {
+            window.chat_input.get_or_insert_with(String::new);
+            window.placeholder = placeholder.to_string();
+        });
+    }
+
+    fn hide_chat_input(&self) {
+        self.record(HeadlessEvent::ChatInputHidden, |window| window.chat_input = None);
+    }
+
+    fn take_chat_input(&self) -> String {
+        let mut recorder = self.recorder.lock().unwrap();
+        let text = recorder
+            .window
+            .chat_input
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default();
+        recorder.events.push(HeadlessEvent::ChatInputTaken(text.clone()));
+        text
+    }
+
+    fn show_thinking(&self) {
+        self.record(HeadlessEvent::ThinkingShown, |window| window.thinking = true);
+    }
+
+    fn hide_thinking(&self) {
+        self.record(HeadlessEvent::ThinkingHidden, |window| window.thinking = false);
+    }
+
+    fn show_response(&self) {
+        self.record(HeadlessEvent::ResponseShown, |window| window.response = Some(String::new()));
+    }
+
+    fn set_response_text(&self, text: &str) {
+        self.record(HeadlessEvent::ResponseText(text.to_string()), |window| {
+            window.response = Some(text.to_string())
+        });
+    }
+
+    fn hide_response(&self) {
+        self.record(HeadlessEvent::ResponseHidden, |window| window.response = None);
+    }
+
+    // There is no input to monitor; tests call the overlay's handlers directly
+    fn start_input_monitor(&self) {}
+
+    fn stop_input_monitor(&self) {}
+}
@@ -0,0 +1,1426 @@
+//! Overlay module - The floating cat and its chat, on any platform
+//! This core owns the chat flow, animation scheduling, positioning and
+//! hit-testing; drawing and input go through an `OverlayBackend`
+//! (Cocoa on macOS, Wayland layer-shell or X11 on Linux, an in-memory one
+//! where none of those is available)
+
+pub mod animation;
+pub mod backend;
+pub mod cache;
+pub mod clock;
+#[cfg(target_os = "linux")]
+mod canvas;
+#[cfg(target_os = "macos")]
+mod cocoa;
+pub mod frames;
+pub mod headless;
+mod screens;
+#[cfg(target_os = "linux")]
+mod wayland;
+#[cfg(target_os = "linux")]
+mod x11;
+
+use crate::chat_state::{self, ChatState};
+use crate::settings::ScreenFollow;
+use crate::skins::Skin;
+use animation::{AnimationGraph, Switch, Trigger};
+use backend::{OverlayBackend, OverlayKey, Point, Rect, ScreenEvent};
+use frames::Frame;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+// Where the panel sits relative to the active screen's bottom-right corner:
+// nudged right towards the edge and down below it
+const SCREEN_EDGE_OFFSET_X: f64 = 40.0;
+const SCREEN_EDGE_OFFSET_Y: f64 = 60.0;
+
+// The backend everything is drawn with, picked on first use
+static BACKEND: Mutex<Option<Arc<dyn OverlayBackend>>> = Mutex::new(None);
+
+// The skin the overlay was created with
+static SKIN: Mutex<Option<Arc<Skin>>> = Mutex::new(None);
+
+// Last click timestamp for debouncing (in milliseconds)
+static LAST_CLICK_TIME: AtomicUsize = AtomicUsize::new(0);
+
+// Track if hand cursor is currently shown (for global mouse move handler)
+static CURSOR_IS_HAND: AtomicBool = AtomicBool::new(false);
+
+// Store current response text for typing effect
+static CURRENT_RESPONSE: Mutex<String> = Mutex::new(String::new());
+static RESPONSE_CHAR_INDEX: AtomicUsize = AtomicUsize::new(0);
+// Set once the provider has finished streaming the current response
+static RESPONSE_COMPLETE: AtomicBool = AtomicBool::new(false);
+// When the finished response is hidden, on the overlay clock; 0 until typed out
+static RESPONSE_HIDE_AT: AtomicU64 = AtomicU64::new(0);
+// Bumped for every new response and every hide; a typing task stops, and
+// doesn't hide the box, once it no longer matches the one it was started for
+static RESPONSE_GENERATION: AtomicUsize = AtomicUsize::new(0);
+// Bumped for every question sent and every cancel, so a cancelled request's
+// late tokens are dropped instead of reopening the response box
+static CHAT_REQUEST: AtomicUsize = AtomicUsize::new(0);
+
+static OVERLAY_WIDTH: Mutex<f64> = Mutex::new(320.0);
+
+// Track if screen monitor is running
+static MONITOR_RUNNING: AtomicBool = AtomicBool::new(false);
+
+// Track if animation is running
+static ANIMATION_RUNNING: AtomicBool = AtomicBool::new(false);
+
+// How often the animation checks for a frame that is still decoding
+const LOADING_POLL_MS: u64 = 15;
+
+// How often loops paused behind other windows check whether the overlay
+// can be seen again
+const OCCLUDED_POLL_MS: u64 = 1000;
+
+// How long loops sleep while the overlay is hidden; showing it wakes them
+const HIDDEN_POLL_MS: u64 = 60_000;
+
+// How often the typing effect reveals more of the response
+const TYPING_MS: u64 = 30;
+
+// How long a typed-out response stays up before the cat goes back to idle
+const RESPONSE_LINGER_MS: u64 = 5000;
+
+// Frames skipped at most to catch up with the clock; further behind (the
+// machine slept, say) the animation carries on from where it is
+const MAX_SKIPPED_FRAMES: usize = 50;
+
+/// Where the animation is: a clip of the animation graph and a frame in it
+/// Times are on the animation clock (see `clock`)
+struct Playhead {
+    clip: usize,
+    frame: usize,
+    /// When the current frame's time started
+    frame_started: u64,
+    /// When the clip was entered or last triggered, for idle timeouts
+    since: u64,
+    /// When it was paused, while the overlay can't be seen
+    paused_at: Option<u64>,
+    /// Source clip and frame last handed to the backend
+    shown: Option<(usize, usize)>,
+}
+
+impl Playhead {
+    fn new(clip: usize, now: u64) -> Self {
+        Playhead { clip, frame: 0, frame_started: now, since: now, paused_at: None, shown: None }
+    }
+
+    fn enter(&mut self, clip: usize, now: u64) {
+        *self = Playhead { shown: self.shown, ..Playhead::new(clip, now) };
+    }
+
+    /// Freeze on the current frame
+    fn pause(&mut self, now: u64) {
+        self.paused_at.get_or_insert(now);
+    }
+
+    /// Carry on from where `pause` stopped, as if no time had passed, so
+    /// frames aren't skipped and idle timeouts don't fire for the pause
+    fn resume(&mut self, now: u64) {
+        if let Some(paused_at) = self.paused_at.take() {
+            let paused_ms = now.saturating_sub(paused_at);
+            self.frame_started += paused_ms;
+            self.since += paused_ms;
+        }
+    }
+}
+
+/// What the frame loader has handed the backend for one clip
+#[derive(Default)]
+struct LoadedClip {
+    /// How long each frame asks to be shown, from its file
+    durations: Vec<Option<u64>>,
+    /// Every frame is in, so the clip may wrap or move on
+    complete: bool,
+}
+
+// Frames loaded so far, indexed by source clip (see `AnimationGraph::source`)
+static LOADED_CLIPS: Mutex<Vec<LoadedClip>> = Mutex::new(Vec::new());
+
+// Bumped by every overlay created or closed; a loader only adds frames,
+// under this lock, while it still holds the current value
+static LOAD_GENERATION: Mutex<usize> = Mutex::new(0);
+
+// Current clip and frame, `None` before the first overlay is created
+static PLAYHEAD: Mutex<Option<Playhead>> = Mutex::new(None);
+
+// Which screen the panel is on and whether it should move
+static SCREENS: Mutex<screens::Tracker> = Mutex::new(screens::Tracker::new());
+
+// Notified whenever the overlay is shown or hidden or the screens change, so
+// loops idling carry on at once instead of at their next poll
+static WAKE: tokio::sync::Notify = tokio::sync::Notify::const_new();
+
+fn backend() -> Arc<dyn OverlayBackend> {
+    BACKEND
+        .lock()
+        .unwrap()
+        .get_or_insert_with(|| {
+            let backend = if std::env::var("CATPANION_OVERLAY_BACKEND").as_deref() == Ok("headless") {
+                Arc::new(headless::HeadlessBackend::new())
+            } else {
+                default_backend()
+            };
+            log::info!("Using the {} overlay backend", backend.name());
+            backend
+        })
+        .clone()
+}
+
+/// Draw with `backend` from now on, e.g. a `HeadlessBackend` a test keeps
+/// a handle to; call before `create_overlay`
+#[cfg(test)]
+pub fn set_backend(backend: Arc<dyn OverlayBackend>) {
+    log::info!("Using the {} overlay backend", backend.name());
+    *BACKEND.lock().unwrap() = Some(backend);
+}
+
+#[cfg(target_os = "macos")]
+fn default_backend() -> Arc<dyn OverlayBackend> {
+    Arc::new(cocoa::CocoaBackend)
+}
+
+/// Wayland when in a Wayland session (XWayland can't keep us on top or
+/// pass clicks through reliably), then X11, then headless
+#[cfg(target_os = "linux")]
+fn default_backend() -> Arc<dyn OverlayBackend> {
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        match wayland::WaylandBackend::connect() {
+            Ok(backend) => return Arc::new(backend),
+            Err(e) => log::warn!("Wayland overlay unavailable ({}), trying X11", e),
+        }
+    }
+    match x11::X11Backend::connect() {
+        Ok(backend) => Arc::new(backend),
+        Err(e) => {
+            log::warn!("X11 overlay unavailable ({}), running headless", e);
+            Arc::new(headless::HeadlessBackend::new())
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+fn default_backend() -> Arc<dyn OverlayBackend> {
+    Arc::new(headless::HeadlessBackend::new())
+}
+
+/// Where the frame files, fonts and other bundled resources live
+/// Tries the app bundle first, then the dev layouts; returns the last
+/// candidate if none exists so callers can log a useful path
+pub fn resource_path(relative: &str) -> PathBuf {
+    let exe_path = std::env::current_exe().unwrap();
+    let candidates = [
+        exe_path.parent().unwrap().join("../Resources/resources").join(relative),
+        PathBuf::from("resources").join(relative),
+        PathBuf::from("src-tauri/resources").join(relative),
+    ];
+    let fallback = candidates[2].clone();
+    candidates.into_iter().find(|p| p.exists()).unwrap_or(fallback)
+}
+
+/// Bottom-left corner of a `width`-wide panel tucked into the screen's
+/// bottom-right corner
+fn corner_origin(screen: Rect, width: f64) -> Point {
+    Point::new(
+        screen.x + screen.width - width + SCREEN_EDGE_OFFSET_X,
+        screen.y - SCREEN_EDGE_OFFSET_Y,
+    )
+}
+
+/// The skin the overlay draws, the active one if no overlay was created yet
+pub fn skin() -> Arc<Skin> {
+    let mut skin = SKIN.lock().unwrap();
+    skin.get_or_insert_with(|| Arc::new(crate::skins::active())).clone()
+}
+
+/// Draw `skin` until the next overlay or skin change, e.g. a changed copy
+/// in tests
+#[cfg(test)]
+pub fn set_skin(skin: Skin) {
+    *SKIN.lock().unwrap() = Some(Arc::new(skin));
+}
+
+/// Size the sprite is drawn at, in points
+pub fn sprite_size() -> (f64, f64) {
+    let size = skin().manifest.frame_size;
+    (size.width, size.height)
+}
+
+/// Where the sprite sits in a `panel_width`-wide panel, bottom-left origin:
+/// the skin's anchor point on the panel's bottom centre
+pub fn cat_frame(panel_width: f64) -> Rect {
+    let skin = skin();
+    let size = skin.manifest.frame_size;
+    let anchor = skin.manifest.anchor();
+    Rect::new(panel_width / 2.0 - anchor.x, anchor.y - size.height, size.width, size.height)
+}
+
+/// Screen area where clicks hit the cat in a panel with frame `panel`
+fn cat_bounds(panel: Rect) -> Rect {
+    let cat = cat_frame(panel.width);
+    let hit_box = skin().manifest.hit_box();
+    // The hit-box is measured from the sprite's top-left corner
+    Rect::new(
+        panel.x + cat.x + hit_box.x,
+        panel.y + cat.y + cat.height - hit_box.y - hit_box.height,
+        hit_box.width,
+        hit_box.height,
+    )
+}
+
+pub fn create_overlay(width: f64, height: f64) {
+    log::info!("create_overlay called with width={}, height={}", width, height);
+    let Some(graph) = open_panel(width, height) else {
+        return;
+    };
+
+    // Frames arrive in the background; the animation shows each as it lands
+    start_frame_loader(graph);
+    start_animation();
+
+    // Start click monitor
+    start_click_monitor();
+}
+
+/// Load the active skin and put an empty panel on the screen the overlay
+/// should be on, returning the skin's animation graph
+fn open_panel(width: f64, height: f64) -> Option<Arc<AnimationGraph>> {
+    // Store width for repositioning later
+    *OVERLAY_WIDTH.lock().unwrap() = width;
+
+    let backend = backend();
+
+    let skin = Arc::new(crate::skins::active());
+    *SKIN.lock().unwrap() = Some(skin.clone());
+    let graph = animation::load(&skin);
+    *LOADED_CLIPS.lock().unwrap() = graph.clips().iter().map(|_| LoadedClip::default()).collect();
+    reset_playhead(graph.start());
+
+    let screen = target_screen(&*backend, crate::settings::current().screen_follow);
+    let origin = corner_origin(screen, width);
+    if let Err(e) = backend.create_panel(Rect::new(origin.x, origin.y, width, height)) {
+        log::error!("Failed to create overlay: {}", e);
+        return None;
+    }
+    SCREENS.lock().unwrap().placed(screen);
+    Some(graph)
+}
+
+/// Load the graph's clips off the main thread, from the frame cache or by
+/// decoding them, in `AnimationGraph::load_order`
+fn start_frame_loader(graph: Arc<AnimationGraph>) {
+    let generation = {
+        let mut current = LOAD_GENERATION.lock().unwrap();
+        *current += 1;
+        *current
+    };
+    crate::tasks::spawn("frame-loader", async move {
+        let loading = tauri::async_runtime::spawn_blocking(move || load_frames(&graph, generation));
+        if let Err(e) = loading.await {
+            log::error!("Frame loader failed: {}", e);
+        }
+    });
+}
+
+fn load_frames(graph: &AnimationGraph, generation: usize) {
+    let started = Instant::now();
+    // Pre-scaled for the sharpest screen, so the backends only ever blit
+    let (width, height) = sprite_size();
+    let scale = backend().scale_factor();
+    let size = ((width * scale).round() as usize, (height * scale).round() as usize);
+
+    let (mut cached, mut frame_count, mut bytes) = (0, 0, 0);
+    for clip in graph.load_order() {
+        let clip_started = Instant::now();
+        let key = cache::key(&graph.files(clip), size);
+        let from_cache = key.as_deref().and_then(cache::read);
+        let hit = from_cache.is_some();
+
+        let mut added = true;
+        let count;
+        if let Some(frames) = from_cache {
+            added = add_frames(clip, &frames, generation);
+            count = frames.len();
+            bytes += frames.iter().map(|f| f.rgba.len()).sum::<usize>();
+        } else {
+            // Handed over one by one, so the start clip plays while it decodes
+            let mut scaled = Vec::new();
+            let decoded = graph.decode(clip, |frame| {
+                if !added {
+                    return;
+                }
+                let frame = frame.scaled(size.0, size.1);
+                added &= add_frames(clip, std::slice::from_ref(&frame), generation);
+                scaled.push(frame);
+            });
+            count = scaled.len();
+            bytes += scaled.iter().map(|f| f.rgba.len()).sum::<usize>();
+            match (decoded, key) {
+                (Err(e), _) => log::error!("Clip {:?}: {}", graph.name(clip), e),
+                (Ok(()), Some(key)) if added => cache::write(&key, &scaled),
+                _ => {}
+            }
+        }
+        if !added {
+            log::info!("Overlay closed, stopped loading frames");
+            return;
+        }
+        if let Some(loaded) = LOADED_CLIPS.lock().unwrap().get_mut(clip) {
+            loaded.complete = true;
+        }
+        cached += hit as usize;
+        frame_count += count;
+        log::info!(
+            "Loaded {} {} frames in {}ms{}",
+            count,
+            graph.name(clip),
+            clip_started.elapsed().as_millis(),
+            if hit { " from the cache" } else { "" }
+        );
+    }
+    log::info!(
+        "Loaded {} frames ({} KiB at {}x{}) in {}ms, {} clips from the cache",
+        frame_count,
+        bytes / 1024,
+        size.0,
+        size.1,
+        started.elapsed().as_millis(),
+        cached
+    );
+}
+
+/// Give the backend more frames of `clip`, unless the overlay they were
+/// loaded for is gone; returns whether it still exists
+fn add_frames(clip: usize, frames: &[Frame], generation: usize) -> bool {
+    let current = LOAD_GENERATION.lock().unwrap();
+    if *current != generation {
+        return false;
+    }
+    let count = backend().add_frames(clip, frames);
+    if let Some(loaded) = LOADED_CLIPS.lock().unwrap().get_mut(clip) {
+        loaded.durations.extend(frames.iter().map(|frame| frame.duration_ms));
+        loaded.durations.truncate(count);
+    }
+    true
+}
+
+pub fn show_overlay() {
+    backend().show_panel();
+    WAKE.notify_waiters();
+}
+
+/// Hide the panel; the animation and screen monitor idle until it is shown
+pub fn hide_overlay() {
+    cancel_chat();
+    backend().hide_panel();
+    WAKE.notify_waiters();
+}
+
+pub fn close_overlay() {
+    // Stop animation, input and any chat request first
+    stop_animation();
+    stop_click_monitor();
+    cancel_chat();
+
+    // A loader still running stops before the frames are dropped
+    *LOAD_GENERATION.lock().unwrap() += 1;
+    backend().close_panel();
+    SCREENS.lock().unwrap().reset();
+}
+
+pub fn is_visible() -> bool {
+    backend().is_panel_visible()
+}
+
+/// Whether the overlay is shown and not covered up, so worth drawing
+fn is_seen() -> bool {
+    let backend = backend();
+    backend.is_panel_visible() && !backend.is_panel_occluded()
+}
+
+/// Whether the window system should be watched closely: the panel is shown
+/// or the chat input is open
+pub fn wants_input() -> bool {
+    is_visible() || chat_state::current() == ChatState::InputOpen
+}
+
+/// Run one step of a background loop, then sleep for the milliseconds it
+/// returns or until the overlay is shown or hidden, whichever comes first
+/// Listening starts before the step, so a change during it isn't missed
+pub async fn step_then_sleep(step: impl FnOnce() -> u64) {
+    let wake = WAKE.notified();
+    tokio::pin!(wake);
+    wake.as_mut().enable();
+    let ms = step();
+    tokio::select! {
+        _ = crate::tasks::sleep_ms(ms) => {}
+        _ = wake => {}
+    }
+}
+
+/// React to changed settings - a new overlay size or skin rebuilds the panel
+/// Delays, debounce and screen following are read live and need nothing here
+pub fn apply_settings(previous: &crate::settings::Settings, settings: &crate::settings::Settings) {
+    let size_changed = previous.overlay_width != settings.overlay_width
+        || previous.overlay_height != settings.overlay_height;
+    let skin_changed = previous.skin != settings.skin;
+    if skin_changed {
+        // Picked up again by `skin()` or the next `create_overlay`
+        *SKIN.lock().unwrap() = None;
+    }
+    if !(size_changed || skin_changed) || backend().panel_frame().is_none() {
+        return;
+    }
+
+    let (width, height) = (settings.overlay_width, settings.overlay_height);
+    log::info!("Overlay is now {}x{} with the {} skin, recreating overlay", width, height, settings.skin);
+    crate::events::run_on_main_thread(move || {
+        close_overlay();
+        create_overlay(width, height);
+    });
+}
+
+/// The screen the overlay should be on, going by `follow`
+fn target_screen(backend: &dyn OverlayBackend, follow: ScreenFollow) -> Rect {
+    match follow {
+        ScreenFollow::Mouse => backend.active_screen(),
+        // Backends that can't tell go by the pointer
+        ScreenFollow::FocusedWindow => backend.focused_screen().unwrap_or_else(|| backend.active_screen()),
+    }
+}
+
+fn move_to_screen(screen: Rect) {
+    let width = *OVERLAY_WIDTH.lock().unwrap();
+    backend().move_panel(corner_origin(screen, width));
+}
+
+/// Move the overlay to the screen it should be on right away, skipping the
+/// screen monitor's switch delay
+pub fn move_to_active_screen() {
+    let backend = backend();
+    if backend.panel_frame().is_none() {
+        return;
+    }
+    let screen = target_screen(&*backend, crate::settings::current().screen_follow);
+    move_to_screen(screen);
+    SCREENS.lock().unwrap().placed(screen);
+}
+
+/// A backend saw the screens change or focus move; the screen monitor takes
+/// another look once the burst of events settles
+pub fn handle_screen_event(event: ScreenEvent) {
+    if event == ScreenEvent::FocusMoved && crate::settings::current().screen_follow != ScreenFollow::FocusedWindow {
+        return;
+    }
+    log::debug!("Screen event: {:?}", event);
+    SCREENS.lock().unwrap().event(event, clock::now_ms());
+    WAKE.notify_waiters();
+}
+
+/// Start keeping the overlay on the screen it should be on (see
+/// `ScreenFollow`), woken by screen events and checking in between
+pub fn start_screen_monitor() {
+    if MONITOR_RUNNING.swap(true, Ordering::SeqCst) {
+        return; // Already running
+    }
+
+    crate::tasks::spawn("screen-monitor", async {
+        while MONITOR_RUNNING.load(Ordering::SeqCst) {
+            step_then_sleep(step_screen_monitor).await;
+        }
+    });
+}
+
+/// Move the panel if the tracker says so, returning how long until it
+/// wants to look again
+fn step_screen_monitor() -> u64 {
+    let backend = backend();
+    if !backend.is_panel_visible() {
+        return HIDDEN_POLL_MS;
+    }
+    let settings = crate::settings::current();
+    let (screen, wait) = SCREENS.lock().unwrap().step(
+        clock::now_ms(),
+        || target_screen(&*backend, settings.screen_follow),
+        settings.screen_switch_delay_ms,
+        settings.screen_monitor_interval_ms,
+    );
+    if let Some(screen) = screen {
+        log::info!("Moving the overlay to the screen at ({}, {})", screen.x, screen.y);
+        move_to_screen(screen);
+    }
+    wait
+}
+
+/// Stop the screen monitor
+pub fn stop_screen_monitor() {
+    MONITOR_RUNNING.store(false, Ordering::SeqCst);
+}
+
+/// Start the animation loop
+/// Plays the animation graph from its start clip: `once` clips hand over to
+/// their `next`, loops repeat until a trigger or idle timeout moves the cat on
+pub fn start_animation() {
+    if ANIMATION_RUNNING.swap(true, Ordering::SeqCst) {
+        return; // Already running
+    }
+
+    reset_playhead(animation::graph().start());
+
+    crate::tasks::spawn("animation", async {
+        // Frames still loading are waited for frame by frame in `step_animation`
+        while ANIMATION_RUNNING.load(Ordering::SeqCst) {
+            step_then_sleep(|| step_animation().max(min_step_ms())).await;
+        }
+    });
+}
+
+/// Least time between animation steps: the `battery_frame_ms` setting while
+/// the chat is idle on battery, nothing otherwise
+fn min_step_ms() -> u64 {
+    if chat_state::current() != ChatState::Idle || !crate::power::on_battery() {
+        return 0;
+    }
+    crate::settings::current().battery_frame_ms
+}
+
+fn reset_playhead(clip: usize) {
+    *PLAYHEAD.lock().unwrap() = Some(Playhead::new(clip, clock::now_ms()));
+}
+
+/// Move the playhead to the frame the clock says should be up and show it,
+/// returning how long until the next one is due
+/// Frames whose time passed while the loop was late are skipped, not shown
+/// late, so the animation never drifts. While the overlay is hidden or
+/// covered the playhead is paused instead. Kept out of the async loop so no
+/// lock is ever held across an await
+fn step_animation() -> u64 {
+    let graph = animation::graph();
+    let hidden = !is_visible();
+    let seen = !hidden && !backend().is_panel_occluded();
+    let now = clock::now_ms();
+    let mut guard = PLAYHEAD.lock().unwrap();
+    let Some(playhead) = guard.as_mut() else {
+        return graph.frame_ms(graph.start(), None);
+    };
+    if !seen {
+        if playhead.paused_at.is_none() {
+            log::info!("Overlay {}, pausing the animation", if hidden { "hidden" } else { "covered" });
+        }
+        playhead.pause(now);
+        return if hidden { HIDDEN_POLL_MS } else { OCCLUDED_POLL_MS };
+    }
+    if playhead.paused_at.is_some() {
+        log::info!("Overlay can be seen again, resuming the animation");
+    }
+    playhead.resume(now);
+
+    let idle_ms = now.saturating_sub(playhead.since);
+    if let Some(switch) = graph.on_idle(playhead.clip, idle_ms) {
+        log::info!("Idle in {} for {}ms, switching to {}", graph.name(playhead.clip), idle_ms, graph.name(switch.to));
+        switch_clip(playhead, switch, now);
+    }
+
+    let mut skipped = 0;
+    let wait = loop {
+        let (clip, frame) = (playhead.clip, playhead.frame);
+        let source = graph.source(clip);
+        let frame_count = backend().frame_count(source);
+        let (complete, duration) = {
+            let loaded = LOADED_CLIPS.lock().unwrap();
+            let loaded = loaded.get(source);
+            let duration = loaded.and_then(|l| l.durations.get(frame).copied().flatten());
+            (loaded.map_or(true, |l| l.complete), duration)
+        };
+        let frame_ms = graph.frame_ms(clip, duration);
+        let frame_ends = playhead.frame_started + frame_ms;
+        if now < frame_ends {
+            break frame_ends - now;
+        }
+
+        // This frame's time is up, move on
+        let next = frame + 1;
+        if next < frame_count {
+            playhead.frame = next;
+        } else if !complete {
+            // The next frame is still decoding; hold this one until it lands,
+            // without making up for the wait by skipping frames afterwards
+            playhead.frame_started = now.saturating_sub(frame_ms);
+            break LOADING_POLL_MS;
+        } else {
+            match graph.after_last_frame(clip) {
+                Some(to) => {
+                    log::info!("{} complete, switching to {}", graph.name(clip), graph.name(to));
+                    playhead.enter(to, frame_ends);
+                    continue;
+                }
+                // Loop - wrap around
+                None => playhead.frame = 0,
+            }
+        }
+        playhead.frame_started = frame_ends;
+
+        skipped += 1;
+        if skipped > MAX_SKIPPED_FRAMES {
+            log::info!("Animation fell {}ms behind, carrying on from here", now - frame_ends);
+            playhead.frame_started = now;
+        }
+    };
+    if skipped > 1 {
+        log::debug!("Animation was late, skipped {} frames", skipped - 1);
+    }
+
+    let source = graph.source(playhead.clip);
+    let frame_count = backend().frame_count(source);
+    if frame_count > 0 {
+        let shown = (source, playhead.frame % frame_count);
+        // Unchanged frames aren't pushed again
+        if playhead.shown != Some(shown) {
+            backend().show_frame(shown.0, shown.1);
+            playhead.shown = Some(shown);
+        }
+    }
+    wait
+}
+
+/// Enter the clip a transition leads to, cross-fading if it asks for it
+fn switch_clip(playhead: &mut Playhead, switch: Switch, now: u64) {
+    if switch.blend_ms > 0 {
+        backend().fade_frames(switch.blend_ms);
+    }
+    playhead.enter(switch.to, now);
+}
+
+/// Tell the animation graph `trigger` happened, switching clips if one of
+/// its transitions applies to the current clip
+pub fn trigger_animation(trigger: Trigger) {
+    let graph = animation::graph();
+    let mut guard = PLAYHEAD.lock().unwrap();
+    let Some(playhead) = guard.as_mut() else {
+        return;
+    };
+    match graph.on_trigger(playhead.clip, trigger) {
+        Some(switch) => {
+            log::info!("{:?} in {}, switching to {}", trigger, graph.name(playhead.clip), graph.name(switch.to));
+            switch_clip(playhead, switch, clock::now_ms());
+        }
+        // Still counts as something happening
+        None => playhead.since = clock::now_ms(),
+    }
+}
+
+/// Stop the animation loop
+pub fn stop_animation() {
+    ANIMATION_RUNNING.store(false, Ordering::SeqCst);
+}
+
+/// Pointer moved to `location` (screen coordinates) - hand cursor over the cat
+pub fn handle_pointer_move(location: Point) {
+    let backend = backend();
+    let is_over_cat = backend
+        .panel_frame()
+        .is_some_and(|panel| cat_bounds(panel).contains(location));
+
+    if is_over_cat != CURSOR_IS_HAND.load(Ordering::SeqCst) {
+        backend.set_hand_cursor(is_over_cat);
+        CURSOR_IS_HAND.store(is_over_cat, Ordering::SeqCst);
+    }
+}
+
+/// Handle click at screen location
+pub fn handle_click(location: Point) {
+    // Debounce: ignore clicks within the configured window of the last click
+    let debounce_ms = crate::settings::current().click_debounce_ms as usize;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as usize;
+    let last_click = LAST_CLICK_TIME.load(Ordering::SeqCst);
+    let time_since_last = now.saturating_sub(last_click);
+
+    // Check if this is a duplicate event (within the debounce window)
+    if time_since_last < debounce_ms && last_click > 0 {
+        log::debug!("Debouncing click ({}ms since last)", time_since_last);
+        return;
+    }
+
+    // Store the current time AFTER checking to ensure first click goes through
+    LAST_CLICK_TIME.store(now, Ordering::SeqCst);
+
+    let state = chat_state::current();
+
+    let backend = backend();
+    let Some(panel_frame) = backend.panel_frame() else {
+        log::warn!("No panel found");
+        return;
+    };
+    let cat = cat_bounds(panel_frame);
+    let is_on_cat = cat.contains(location);
+    log::debug!("Click at ({}, {}), state={:?}, on cat: {}", location.x, location.y, state, is_on_cat);
+
+    if is_on_cat {
+        // Always animate click feedback on cat
+        backend.play_click_feedback();
+        trigger_animation(Trigger::Click);
+
+        match state {
+            ChatState::Idle => {
+                log::info!("Opening chat input");
+                show_chat_input();
+            }
+            ChatState::InputOpen => {
+                log::info!("Closing chat input");
+                hide_chat_input();
+            }
+            ChatState::Thinking | ChatState::Streaming | ChatState::Responding | ChatState::Error => {
+                log::debug!("Cancelling chat - thinking or responding");
+                cancel_chat();
+            }
+            ChatState::Cancelled => {}
+        }
+    }
+}
+
+/// Enter submits the open chat input; Escape closes it, or cancels a
+/// question being answered
+pub fn handle_key(key: OverlayKey) {
+    let state = chat_state::current();
+    log::info!("{:?} key pressed, state={:?}", key, state);
+    match (key, state) {
+        (OverlayKey::Enter, ChatState::InputOpen) => submit_input_from_window(),
+        (OverlayKey::Enter, _) => {}
+        (OverlayKey::Escape, ChatState::InputOpen) => hide_chat_input(),
+        (OverlayKey::Escape, ChatState::Idle | ChatState::Cancelled) => {}
+        (OverlayKey::Escape, _) => cancel_chat(),
+    }
+}
+
+/// Start click monitor for cat interaction
+pub fn start_click_monitor() {
+    backend().start_input_monitor();
+}
+
+/// Stop click monitor
+pub fn stop_click_monitor() {
+    backend().stop_input_monitor();
+}
+
+/// Move the chat to `next` (see `chat_state::transition`) and let the
+/// animation graph react to it
+fn set_chat_state(next: ChatState) -> Result<ChatState, String> {
+    let from = chat_state::transition(next)?;
+    if from != next {
+        if let Some(trigger) = Trigger::for_chat_state(next) {
+            trigger_animation(trigger);
+        }
+    }
+    Ok(from)
+}
+
+/// Open the chat input, greeting the user with the persona's placeholder
+pub fn show_chat_input() {
+    log::debug!("show_chat_input called");
+    if set_chat_state(ChatState::InputOpen).is_err() {
+        return; // Busy with a question
+    }
+    backend().show_chat_input(&crate::personas::greeting());
+    log::debug!("show_chat_input complete");
+}
+
+/// Hide chat input box
+pub fn hide_chat_input() {
+    // Closing the chat abandons any question still being answered
+    cancel_chat();
+    let _ = set_chat_state(ChatState::Idle);
+    backend().hide_chat_input();
+}
+
+/// Swap the chat input for the thinking indicator
+pub fn show_thinking() {
+    let backend = backend();
+    backend.hide_chat_input();
+    backend.show_thinking();
+}
+
+/// Hide thinking indicator
+pub fn hide_thinking() {
+    backend().hide_thinking();
+}
+
+/// Show the empty response box and start typing out text as it streams in
+pub fn begin_response() {
+    CURRENT_RESPONSE.lock().unwrap().clear();
+    RESPONSE_CHAR_INDEX.store(0, Ordering::SeqCst);
+    RESPONSE_COMPLETE.store(false, Ordering::SeqCst);
+    RESPONSE_HIDE_AT.store(0, Ordering::SeqCst);
+    let generation = RESPONSE_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+
+    hide_thinking();
+    backend().show_response();
+
+    start_typing_effect(generation);
+}
+
+/// Append streamed text to the current response
+pub fn append_response(text: &str) {
+    let mut guard = CURRENT_RESPONSE.lock().unwrap();
+    guard.push_str(text);
+}
+
+/// Mark the current response as complete so the typing effect can wind down
+pub fn finish_response() {
+    RESPONSE_COMPLETE.store(true, Ordering::SeqCst);
+}
+
+/// Show response box with typing effect
+pub fn show_response_with_typing(text: String) {
+    begin_response();
+    append_response(&text);
+    finish_response();
+}
+
+/// Start typing effect for response `generation`
+fn start_typing_effect(generation: usize) {
+    crate::tasks::spawn("typing", async move {
+        let mut typing = true;
+        while typing {
+            step_then_sleep(|| {
+                let next = step_typing(generation);
+                typing = next.is_some();
+                next.unwrap_or(0)
+            })
+            .await;
+        }
+    });
+}
+
+/// Reveal more of CURRENT_RESPONSE as it grows, then hide the box
+/// `RESPONSE_LINGER_MS` after it completes
+/// Returns how long to wait before the next step, or `None` once response
+/// `generation` is hidden or replaced
+fn step_typing(generation: usize) -> Option<u64> {
+    if RESPONSE_GENERATION.load(Ordering::SeqCst) != generation {
+        return None;
+    }
+    // Nobody can read along while the panel is covered
+    if !is_seen() {
+        return Some(OCCLUDED_POLL_MS);
+    }
+
+    let response = CURRENT_RESPONSE.lock().unwrap().clone();
+    let total = response.chars().count();
+    let idx = RESPONSE_CHAR_INDEX.load(Ordering::SeqCst);
+
+    if idx >= total {
+        if !RESPONSE_COMPLETE.load(Ordering::SeqCst) {
+            // Caught up with the stream - wait for more tokens
+            return Some(TYPING_MS);
+        }
+
+        // Typing complete; after a delay, hide response and return to idle
+        let now = clock::now_ms();
+        let hide_at = match RESPONSE_HIDE_AT.load(Ordering::SeqCst) {
+            0 => {
+                RESPONSE_HIDE_AT.store(now + RESPONSE_LINGER_MS, Ordering::SeqCst);
+                now + RESPONSE_LINGER_MS
+            }
+            hide_at => hide_at,
+        };
+        if now < hide_at {
+            return Some(hide_at - now);
+        }
+        hide_response();
+        return None;
+    }
+
+    // Reveal faster when the stream is far ahead of the typing
+    let shown = (idx + 1 + (total - idx) / 20).min(total);
+    RESPONSE_CHAR_INDEX.store(shown, Ordering::SeqCst);
+
+    // Update displayed text
+    let display_text: String = response.chars().take(shown).collect();
+    backend().set_response_text(&display_text);
+    Some(TYPING_MS)
+}
+
+/// Hide response box
+pub fn hide_response() {
+    RESPONSE_GENERATION.fetch_add(1, Ordering::SeqCst);
+    backend().hide_response();
+    let _ = set_chat_state(ChatState::Idle);
+}
+
+/// Abort the question being answered, if any, and return the chat to idle
+pub fn cancel_chat() {
+    let state = chat_state::current();
+    if !state.is_busy() && !state.shows_response() {
+        return;
+    }
+    if set_chat_state(ChatState::Cancelled).is_err() {
+        return;
+    }
+    log::info!("Cancelling chat (state={:?})", state);
+
+    CHAT_REQUEST.fetch_add(1, Ordering::SeqCst);
+    crate::ai::cancel("overlay");
+
+    hide_thinking();
+    hide_response(); // Back to idle
+}
+
+/// Send message to the chat provider
+/// Fails if the chat can't take a question now, e.g. one is being answered
+pub fn send_chat_message(message: String) -> Result<(), String> {
+    set_chat_state(ChatState::Thinking)?;
+    show_thinking();
+    let request = CHAT_REQUEST.fetch_add(1, Ordering::SeqCst) + 1;
+    let is_current = move || CHAT_REQUEST.load(Ordering::SeqCst) == request;
+
+    crate::tasks::spawn("chat", async move {
+        // The response box opens on the first token and types out the stream
+        let mut started = false;
+        let on_token = Box::new(move |token: &str| {
+            if !is_current() {
+                return;
+            }
+            if !started {
+                started = true;
+                let _ = set_chat_state(ChatState::Streaming);
+                begin_response();
+            }
+            append_response(token);
+            crate::ai::emit_token("overlay", token);
+        });
+
+        let result = crate::ai::ask("overlay", message, on_token).await;
+        if !is_current() {
+            // Cancelled (or superseded) while the request was finishing
+            return;
+        }
+        match result {
+            Ok(content) => {
+                finish_response();
+                let _ = set_chat_state(ChatState::Responding);
+                crate::ai::emit_done("overlay", &content);
+            }
+            Err(crate::ai::AiError::Cancelled) => {
+                // Whoever cancelled has already put the chat back to idle
+                log::info!("Chat request cancelled");
+            }
+            Err(e) => {
+                log::error!("Chat request failed: {}", e);
+                if chat_state::current() == ChatState::Streaming {
+                    // Keep whatever already streamed in
+                    finish_response();
+                    let _ = set_chat_state(ChatState::Responding);
+                } else {
+                    // Tell the user what to do about it (fix the key, wait, retry...)
+                    let _ = set_chat_state(ChatState::Error);
+                    show_response_with_typing(e.user_message());
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Submit from the chat input itself, where there is no caller to tell
+pub fn submit_input_from_window() {
+    if let Err(e) = submit_chat_input() {
+        log::warn!("Question not sent: {}", e);
+    }
+}
+
+/// Get current input text and send it to the chat provider
+/// Fails, leaving the text in the input, if the chat can't take a question now
+pub fn submit_chat_input() -> Result<(), String> {
+    let state = chat_state::current();
+    if !state.can_transition_to(ChatState::Thinking) {
+        return Err(format!("Can't send a question while the chat is {:?}", state));
+    }
+    let message = backend().take_chat_input();
+    if message.is_empty() {
+        return Ok(());
+    }
+    send_chat_message(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{ChatFuture, ChatProvider, ChatRequest, CheckFuture, TokenSink};
+    use clock::FakeClock;
+    use headless::{HeadlessBackend, HeadlessEvent};
+    use std::sync::MutexGuard;
+
+    // Every test frame asks to be shown this long, and so do the delay settings
+    const FRAME_MS: u64 = 100;
+    // Frames in every test clip
+    const FRAMES: usize = 4;
+    // The built-in skin's idle timeout
+    const IDLE_TIMEOUT_MS: u64 = 45_000;
+    // Real time a test waits for background tasks such as the typing effect
+    const WAIT_MS: u64 = 2_000;
+
+    /// An overlay drawn by a `HeadlessBackend` and timed by a `FakeClock`,
+    /// every clip of the built-in skin loaded with `FRAMES` blank frames
+    /// Nothing runs in the background; tests step the animation themselves
+    struct TestOverlay {
+        backend: Arc<HeadlessBackend>,
+        clock: Arc<FakeClock>,
+        _globals: MutexGuard<'static, ()>,
+    }
+
+    impl TestOverlay {
+        fn new() -> Self {
+            let globals = crate::lock_globals();
+            let backend = Arc::new(HeadlessBackend::new());
+            set_backend(backend.clone());
+            let clock = Arc::new(FakeClock::new());
+            clock::set_clock(clock.clone());
+            // The built-in skin's clips follow the delay settings
+            crate::settings::set_current(crate::settings::Settings {
+                spawn_delay_ms: FRAME_MS,
+                yawn_delay_ms: FRAME_MS,
+                idle_delay_ms: FRAME_MS,
+                ..Default::default()
+            });
+            // Whatever chat an earlier test left open
+            hide_chat_input();
+
+            let graph = open_panel(320.0, 320.0).expect("headless panels can't fail");
+            let generation = *LOAD_GENERATION.lock().unwrap();
+            for clip in 0..graph.clips().len() {
+                if graph.source(clip) == clip {
+                    let frames: Vec<Frame> = (0..FRAMES).map(|_| blank_frame()).collect();
+                    assert!(add_frames(clip, &frames, generation));
+                    LOADED_CLIPS.lock().unwrap()[clip].complete = true;
+                }
+            }
+            backend.take_events();
+            TestOverlay { backend, clock, _globals: globals }
+        }
+
+        /// Step the animation `ms` later, returning the frames it showed and
+        /// how long until it wants to step again
+        fn step_after(&self, ms: u64) -> (Vec<(usize, usize)>, u64) {
+            self.clock.advance(ms);
+            let wait = step_animation();
+            let shown = self
+                .backend
+                .take_events()
+                .into_iter()
+                .filter_map(|event| match event {
+                    HeadlessEvent::FrameShown(clip, frame) => Some((clip, frame)),
+                    _ => None,
+                })
+                .collect();
+            (shown, wait)
+        }
+
+        /// Play through spawn and yawn to the first frame of idle
+        fn play_to_idle(&self) {
+            self.step_after(0);
+            self.step_after(2 * FRAMES as u64 * FRAME_MS);
+            assert_eq!(self.clip(), "idle");
+            self.backend.take_events();
+        }
+
+        /// Name of the clip being played
+        fn clip(&self) -> String {
+            let clip = PLAYHEAD.lock().unwrap().as_ref().expect("no playhead").clip;
+            animation::graph().name(clip).to_string()
+        }
+
+        /// Step the typing effect through the typed-out response's time on
+        /// screen, checking it is hidden once that is up and not before
+        fn linger(&self) {
+            let generation = RESPONSE_GENERATION.load(Ordering::SeqCst);
+            assert_eq!(step_typing(generation), Some(RESPONSE_LINGER_MS));
+            self.clock.advance(RESPONSE_LINGER_MS - 1);
+            assert_eq!(step_typing(generation), Some(1));
+            assert!(self.backend.window().response.is_some());
+            self.clock.advance(1);
+            assert_eq!(step_typing(generation), None);
+            assert_eq!(self.backend.window().response, None);
+        }
+
+        /// Click at `location`, however soon after the last click
+        fn click(&self, location: Point) {
+            LAST_CLICK_TIME.store(0, Ordering::SeqCst);
+            handle_click(location);
+        }
+    }
+
+    fn blank_frame() -> Frame {
+        Frame { width: 1, height: 1, rgba: vec![0; 4], duration_ms: Some(FRAME_MS) }
+    }
+
+    /// Index of the built-in skin's clip called `name`
+    fn clip(name: &str) -> usize {
+        animation::graph().clips().iter().position(|c| c.name == name).expect("no such clip")
+    }
+
+    /// Wait up to `WAIT_MS` for `done`, returning whether it happened
+    async fn wait_for(done: impl Fn() -> bool) -> bool {
+        for _ in 0..WAIT_MS / 10 {
+            if done() {
+                return true;
+            }
+            crate::tasks::sleep_ms(10).await;
+        }
+        done()
+    }
+
+    const REPLY: &str = "Mrrp, wide awake.";
+
+    /// Answers every question with `REPLY`, a word at a time, once told to
+    #[derive(Default)]
+    struct FakeProvider {
+        answer: tokio::sync::Notify,
+    }
+
+    impl ChatProvider for FakeProvider {
+        fn name(&self) -> &str {
+            "fake"
+        }
+
+        fn model(&self) -> &str {
+            "fake"
+        }
+
+        fn requires_api_key(&self) -> bool {
+            false
+        }
+
+        fn stream<'a>(&'a self, _api_key: String, _request: ChatRequest, mut on_token: TokenSink<'a>) -> ChatFuture<'a> {
+            Box::pin(async move {
+                self.answer.notified().await;
+                for word in REPLY.split_inclusive(' ') {
+                    on_token(word);
+                }
+                Ok(REPLY.to_string())
+            })
+        }
+
+        fn check_api_key(&self, _api_key: String) -> CheckFuture<'_> {
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[test]
+    fn plays_spawn_then_yawn_then_idle() {
+        let overlay = TestOverlay::new();
+        let (mut shown, wait) = overlay.step_after(0);
+        assert_eq!(wait, FRAME_MS);
+        for _ in 0..3 * FRAMES {
+            shown.extend(overlay.step_after(FRAME_MS).0);
+        }
+
+        let expected: Vec<_> = ["spawn", "yawn", "idle"]
+            .iter()
+            .flat_map(|name| (0..FRAMES).map(move |frame| (clip(name), frame)))
+            .chain([(clip("idle"), 0)])
+            .collect();
+        assert_eq!(shown, expected);
+        assert_eq!(overlay.clip(), "idle");
+    }
+
+    #[test]
+    fn frames_advance_with_the_clock() {
+        let overlay = TestOverlay::new();
+        let spawn = clip("spawn");
+        assert_eq!(overlay.step_after(0), (vec![(spawn, 0)], FRAME_MS));
+        assert_eq!(overlay.step_after(60), (vec![], 40));
+        assert_eq!(overlay.step_after(40), (vec![(spawn, 1)], FRAME_MS));
+    }
+
+    #[test]
+    fn late_steps_skip_frames() {
+        let overlay = TestOverlay::new();
+        overlay.step_after(0);
+        // Frame 1 was due at 100 and is never shown
+        assert_eq!(overlay.step_after(250), (vec![(clip("spawn"), 2)], 50));
+    }
+
+    #[test]
+    fn catching_up_is_capped() {
+        let overlay = TestOverlay::new();
+        overlay.play_to_idle();
+        // Far more frames late than are ever skipped, but short of the idle timeout
+        let late_frames = MAX_SKIPPED_FRAMES as u64 + 10;
+        let (shown, wait) = overlay.step_after(late_frames * FRAME_MS + 50);
+        // It carries on from the last skipped frame with a full frame ahead
+        assert_eq!(shown, [(clip("idle"), (MAX_SKIPPED_FRAMES + 1) % FRAMES)]);
+        assert_eq!(wait, FRAME_MS);
+    }
+
+    #[test]
+    fn pauses_while_hidden_or_covered() {
+        let overlay = TestOverlay::new();
+        overlay.step_after(0);
+        overlay.step_after(60);
+
+        // Hiding wakes the loop, which pauses at once
+        hide_overlay();
+        assert_eq!(overlay.step_after(0), (vec![], HIDDEN_POLL_MS));
+        overlay.clock.advance(10_000);
+        show_overlay();
+        // Picks up 60ms into the frame, as when it was hidden
+        assert_eq!(overlay.step_after(0), (vec![], 40));
+
+        overlay.backend.set_occluded(true);
+        assert_eq!(overlay.step_after(0), (vec![], OCCLUDED_POLL_MS));
+        assert_eq!(overlay.step_after(OCCLUDED_POLL_MS), (vec![], OCCLUDED_POLL_MS));
+        overlay.backend.set_occluded(false);
+        assert_eq!(overlay.step_after(0), (vec![], 40));
+        assert_eq!(overlay.step_after(40), (vec![(clip("spawn"), 1)], FRAME_MS));
+    }
+
+    #[test]
+    fn idle_timeout_ignores_time_spent_hidden() {
+        let overlay = TestOverlay::new();
+        overlay.play_to_idle();
+        hide_overlay();
+        overlay.step_after(0);
+        overlay.clock.advance(2 * IDLE_TIMEOUT_MS);
+        show_overlay();
+        overlay.step_after(0);
+        assert_eq!(overlay.clip(), "idle");
+    }
+
+    #[test]
+    fn once_clips_hand_over_where_they_end() {
+        let overlay = TestOverlay::new();
+        overlay.step_after(0);
+        // Spawn's last frame ended at 400, so yawn is 50ms into its first
+        assert_eq!(overlay.step_after(450), (vec![(clip("yawn"), 0)], 50));
+        assert_eq!(overlay.clip(), "yawn");
+
+        // Yawn goes on to idle the same way
+        assert_eq!(overlay.step_after(FRAMES as u64 * FRAME_MS), (vec![(clip("idle"), 0)], 50));
+        assert_eq!(overlay.clip(), "idle");
+    }
+
+    #[test]
+    fn idle_timeout_yawns() {
+        let overlay = TestOverlay::new();
+        overlay.play_to_idle();
+        overlay.step_after(IDLE_TIMEOUT_MS - 1);
+        assert_eq!(overlay.clip(), "idle");
+        overlay.step_after(1);
+        assert_eq!(overlay.clip(), "yawn");
+    }
+
+    #[test]
+    fn clicks_only_count_on_the_cat() {
+        let overlay = TestOverlay::new();
+        overlay.play_to_idle();
+        let panel = overlay.backend.window().panel.expect("no panel");
+        let on_cat = Point::new(panel.x + panel.width / 2.0, panel.y + panel.height / 2.0);
+        assert!(cat_bounds(panel).contains(on_cat));
+
+        overlay.click(Point::new(panel.x - 10.0, on_cat.y));
+        assert_eq!(overlay.backend.take_events(), []);
+        assert_eq!(chat_state::current(), ChatState::Idle);
+
+        overlay.click(on_cat);
+        let events = overlay.backend.take_events();
+        assert!(matches!(events[..], [HeadlessEvent::ClickFeedback, HeadlessEvent::ChatInputShown(_)]));
+        assert_eq!(chat_state::current(), ChatState::InputOpen);
+        // The bundled skin has no listening clip
+        assert_eq!(overlay.clip(), "idle");
+
+        // A second click puts the input away again
+        overlay.click(on_cat);
+        assert_eq!(chat_state::current(), ChatState::Idle);
+        assert_eq!(overlay.backend.window().chat_input, None);
+        assert_eq!(overlay.clip(), "idle");
+    }
+
+    #[test]
+    fn enter_asks_and_the_answer_is_typed_out_then_hidden() {
+        let overlay = TestOverlay::new();
+        overlay.play_to_idle();
+        let provider = Arc::new(FakeProvider::default());
+        crate::ai::set_provider(provider.clone());
+
+        // The question goes out on the app's runtime
+        tauri::async_runtime::block_on(async {
+            show_chat_input();
+            overlay.backend.type_text("Are you awake?");
+            handle_key(OverlayKey::Enter);
+            assert_eq!(chat_state::current(), ChatState::Thinking);
+            let window = overlay.backend.window();
+            assert!(window.thinking);
+            assert_eq!(window.chat_input, None);
+            assert!(overlay.backend.events().contains(&HeadlessEvent::ChatInputTaken("Are you awake?".to_string())));
+            assert_eq!(overlay.clip(), "thinking");
+
+            provider.answer.notify_one();
+            assert!(wait_for(|| chat_state::current() == ChatState::Responding).await);
+            assert_eq!(overlay.clip(), "idle");
+            assert!(wait_for(|| overlay.backend.window().response.as_deref() == Some(REPLY)).await);
+            assert!(!overlay.backend.window().thinking);
+
+            // Read or not, the answer goes away by itself
+            overlay.linger();
+        });
+        assert_eq!(chat_state::current(), ChatState::Idle);
+        assert_eq!(overlay.backend.window().response, None);
+        assert_eq!(overlay.clip(), "idle");
+
+        crate::ai::reload_provider();
+        crate::conversation::reset();
+    }
+
+    #[test]
+    fn a_response_started_right_after_a_hide_is_typed_out_and_hidden() {
+        let overlay = TestOverlay::new();
+        tauri::async_runtime::block_on(async {
+            show_response_with_typing("A long answer that is still being typed out when it is closed.".to_string());
+            crate::tasks::sleep_ms(30).await;
+            // Within one tick, so the first typing task hasn't noticed the hide yet
+            hide_response();
+            show_response_with_typing(REPLY.to_string());
+            crate::tasks::sleep_ms(100).await;
+            assert_eq!(crate::tasks::running("typing"), 1);
+
+            assert!(wait_for(|| overlay.backend.window().response.as_deref() == Some(REPLY)).await);
+            overlay.linger();
+        });
+        assert_eq!(overlay.backend.window().response, None);
+    }
+
+    #[test]
+    fn follows_the_pointer_to_another_screen_after_the_delay() {
+        let overlay = TestOverlay::new();
+        let settings = crate::settings::current();
+        let second = Rect::new(1920.0, 0.0, 1280.0, 800.0);
+        overlay.backend.set_active_screen(second);
+
+        assert_eq!(step_screen_monitor(), settings.screen_switch_delay_ms);
+        assert_eq!(overlay.backend.take_events(), []);
+        overlay.clock.advance(settings.screen_switch_delay_ms);
+        assert_eq!(step_screen_monitor(), settings.screen_monitor_interval_ms);
+        assert_eq!(overlay.backend.take_events(), [HeadlessEvent::PanelMoved(corner_origin(second, 320.0))]);
+    }
+
+    #[test]
+    fn focused_window_screen_falls_back_to_the_pointer() {
+        let overlay = TestOverlay::new();
+        let backend: &dyn OverlayBackend = &*overlay.backend;
+        let pointer = backend.active_screen();
+        assert_eq!(target_screen(backend, ScreenFollow::FocusedWindow), pointer);
+
+        let focused = Rect::new(-1280.0, 0.0, 1280.0, 800.0);
+        overlay.backend.set_focused_screen(Some(focused));
+        assert_eq!(target_screen(backend, ScreenFollow::FocusedWindow), focused);
+        assert_eq!(target_screen(backend, ScreenFollow::Mouse), pointer);
+    }
+}
@@ -0,0 +1,196 @@
+//! Screen tracking - Which screen the overlay should sit on
+//! Backends report screens being plugged in, unplugged or rearranged,
+//! spaces switching and focus moving as `ScreenEvent`s, which wake the
+//! screen monitor at once; between events it only checks the pointer or the
+//! focused window now and then. Events come in bursts (one per output when a
+//! dock is plugged in), so they are debounced, and the overlay only follows
+//! to another screen once the target has stayed there a moment, so a pointer
+//! crossing a screen on its way elsewhere doesn't drag the cat along.
+
+use super::backend::{Rect, ScreenEvent};
+
+// Events closer together than this are handled once, after the last
+const DEBOUNCE_MS: u64 = 250;
+
+/// Where the overlay is and where it may be going; times are on the
+/// animation clock (see `clock`)
+pub struct Tracker {
+    /// Screen the panel was last placed on, `None` to place it afresh
+    placed: Option<Rect>,
+    /// Another screen the overlay should be on, and since when
+    candidate: Option<(Rect, u64)>,
+    /// The burst of events being debounced and when its last event came in
+    /// A rearrangement anywhere in the burst wins over focus moves
+    pending: Option<(ScreenEvent, u64)>,
+}
+
+impl Tracker {
+    pub const fn new() -> Self {
+        Tracker { placed: None, candidate: None, pending: None }
+    }
+
+    pub fn event(&mut self, event: ScreenEvent, now: u64) {
+        let event = match self.pending {
+            Some((ScreenEvent::Rearranged, _)) => ScreenEvent::Rearranged,
+            _ => event,
+        };
+        self.pending = Some((event, now));
+    }
+
+    /// The panel was put on `screen`
+    pub fn placed(&mut self, screen: Rect) {
+        self.placed = Some(screen);
+        self.candidate = None;
+    }
+
+    /// Forget everything, for a closed panel
+    pub fn reset(&mut self) {
+        *self = Tracker::new();
+    }
+
+    /// The screen to move the panel to now, if any, given the one it should
+    /// be on (`target`, only asked for once events settle), and how long
+    /// until the tracker wants to look again: `poll_ms`, or sooner while an
+    /// event is debounced or a move is waiting out `switch_delay_ms`
+    pub fn step(
+        &mut self,
+        now: u64,
+        target: impl FnOnce() -> Rect,
+        switch_delay_ms: u64,
+        poll_ms: u64,
+    ) -> (Option<Rect>, u64) {
+        if let Some((event, at)) = self.pending {
+            let settled_at = at + DEBOUNCE_MS;
+            if now < settled_at {
+                return (None, settled_at - now);
+            }
+            self.pending = None;
+            if event == ScreenEvent::Rearranged {
+                // The old screen may be gone or moved; no reason to wait
+                self.placed = None;
+            }
+        }
+
+        let target = target();
+        match self.placed {
+            None => {
+                self.placed(target);
+                return (Some(target), poll_ms);
+            }
+            Some(placed) if placed == target => {
+                self.candidate = None;
+                return (None, poll_ms);
+            }
+            Some(_) => {}
+        }
+
+        let since = match self.candidate {
+            Some((screen, since)) if screen == target => since,
+            _ => {
+                self.candidate = Some((target, now));
+                now
+            }
+        };
+        let waited = now - since;
+        if waited >= switch_delay_ms {
+            self.placed(target);
+            (Some(target), poll_ms)
+        } else {
+            (None, (switch_delay_ms - waited).min(poll_ms))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    const SWITCH_DELAY_MS: u64 = 400;
+    const POLL_MS: u64 = 500;
+
+    const LEFT: Rect = Rect { x: 0.0, y: 0.0, width: 1920.0, height: 1080.0 };
+    const RIGHT: Rect = Rect { x: 1920.0, y: 0.0, width: 1280.0, height: 800.0 };
+    const ABOVE: Rect = Rect { x: 0.0, y: 1080.0, width: 1920.0, height: 1080.0 };
+
+    /// A tracker with the panel on `screen`
+    fn placed_on(screen: Rect) -> Tracker {
+        let mut tracker = Tracker::new();
+        tracker.placed(screen);
+        tracker
+    }
+
+    fn step(tracker: &mut Tracker, now: u64, target: Rect) -> (Option<Rect>, u64) {
+        tracker.step(now, || target, SWITCH_DELAY_MS, POLL_MS)
+    }
+
+    #[test]
+    fn first_step_places_the_panel() {
+        let mut tracker = Tracker::new();
+        assert_eq!(step(&mut tracker, 0, LEFT), (Some(LEFT), POLL_MS));
+        assert_eq!(step(&mut tracker, 10, LEFT), (None, POLL_MS));
+    }
+
+    #[test]
+    fn a_burst_of_events_is_handled_once() {
+        let mut tracker = placed_on(LEFT);
+        let asked = Cell::new(0);
+        let target = || {
+            asked.set(asked.get() + 1);
+            LEFT
+        };
+        for at in [0, 100, 200] {
+            tracker.event(ScreenEvent::FocusMoved, at);
+        }
+
+        // Waits out the debounce from the last event without looking
+        assert_eq!(tracker.step(300, target, SWITCH_DELAY_MS, POLL_MS), (None, 200 + DEBOUNCE_MS - 300));
+        assert_eq!(asked.get(), 0);
+        assert_eq!(tracker.step(200 + DEBOUNCE_MS, target, SWITCH_DELAY_MS, POLL_MS), (None, POLL_MS));
+        assert_eq!(asked.get(), 1);
+        assert_eq!(tracker.pending, None);
+    }
+
+    #[test]
+    fn a_rearrangement_wins_over_focus_moves() {
+        let mut tracker = placed_on(LEFT);
+        tracker.event(ScreenEvent::Rearranged, 0);
+        tracker.event(ScreenEvent::FocusMoved, 100);
+        // The old screen can't be trusted, so the panel is placed again at once
+        assert_eq!(step(&mut tracker, 100 + DEBOUNCE_MS, LEFT), (Some(LEFT), POLL_MS));
+
+        // Focus moves alone leave a panel on the right screen where it is
+        tracker.event(ScreenEvent::FocusMoved, 1000);
+        tracker.event(ScreenEvent::FocusMoved, 1100);
+        assert_eq!(step(&mut tracker, 1100 + DEBOUNCE_MS, LEFT), (None, POLL_MS));
+    }
+
+    #[test]
+    fn moves_once_the_target_stays_put() {
+        let mut tracker = placed_on(LEFT);
+        assert_eq!(step(&mut tracker, 1000, RIGHT), (None, SWITCH_DELAY_MS));
+        assert_eq!(step(&mut tracker, 1000 + SWITCH_DELAY_MS - 1, RIGHT), (None, 1));
+        assert_eq!(step(&mut tracker, 1000 + SWITCH_DELAY_MS, RIGHT), (Some(RIGHT), POLL_MS));
+        assert_eq!(step(&mut tracker, 2000, RIGHT), (None, POLL_MS));
+    }
+
+    #[test]
+    fn passing_through_a_screen_does_not_move_the_panel() {
+        let mut tracker = placed_on(LEFT);
+        step(&mut tracker, 0, RIGHT);
+        // On to another screen: the wait starts over
+        assert_eq!(step(&mut tracker, 300, ABOVE), (None, SWITCH_DELAY_MS));
+        // And back before either delay is up
+        assert_eq!(step(&mut tracker, 500, LEFT), (None, POLL_MS));
+        assert_eq!(step(&mut tracker, 5000, LEFT), (None, POLL_MS));
+        assert_eq!(tracker.candidate, None);
+    }
+
+    #[test]
+    fn waits_no_longer_than_the_poll_interval() {
+        let mut tracker = placed_on(LEFT);
+        assert_eq!(tracker.step(0, || RIGHT, 2000, POLL_MS), (None, POLL_MS));
+        assert_eq!(tracker.step(1800, || RIGHT, 2000, POLL_MS), (None, 200));
+        assert_eq!(tracker.step(2000, || RIGHT, 2000, POLL_MS), (Some(RIGHT), POLL_MS));
+    }
+}
@@ -0,0 +1,1034 @@
+//! Wayland overlay backend - The cat as a wlr-layer-shell surface
+//! The panel is an overlay-layer surface anchored to the bottom-right corner
+//! of an output, its input region cut down to the cat (and the response box
+//! while it is up) so clicks elsewhere reach the windows below. The chat
+//! input is a second, centred layer surface that takes the keyboard while
+//! open. Needs a compositor with zwlr_layer_shell_v1 (Sway, Hyprland, KDE);
+//! tests can run it against a headless Sway (`WLR_BACKENDS=headless`).
+//! Wayland never tells clients where the pointer is, so the active screen
+//! is the output the compositor put the panel on.
+
+use super::backend::{OverlayBackend, OverlayKey, Point, Rect, ScreenEvent};
+use super::canvas::{self, Canvas, Fade, PanelLayout, Sprite, CHAT_PADDING, CHAT_SIZE, RESPONSE_PADDING};
+use super::frames::Frame;
+use smithay_client_toolkit::compositor::{CompositorHandler, CompositorState, Region};
+use smithay_client_toolkit::output::{OutputHandler, OutputState};
+use smithay_client_toolkit::registry::{ProvidesRegistryState, RegistryState};
+use smithay_client_toolkit::seat::keyboard::{KeyEvent, KeyboardHandler, Keysym, Modifiers};
+use smithay_client_toolkit::seat::pointer::{
+    CursorIcon, PointerEvent, PointerEventKind, PointerHandler, ThemeSpec, ThemedPointer,
+};
+use smithay_client_toolkit::seat::{Capability, SeatHandler, SeatState};
+use smithay_client_toolkit::shell::wlr_layer::{
+    Anchor, KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface, LayerSurfaceConfigure,
+};
+use smithay_client_toolkit::shell::WaylandSurface;
+use smithay_client_toolkit::shm::slot::{Buffer, SlotPool};
+use smithay_client_toolkit::shm::{Shm, ShmHandler};
+use smithay_client_toolkit::{
+    delegate_compositor, delegate_keyboard, delegate_layer, delegate_output, delegate_pointer, delegate_registry,
+    delegate_seat, delegate_shm, registry_handlers,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+use wayland_client::backend::WaylandError;
+use wayland_client::globals::registry_queue_init;
+use wayland_client::protocol::{wl_keyboard, wl_output, wl_pointer, wl_seat, wl_shm, wl_surface};
+use wayland_client::{Connection, EventQueue, QueueHandle};
+
+// Layer-shell namespace, which is how compositor rules and tests find us
+const NAMESPACE: &str = "catpanion";
+
+// Font families for cairo's toy text API, with the Cocoa backend's sizes
+const TEXT_FAMILY: &str = "sans-serif";
+const THINKING_FAMILY: &str = "Chicle";
+
+// Starting size of the shared-memory pool; it grows on demand
+const POOL_SIZE: usize = 1 << 20;
+
+// How often the event task drains the Wayland connection
+const EVENT_POLL_MS: u64 = 15;
+
+// How often it does while there is nothing on screen to take input
+const IDLE_EVENT_POLL_MS: u64 = 250;
+
+// BTN_LEFT from linux/input-event-codes.h
+const BTN_LEFT: u32 = 0x110;
+
+// The connection and everything drawn through it; `None` until `connect`
+static WAYLAND_STATE: Mutex<Option<Wayland>> = Mutex::new(None);
+
+// Track if the event task is running
+static EVENTS_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Run `f` with the Wayland state, if connected, flushing requests after
+fn with_wayland<R>(f: impl FnOnce(&mut State, &QueueHandle<State>, &Connection) -> R) -> Option<R> {
+    let mut guard = WAYLAND_STATE.lock().unwrap();
+    let wayland = guard.as_mut()?;
+    let qh = wayland.queue.handle();
+    let result = f(&mut wayland.state, &qh, &wayland.conn);
+    if let Err(e) = wayland.queue.flush() {
+        log::warn!("Failed to flush Wayland requests: {}", e);
+    }
+    Some(result)
+}
+
+/// What the event task hands to the overlay core once the lock is released
+enum Input {
+    PointerMove(Point),
+    Click(Point),
+    Key(OverlayKey),
+    Submit,
+    Screens(ScreenEvent),
+}
+
+/// A layer surface and where it is
+struct Surface {
+    layer: LayerSurface,
+    /// Frame in overlay coordinates (bottom-left origin)
+    frame: Rect,
+    output: Option<wl_output::WlOutput>,
+    /// Nothing may be attached before the compositor's first configure
+    configured: bool,
+    /// The buffer last attached, kept alive until the next one replaces it
+    buffer: Option<Buffer>,
+}
+
+impl Surface {
+    fn is(&self, surface: &wl_surface::WlSurface) -> bool {
+        self.layer.wl_surface() == surface
+    }
+}
+
+struct Wayland {
+    conn: Connection,
+    queue: EventQueue<State>,
+    state: State,
+}
+
+struct State {
+    registry_state: RegistryState,
+    seat_state: SeatState,
+    output_state: OutputState,
+    compositor: CompositorState,
+    layer_shell: LayerShell,
+    shm: Shm,
+    pool: SlotPool,
+    pointer: Option<ThemedPointer>,
+    keyboard: Option<wl_keyboard::WlKeyboard>,
+    /// The output the panel last entered
+    active_output: Option<wl_output::WlOutput>,
+
+    /// Decoded frames per clip, indexed like the animation graph's clips
+    frames: Vec<Vec<Sprite>>,
+    /// Where the panel goes; kept while it is hidden and has no surface
+    panel_frame: Option<Rect>,
+    panel_visible: bool,
+    panel: Option<Surface>,
+    chat: Option<Surface>,
+    /// Clip and frame, `None` until the core shows one
+    shown_frame: Option<(usize, usize)>,
+    fade: Option<Fade>,
+    pressed_until: Option<Instant>,
+    thinking: bool,
+    response: Option<String>,
+    input: String,
+    placeholder: String,
+    inputs: Vec<Input>,
+}
+
+/// Draw text over `canvas` with cairo, whose ARGB32 layout the canvas shares
+fn with_cairo(canvas: &mut Canvas, draw: impl FnOnce(&cairo::Context) -> Result<(), cairo::Error>) {
+    let (width, height) = (canvas.width as i32, canvas.height as i32);
+    // The surface is finished before the canvas is touched again
+    let surface = unsafe {
+        cairo::ImageSurface::create_for_data_unsafe(
+            canvas.pixels.as_mut_ptr().cast(),
+            cairo::Format::ARgb32,
+            width,
+            height,
+            width * 4,
+        )
+    };
+    let result = surface.and_then(|surface| {
+        let cr = cairo::Context::new(&surface)?;
+        draw(&cr)?;
+        drop(cr);
+        surface.finish();
+        Ok(())
+    });
+    if let Err(e) = result {
+        log::warn!("Failed to draw overlay text: {}", e);
+    }
+}
+
+fn set_font(cr: &cairo::Context, family: &str, bold: bool, size: f64) {
+    let weight = if bold { cairo::FontWeight::Bold } else { cairo::FontWeight::Normal };
+    cr.select_font_face(family, cairo::FontSlant::Normal, weight);
+    cr.set_font_size(size);
+}
+
+fn text_width(cr: &cairo::Context, text: &str) -> f64 {
+    cr.text_extents(text).map(|e| e.x_advance()).unwrap_or(0.0)
+}
+
+/// Baseline that centres a line of the current font in `top..top + height`
+fn centred_baseline(cr: &cairo::Context, top: f64, height: f64) -> Result<f64, cairo::Error> {
+    let extents = cr.font_extents()?;
+    Ok(top + (height - (extents.ascent() + extents.descent())) / 2.0 + extents.ascent())
+}
+
+fn show_text(cr: &cairo::Context, x: f64, baseline: f64, text: &str, grey: f64) -> Result<(), cairo::Error> {
+    cr.set_source_rgb(grey, grey, grey);
+    cr.move_to(x, baseline);
+    cr.show_text(text)
+}
+
+/// Attach `canvas` to `surface` and commit it
+fn present(pool: &mut SlotPool, surface: &mut Surface, canvas: &Canvas) {
+    let (width, height) = (canvas.width as i32, canvas.height as i32);
+    let (buffer, data) = match pool.create_buffer(width, height, width * 4, wl_shm::Format::Argb8888) {
+        Ok(buffer) => buffer,
+        Err(e) => {
+            log::warn!("Failed to allocate overlay buffer: {}", e);
+            return;
+        }
+    };
+    // ARGB8888 is little-endian whatever the host
+    for (bytes, pixel) in data.chunks_exact_mut(4).zip(&canvas.pixels) {
+        bytes.copy_from_slice(&pixel.to_le_bytes());
+    }
+    let wl_surface = surface.layer.wl_surface();
+    wl_surface.damage_buffer(0, 0, width, height);
+    if let Err(e) = buffer.attach_to(wl_surface) {
+        log::warn!("Failed to attach overlay buffer: {}", e);
+        return;
+    }
+    surface.layer.commit();
+    surface.buffer = Some(buffer);
+}
+
+/// Right and bottom margins that put `frame` in place, measured from
+/// `screen`'s bottom-right corner (negative to hang off the edge, as the
+/// panel does)
+fn corner_margins(screen: Rect, frame: Rect) -> (i32, i32) {
+    let right = screen.x + screen.width - (frame.x + frame.width);
+    let bottom = frame.y - screen.y;
+    (right.round() as i32, bottom.round() as i32)
+}
+
+fn set_corner_margins(layer: &LayerSurface, screen: Rect, frame: Rect) {
+    let (right, bottom) = corner_margins(screen, frame);
+    layer.set_margin(0, right, bottom, 0);
+}
+
+/// Where a panel of `frame`'s size takes clicks (top-left origin): the
+/// cat's hit-box, plus the response box while it is up
+fn panel_input_region(frame: Rect, response: bool) -> Vec<Rect> {
+    let layout = PanelLayout::new(frame.width, frame.height);
+    let mut rects = vec![layout.hit_box];
+    if response {
+        rects.push(layout.response);
+    }
+    rects
+}
+
+impl State {
+    /// Outputs in the compositor's layout (top-left origin, logical pixels)
+    fn outputs(&self) -> Vec<(wl_output::WlOutput, Rect)> {
+        self.output_state
+            .outputs()
+            .filter_map(|output| {
+                let info = self.output_state.info(&output)?;
+                let (x, y) = info.logical_position?;
+                let (width, height) = info.logical_size?;
+                Some((output, Rect::new(x as f64, y as f64, width as f64, height as f64)))
+            })
+            .collect()
+    }
+
+    /// Output rects flipped into overlay coordinates (bottom-left origin)
+    fn screens(&self) -> Vec<(wl_output::WlOutput, Rect)> {
+        let outputs = self.outputs();
+        let layout_height = outputs.iter().map(|(_, r)| r.y + r.height).fold(0.0, f64::max);
+        outputs
+            .into_iter()
+            .map(|(output, r)| (output, Rect::new(r.x, layout_height - r.y - r.height, r.width, r.height)))
+            .collect()
+    }
+
+    fn active_screen(&self) -> Rect {
+        let screens = self.screens();
+        screens
+            .iter()
+            .find(|(output, _)| Some(output) == self.active_output.as_ref())
+            .or(screens.first())
+            .map(|(_, rect)| *rect)
+            .unwrap_or_default()
+    }
+
+    /// Tell the core once per dispatch, however many outputs changed
+    fn screens_changed(&mut self) {
+        if !self.inputs.iter().any(|input| matches!(input, Input::Screens(_))) {
+            self.inputs.push(Input::Screens(ScreenEvent::Rearranged));
+        }
+    }
+
+    /// The screen a panel at `frame` mostly sits on
+    fn screen_for(&self, frame: Rect) -> Option<(wl_output::WlOutput, Rect)> {
+        let centre = Point::new(frame.x + frame.width / 2.0, frame.y + frame.height / 2.0);
+        self.screens().into_iter().find(|(_, rect)| rect.contains(centre))
+    }
+
+    fn create_panel_surface(&mut self, qh: &QueueHandle<State>, frame: Rect) {
+        let placement = self.screen_for(frame);
+        let output = placement.as_ref().map(|(output, _)| output.clone());
+        let surface = self.compositor.create_surface(qh);
+        let layer = self
+            .layer_shell
+            .create_layer_surface(qh, surface, Layer::Overlay, Some(NAMESPACE), output.as_ref());
+        layer.set_anchor(Anchor::BOTTOM | Anchor::RIGHT);
+        layer.set_size(frame.width.round() as u32, frame.height.round() as u32);
+        // Sit in the very corner, ignoring panels' exclusive zones
+        layer.set_exclusive_zone(-1);
+        layer.set_keyboard_interactivity(KeyboardInteractivity::None);
+        if let Some((_, screen)) = placement {
+            set_corner_margins(&layer, screen, frame);
+        }
+        layer.commit();
+        self.panel = Some(Surface { layer, frame, output, configured: false, buffer: None });
+    }
+
+    /// The cat's hit-box, plus the response box while it is up, catch clicks
+    fn update_panel_region(&self) {
+        let Some(panel) = &self.panel else {
+            return;
+        };
+        let region = match Region::new(&self.compositor) {
+            Ok(region) => region,
+            Err(e) => {
+                log::warn!("Failed to create input region: {}", e);
+                return;
+            }
+        };
+        for rect in panel_input_region(panel.frame, self.response.is_some()) {
+            region.add(rect.x as i32, rect.y as i32, rect.width as i32, rect.height as i32);
+        }
+        panel.layer.wl_surface().set_input_region(Some(region.wl_region()));
+    }
+
+    /// Redraw the whole panel: cat, thinking label, response
+    fn redraw_panel(&mut self) {
+        let Some(panel) = self.panel.as_mut().filter(|p| p.configured) else {
+            return;
+        };
+        let (width, height) = (panel.frame.width, panel.frame.height);
+        let sprite = self.shown_frame.and_then(|(clip, index)| self.frames.get(clip)?.get(index));
+        let faded = self.fade.as_ref().and_then(|fade| {
+            let amount = fade.progress()?;
+            let from = self.frames.get(fade.from.0)?.get(fade.from.1)?;
+            from.mix(sprite?, amount)
+        });
+        let sprite = faded.as_ref().or(sprite);
+        let pressed = self.pressed_until.is_some_and(|until| Instant::now() < until);
+        let mut canvas = Canvas::panel(width, height, sprite, pressed, self.response.is_some());
+
+        let layout = PanelLayout::new(width, height);
+        let (thinking, response) = (self.thinking, self.response.as_deref());
+        with_cairo(&mut canvas, |cr| {
+            if thinking {
+                let text = canvas::THINKING_TEXT;
+                set_font(cr, THINKING_FAMILY, true, 26.0);
+                let x = layout.thinking.x + (layout.thinking.width - text_width(cr, text)) / 2.0;
+                // White with a black stroke, like the outline shadow on macOS
+                cr.move_to(x, centred_baseline(cr, layout.thinking.y, layout.thinking.height)?);
+                cr.text_path(text);
+                cr.set_source_rgb(0.0, 0.0, 0.0);
+                cr.set_line_width(3.0);
+                cr.stroke_preserve()?;
+                cr.set_source_rgb(1.0, 1.0, 1.0);
+                cr.fill()?;
+            }
+
+            if let Some(text) = response {
+                let area = layout.response;
+                set_font(cr, TEXT_FAMILY, false, 15.0);
+                let extents = cr.font_extents()?;
+                let mut baseline = area.y + RESPONSE_PADDING + extents.ascent();
+                for line in canvas::wrap_text(text, area.width - RESPONSE_PADDING * 2.0, |t| text_width(cr, t)) {
+                    if baseline + extents.descent() > area.y + area.height - RESPONSE_PADDING / 2.0 {
+                        break; // Cut off at the bottom, as the Cocoa text field does
+                    }
+                    show_text(cr, area.x + RESPONSE_PADDING, baseline, &line, 0.0)?;
+                    baseline += extents.height();
+                }
+            }
+            Ok(())
+        });
+
+        present(&mut self.pool, panel, &canvas);
+    }
+
+    /// Redraw the chat input: white pill, typed text or placeholder, send button
+    fn redraw_chat(&mut self) {
+        let Some(chat) = self.chat.as_mut().filter(|c| c.configured) else {
+            return;
+        };
+        let mut canvas = Canvas::chat();
+        let (input, placeholder) = (self.input.as_str(), self.placeholder.as_str());
+        with_cairo(&mut canvas, |cr| {
+            set_font(cr, TEXT_FAMILY, false, 16.0);
+            let baseline = centred_baseline(cr, 0.0, CHAT_SIZE.1)?;
+            if input.is_empty() {
+                show_text(cr, CHAT_PADDING, baseline, placeholder, 160.0 / 255.0)
+            } else {
+                let shown = canvas::tail_that_fits(input, canvas::chat_field_width(), |t| text_width(cr, t));
+                show_text(cr, CHAT_PADDING, baseline, shown, 0.0)
+            }
+        });
+        present(&mut self.pool, chat, &canvas);
+    }
+
+    fn show_chat(&mut self, qh: &QueueHandle<State>) {
+        if self.chat.is_some() {
+            self.redraw_chat();
+            return;
+        }
+        let output = self.panel.as_ref().and_then(|p| p.output.clone());
+        let surface = self.compositor.create_surface(qh);
+        let layer = self
+            .layer_shell
+            .create_layer_surface(qh, surface, Layer::Overlay, Some(NAMESPACE), output.as_ref());
+        // No anchor centres it on the output
+        layer.set_size(CHAT_SIZE.0 as u32, CHAT_SIZE.1 as u32);
+        layer.set_keyboard_interactivity(KeyboardInteractivity::Exclusive);
+        layer.commit();
+        let screen = self.active_screen();
+        let frame = Rect::new(
+            screen.x + (screen.width - CHAT_SIZE.0) / 2.0,
+            screen.y + (screen.height - CHAT_SIZE.1) / 2.0,
+            CHAT_SIZE.0,
+            CHAT_SIZE.1,
+        );
+        self.chat = Some(Surface { layer, frame, output, configured: false, buffer: None });
+    }
+
+    /// Pointer position on the panel, surface-local, in overlay coordinates
+    fn panel_location(panel: &Surface, (x, y): (f64, f64)) -> Point {
+        Point::new(panel.frame.x + x, panel.frame.y + panel.frame.height - y)
+    }
+}
+
+impl CompositorHandler for State {
+    fn scale_factor_changed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: i32) {}
+
+    fn transform_changed(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_surface::WlSurface,
+        _: wl_output::Transform,
+    ) {
+    }
+
+    fn frame(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: u32) {}
+
+    fn surface_enter(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        surface: &wl_surface::WlSurface,
+        output: &wl_output::WlOutput,
+    ) {
+        if self.panel.as_ref().is_some_and(|p| p.is(surface)) {
+            self.active_output = Some(output.clone());
+        }
+    }
+
+    fn surface_leave(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_surface::WlSurface,
+        _: &wl_output::WlOutput,
+    ) {
+    }
+}
+
+impl OutputHandler for State {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+
+    fn new_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {
+        self.screens_changed();
+    }
+
+    fn update_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_output::WlOutput) {
+        self.screens_changed();
+    }
+
+    fn output_destroyed(&mut self, _: &Connection, _: &QueueHandle<Self>, output: wl_output::WlOutput) {
+        if self.active_output.as_ref() == Some(&output) {
+            self.active_output = None;
+        }
+        self.screens_changed();
+    }
+}
+
+impl LayerShellHandler for State {
+    /// The compositor took a surface away (its output went, usually); the
+    /// panel comes back on the next move or show
+    fn closed(&mut self, _: &Connection, _: &QueueHandle<Self>, layer: &LayerSurface) {
+        if self.panel.as_ref().is_some_and(|p| p.is(layer.wl_surface())) {
+            log::info!("Overlay layer surface closed by the compositor");
+            self.panel = None;
+        } else if self.chat.as_ref().is_some_and(|c| c.is(layer.wl_surface())) {
+            self.chat = None;
+        }
+    }
+
+    fn configure(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        layer: &LayerSurface,
+        _: LayerSurfaceConfigure,
+        _: u32,
+    ) {
+        if let Some(panel) = self.panel.as_mut().filter(|p| p.is(layer.wl_surface())) {
+            panel.configured = true;
+            self.update_panel_region();
+            self.redraw_panel();
+        } else if let Some(chat) = self.chat.as_mut().filter(|c| c.is(layer.wl_surface())) {
+            chat.configured = true;
+            self.redraw_chat();
+        }
+    }
+}
+
+impl SeatHandler for State {
+    fn seat_state(&mut self) -> &mut SeatState {
+        &mut self.seat_state
+    }
+
+    fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+
+    fn new_capability(&mut self, _: &Connection, qh: &QueueHandle<Self>, seat: wl_seat::WlSeat, capability: Capability) {
+        if capability == Capability::Pointer && self.pointer.is_none() {
+            let cursor_surface = self.compositor.create_surface(qh);
+            match self
+                .seat_state
+                .get_pointer_with_theme(qh, &seat, self.shm.wl_shm(), cursor_surface, ThemeSpec::default())
+            {
+                Ok(pointer) => self.pointer = Some(pointer),
+                Err(e) => log::warn!("Failed to get the pointer: {}", e),
+            }
+        }
+        if capability == Capability::Keyboard && self.keyboard.is_none() {
+            match self.seat_state.get_keyboard(qh, &seat, None) {
+                Ok(keyboard) => self.keyboard = Some(keyboard),
+                Err(e) => log::warn!("Failed to get the keyboard: {}", e),
+            }
+        }
+    }
+
+    fn remove_capability(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: wl_seat::WlSeat,
+        capability: Capability,
+    ) {
+        match capability {
+            Capability::Pointer => self.pointer = None,
+            Capability::Keyboard => {
+                if let Some(keyboard) = self.keyboard.take() {
+                    keyboard.release();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+}
+
+impl PointerHandler for State {
+    fn pointer_frame(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_pointer::WlPointer,
+        events: &[PointerEvent],
+    ) {
+        for event in events {
+            if let Some(panel) = self.panel.as_ref().filter(|p| p.is(&event.surface)) {
+                let location = Self::panel_location(panel, event.position);
+                match event.kind {
+                    PointerEventKind::Enter { .. } | PointerEventKind::Motion { .. } => {
+                        // Only the latest position matters
+                        self.inputs.retain(|input| !matches!(input, Input::PointerMove(_)));
+                        self.inputs.push(Input::PointerMove(location));
+                    }
+                    PointerEventKind::Leave { .. } => {
+                        // Anywhere off the panel resets the cursor
+                        let outside = Point::new(panel.frame.x - 1.0, panel.frame.y - 1.0);
+                        self.inputs.push(Input::PointerMove(outside));
+                    }
+                    PointerEventKind::Press { button: BTN_LEFT, .. } => self.inputs.push(Input::Click(location)),
+                    _ => {}
+                }
+            } else if self.chat.as_ref().is_some_and(|c| c.is(&event.surface)) {
+                let (x, y) = event.position;
+                if matches!(event.kind, PointerEventKind::Press { button: BTN_LEFT, .. })
+                    && canvas::send_button().contains(Point::new(x, y))
+                {
+                    self.inputs.push(Input::Submit);
+                }
+            }
+        }
+    }
+}
+
+impl KeyboardHandler for State {
+    fn enter(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_keyboard::WlKeyboard,
+        _: &wl_surface::WlSurface,
+        _: u32,
+        _: &[u32],
+        _: &[Keysym],
+    ) {
+    }
+
+    fn leave(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: &wl_surface::WlSurface, _: u32) {}
+
+    /// Typing into the chat input; Enter and Escape go to the core
+    fn press_key(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, event: KeyEvent) {
+        if self.chat.is_none() {
+            return;
+        }
+        match event.keysym {
+            Keysym::Return | Keysym::KP_Enter => self.inputs.push(Input::Key(OverlayKey::Enter)),
+            Keysym::Escape => self.inputs.push(Input::Key(OverlayKey::Escape)),
+            Keysym::BackSpace => {
+                self.input.pop();
+                self.redraw_chat();
+            }
+            _ => {
+                if let Some(text) = event.utf8.filter(|t| !t.chars().any(char::is_control)) {
+                    self.input.push_str(&text);
+                    self.redraw_chat();
+                }
+            }
+        }
+    }
+
+    fn release_key(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_keyboard::WlKeyboard, _: u32, _: KeyEvent) {}
+
+    fn update_modifiers(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_keyboard::WlKeyboard,
+        _: u32,
+        _: Modifiers,
+        _: u32,
+    ) {
+    }
+}
+
+impl ShmHandler for State {
+    fn shm_state(&mut self) -> &mut Shm {
+        &mut self.shm
+    }
+}
+
+impl ProvidesRegistryState for State {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+
+    registry_handlers![OutputState, SeatState];
+}
+
+delegate_compositor!(State);
+delegate_output!(State);
+delegate_shm!(State);
+delegate_seat!(State);
+delegate_pointer!(State);
+delegate_keyboard!(State);
+delegate_layer!(State);
+delegate_registry!(State);
+
+impl Wayland {
+    /// Read and dispatch whatever arrived, returning the input it produced
+    fn pump(&mut self) -> Vec<Input> {
+        if let Some(guard) = self.queue.prepare_read() {
+            match guard.read() {
+                Ok(_) => {}
+                // The socket is non-blocking; nothing to read is fine
+                Err(WaylandError::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(e) => log::warn!("Failed to read Wayland events: {}", e),
+            }
+        }
+        if let Err(e) = self.queue.dispatch_pending(&mut self.state) {
+            log::warn!("Failed to dispatch Wayland events: {}", e);
+        }
+        if let Err(e) = self.queue.flush() {
+            log::warn!("Failed to flush Wayland requests: {}", e);
+        }
+        std::mem::take(&mut self.state.inputs)
+    }
+}
+
+pub struct WaylandBackend;
+
+impl WaylandBackend {
+    /// Connect to the compositor named by `WAYLAND_DISPLAY`
+    pub fn connect() -> Result<Self, String> {
+        let conn = Connection::connect_to_env().map_err(|e| format!("No Wayland compositor: {}", e))?;
+        let (globals, mut queue) =
+            registry_queue_init::<State>(&conn).map_err(|e| format!("Wayland registry failed: {}", e))?;
+        let qh = queue.handle();
+        let compositor = CompositorState::bind(&globals, &qh).map_err(|e| format!("wl_compositor unavailable: {}", e))?;
+        let layer_shell = LayerShell::bind(&globals, &qh).map_err(|e| format!("wlr-layer-shell unavailable: {}", e))?;
+        let shm = Shm::bind(&globals, &qh).map_err(|e| format!("wl_shm unavailable: {}", e))?;
+        let pool = SlotPool::new(POOL_SIZE, &shm).map_err(|e| format!("Failed to create shm pool: {}", e))?;
+
+        let mut state = State {
+            registry_state: RegistryState::new(&globals),
+            seat_state: SeatState::new(&globals, &qh),
+            output_state: OutputState::new(&globals, &qh),
+            compositor,
+            layer_shell,
+            shm,
+            pool,
+            pointer: None,
+            keyboard: None,
+            active_output: None,
+            frames: Default::default(),
+            panel_frame: None,
+            panel_visible: false,
+            panel: None,
+            chat: None,
+            shown_frame: None,
+            fade: None,
+            pressed_until: None,
+            thinking: false,
+            response: None,
+            input: String::new(),
+            placeholder: String::new(),
+            inputs: Vec::new(),
+        };
+        // Learn the outputs and seats before the first panel is placed
+        queue
+            .roundtrip(&mut state)
+            .map_err(|e| format!("Wayland roundtrip failed: {}", e))?;
+
+        *WAYLAND_STATE.lock().unwrap() = Some(Wayland { conn, queue, state });
+        Ok(WaylandBackend)
+    }
+}
+
+impl OverlayBackend for WaylandBackend {
+    fn name(&self) -> &'static str {
+        "wayland"
+    }
+
+    fn active_screen(&self) -> Rect {
+        with_wayland(|state, _, _| state.active_screen()).unwrap_or_default()
+    }
+
+    fn focused_screen(&self) -> Option<Rect> {
+        // Other clients' windows are hidden from us
+        None
+    }
+
+    fn scale_factor(&self) -> f64 {
+        // Buffers are drawn one pixel per point
+        1.0
+    }
+
+    fn add_frames(&self, clip: usize, frames: &[Frame]) -> usize {
+        // Converted outside the lock; the panel only blits them
+        let sprites: Vec<Sprite> = frames.iter().map(Sprite::from_frame).collect();
+        with_wayland(|state, _, _| {
+            if state.frames.len() <= clip {
+                state.frames.resize_with(clip + 1, Vec::new);
+            }
+            state.frames[clip].extend(sprites);
+            state.frames[clip].len()
+        })
+        .unwrap_or(0)
+    }
+
+    fn frame_count(&self, clip: usize) -> usize {
+        with_wayland(|state, _, _| state.frames.get(clip).map_or(0, Vec::len)).unwrap_or(0)
+    }
+
+    fn create_panel(&self, frame: Rect) -> Result<(), String> {
+        with_wayland(|state, qh, _| {
+            state.shown_frame = None;
+            state.fade = None;
+            state.thinking = false;
+            state.response = None;
+            state.panel_frame = Some(frame);
+            state.panel_visible = true;
+            // Drawn once the compositor configures it
+            state.create_panel_surface(qh, frame);
+        })
+        .ok_or_else(|| "Not connected to Wayland".to_string())
+    }
+
+    fn close_panel(&self) {
+        with_wayland(|state, _, _| {
+            state.panel = None;
+            state.panel_frame = None;
+            state.panel_visible = false;
+            state.frames = Default::default();
+        });
+    }
+
+    /// Layer surfaces can't be unmapped and kept, so showing makes a new one
+    fn show_panel(&self) {
+        with_wayland(|state, qh, _| {
+            let Some(frame) = state.panel_frame else {
+                return;
+            };
+            state.panel_visible = true;
+            if state.panel.is_none() {
+                state.create_panel_surface(qh, frame);
+            }
+        });
+    }
+
+    fn hide_panel(&self) {
+        with_wayland(|state, _, _| {
+            state.panel_visible = false;
+            state.panel = None;
+        });
+    }
+
+    fn is_panel_visible(&self) -> bool {
+        with_wayland(|state, _, _| state.panel_visible).unwrap_or(false)
+    }
+
+    fn is_panel_occluded(&self) -> bool {
+        // Compositors don't say what is covered
+        false
+    }
+
+    fn panel_frame(&self) -> Option<Rect> {
+        with_wayland(|state, _, _| state.panel_frame).flatten()
+    }
+
+    fn move_panel(&self, origin: Point) {
+        with_wayland(|state, qh, _| {
+            let Some(frame) = state.panel_frame else {
+                return;
+            };
+            let frame = Rect::new(origin.x, origin.y, frame.width, frame.height);
+            state.panel_frame = Some(frame);
+            if !state.panel_visible {
+                return;
+            }
+            let placement = state.screen_for(frame);
+            let output = placement.as_ref().map(|(output, _)| output.clone());
+            if let (Some(panel), Some((_, screen))) = (state.panel.as_mut().filter(|p| p.output == output), placement) {
+                panel.frame = frame;
+                set_corner_margins(&panel.layer, screen, frame);
+                panel.layer.commit();
+                return;
+            }
+            // A surface's output is fixed, so another screen needs a new one
+            state.create_panel_surface(qh, frame);
+        });
+    }
+
+    fn show_frame(&self, clip: usize, index: usize) {
+        with_wayland(|state, _, _| {
+            state.shown_frame = Some((clip, index));
+            state.redraw_panel();
+        });
+    }
+
+    fn fade_frames(&self, duration_ms: u64) {
+        with_wayland(|state, _, _| state.fade = state.shown_frame.map(|from| Fade::new(from, duration_ms)));
+    }
+
+    fn play_click_feedback(&self) {
+        // The cat is drawn squashed until this runs out; the animation's
+        // next frames bring it back
+        with_wayland(|state, _, _| {
+            state.pressed_until = Some(Instant::now() + canvas::CLICK_FEEDBACK);
+            state.redraw_panel();
+        });
+    }
+
+    fn set_hand_cursor(&self, hand: bool) {
+        with_wayland(|state, _, conn| {
+            let icon = if hand { CursorIcon::Pointer } else { CursorIcon::Default };
+            if let Some(Err(e)) = state.pointer.as_ref().map(|p| p.set_cursor(conn, icon)) {
+                log::warn!("Failed to set the cursor: {}", e);
+            }
+        });
+    }
+
+    fn show_chat_input(&self, placeholder: &str) {
+        with_wayland(|state, qh, _| {
+            state.placeholder = placeholder.to_string();
+            state.input.clear();
+            state.show_chat(qh);
+        });
+    }
+
+    fn hide_chat_input(&self) {
+        with_wayland(|state, _, _| {
+            state.chat = None;
+            state.input.clear();
+        });
+    }
+
+    fn take_chat_input(&self) -> String {
+        with_wayland(|state, _, _| {
+            let text = std::mem::take(&mut state.input);
+            state.redraw_chat();
+            text
+        })
+        .unwrap_or_default()
+    }
+
+    fn show_thinking(&self) {
+        with_wayland(|state, _, _| {
+            state.thinking = true;
+            state.redraw_panel();
+        });
+    }
+
+    fn hide_thinking(&self) {
+        with_wayland(|state, _, _| {
+            state.thinking = false;
+            state.redraw_panel();
+        });
+    }
+
+    fn show_response(&self) {
+        with_wayland(|state, _, _| {
+            state.response = Some(String::new());
+            state.update_panel_region();
+            state.redraw_panel();
+        });
+    }
+
+    fn set_response_text(&self, text: &str) {
+        with_wayland(|state, _, _| {
+            if let Some(response) = state.response.as_mut() {
+                *response = text.to_string();
+                state.redraw_panel();
+            }
+        });
+    }
+
+    fn hide_response(&self) {
+        with_wayland(|state, _, _| {
+            state.response = None;
+            state.update_panel_region();
+            state.redraw_panel();
+        });
+    }
+
+    /// Pump Wayland events on a task: pointer moves and clicks on the panel,
+    /// keys and the send button in the chat input, configures to draw on
+    fn start_input_monitor(&self) {
+        if EVENTS_RUNNING.swap(true, Ordering::SeqCst) {
+            return; // Already running
+        }
+
+        crate::tasks::spawn("wayland-events", async {
+            while EVENTS_RUNNING.load(Ordering::SeqCst) {
+                super::step_then_sleep(|| {
+                    // Dispatched after the state lock is released; the handlers call back in
+                    let inputs = WAYLAND_STATE.lock().unwrap().as_mut().map(Wayland::pump).unwrap_or_default();
+                    for input in inputs {
+                        match input {
+                            Input::PointerMove(location) => super::handle_pointer_move(location),
+                            Input::Click(location) => super::handle_click(location),
+                            Input::Key(key) => super::handle_key(key),
+                            Input::Submit => super::submit_input_from_window(),
+                            Input::Screens(event) => super::handle_screen_event(event),
+                        }
+                    }
+                    if super::wants_input() { EVENT_POLL_MS } else { IDLE_EVENT_POLL_MS }
+                })
+                .await;
+            }
+        });
+    }
+
+    fn stop_input_monitor(&self) {
+        EVENTS_RUNNING.store(false, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn input_region_is_the_hit_box() {
+        let frame = Rect::new(0.0, 0.0, 320.0, 320.0);
+        let layout = PanelLayout::new(frame.width, frame.height);
+        assert_eq!(panel_input_region(frame, false), [layout.hit_box]);
+        assert_eq!(panel_input_region(frame, true), [layout.hit_box, layout.response]);
+    }
+
+    #[test]
+    fn margins_hang_the_panel_off_the_corner() {
+        let screen = Rect::new(1920.0, 0.0, 1280.0, 800.0);
+        let origin = super::super::corner_origin(screen, 320.0);
+        let frame = Rect::new(origin.x, origin.y, 320.0, 320.0);
+        let (right, bottom) = corner_margins(screen, frame);
+        assert_eq!(right as f64, -super::super::SCREEN_EDGE_OFFSET_X);
+        assert_eq!(bottom as f64, -super::super::SCREEN_EDGE_OFFSET_Y);
+    }
+
+    /// Needs a compositor with wlr-layer-shell, e.g. a headless Sway:
+    /// `WLR_BACKENDS=headless sway -c /dev/null &`, then
+    /// `WAYLAND_DISPLAY=wayland-1 cargo test -- --ignored overlay::wayland`
+    #[test]
+    #[ignore]
+    fn panel_maps_on_the_output_it_asked_for() {
+        let _globals = crate::lock_globals();
+        let backend = WaylandBackend::connect().expect("no layer-shell compositor on $WAYLAND_DISPLAY");
+        let screen = backend.active_screen();
+        let origin = super::super::corner_origin(screen, 320.0);
+        backend.create_panel(Rect::new(origin.x, origin.y, 320.0, 320.0)).unwrap();
+
+        // Mapped once the compositor has configured it and put it on an output
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let (asked_for, entered) = loop {
+            {
+                let mut guard = WAYLAND_STATE.lock().unwrap();
+                let wayland = guard.as_mut().unwrap();
+                wayland.pump();
+                let state = &wayland.state;
+                let panel = state.panel.as_ref().expect("the compositor closed the panel");
+                if panel.configured && state.active_output.is_some() {
+                    break (panel.output.clone(), state.active_output.clone());
+                }
+            }
+            assert!(Instant::now() < deadline, "the panel was never mapped");
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        backend.close_panel();
+
+        assert!(asked_for.is_some(), "no output for the panel's frame");
+        assert_eq!(entered, asked_for);
+    }
+}
@@ -0,0 +1,977 @@
+//! X11 overlay backend - The cat drawn with Xlib on Linux
+//! The panel is an override-redirect ARGB window whose input shape covers
+//! only the cat (and the response box while it is up), so clicks anywhere
+//! else reach the windows below. The chat input is a second small window
+//! that takes the keyboard focus while it is open.
+//! libX11, libXfixes and libXft are loaded at runtime, so without them (or
+//! without `DISPLAY`) the overlay falls back to the headless backend. Both
+//! windows are named "Catpanion", which is how tests under Xvfb find them.
+//! Screens being added or rearranged are picked up through RandR when
+//! libXrandr is there, and focus moves through the window manager's
+//! `_NET_ACTIVE_WINDOW`.
+
+use super::backend::{OverlayBackend, OverlayKey, Point, Rect, ScreenEvent};
+use super::canvas::{self, Canvas, Fade, PanelLayout, Sprite, CHAT_PADDING, CHAT_SIZE, RESPONSE_PADDING};
+use super::frames::Frame;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int, c_long, c_uchar, c_uint, c_ulong};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+use x11_dl::xft::{self, Xft, XftFont};
+use x11_dl::xlib::{self, Display, Xlib};
+use x11_dl::xrender::{XGlyphInfo, XRenderColor};
+use x11_dl::{keysym, xfixes, xinerama, xrandr};
+
+const WINDOW_NAME: &str = "Catpanion";
+
+// Font patterns, with the sizes the Cocoa backend uses
+const RESPONSE_FONT: &str = "sans-serif:pixelsize=15";
+const INPUT_FONT: &str = "sans-serif:pixelsize=16";
+const THINKING_FONT: &str = "Chicle,sans-serif:bold:pixelsize=26";
+
+// How often the event task drains the X connection
+const EVENT_POLL_MS: u64 = 15;
+
+// How often it does while there is nothing on screen to take input
+const IDLE_EVENT_POLL_MS: u64 = 250;
+
+// Input shape kind for XFixesSetWindowShapeRegion (ShapeInput in shape.h)
+const SHAPE_INPUT: c_int = 2;
+
+// XC_hand2 from cursorfont.h
+const XC_HAND2: c_uint = 60;
+
+// The connection and everything drawn through it; `None` until `connect`
+static X11_STATE: Mutex<Option<X11>> = Mutex::new(None);
+
+// Track if the event task is running
+static EVENTS_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Run `f` with the X11 state, if connected
+fn with_x11<R>(f: impl FnOnce(&mut X11) -> R) -> Option<R> {
+    X11_STATE.lock().unwrap().as_mut().map(f)
+}
+
+/// Xlib's default handler exits the process; a stray BadWindow from a
+/// racing unmap is not worth that
+unsafe extern "C" fn on_x_error(_display: *mut Display, event: *mut xlib::XErrorEvent) -> c_int {
+    log::warn!(
+        "X11 error {} (request {}.{})",
+        (*event).error_code,
+        (*event).request_code,
+        (*event).minor_code
+    );
+    0
+}
+
+fn color(r: u8, g: u8, b: u8) -> XRenderColor {
+    let wide = |c: u8| c as u16 * 257;
+    XRenderColor { red: wide(r), green: wide(g), blue: wide(b), alpha: 0xffff }
+}
+
+/// A window, the pixmap it is drawn into off-screen and what draws there
+struct Surface {
+    window: xlib::Window,
+    pixmap: xlib::Pixmap,
+    gc: xlib::GC,
+    draw: *mut xft::XftDraw,
+    /// Frame in overlay coordinates (bottom-left origin)
+    frame: Rect,
+    visible: bool,
+    /// Fully covered, going by the last `VisibilityNotify`
+    obscured: bool,
+}
+
+/// What the event task hands to the overlay core once the lock is released
+enum Input {
+    PointerMove(Point),
+    Click(Point),
+    Key(OverlayKey),
+    Submit,
+    Screens(ScreenEvent),
+}
+
+struct X11 {
+    xlib: Xlib,
+    xfixes: xfixes::Xlib,
+    xft: Xft,
+    xinerama: Option<xinerama::Xlib>,
+    /// With the extension's first event number
+    xrandr: Option<(xrandr::Xrandr, c_int)>,
+    display: *mut Display,
+    screen: c_int,
+    root: xlib::Window,
+    root_height: f64,
+    net_active_window: xlib::Atom,
+    visual: *mut xlib::Visual,
+    colormap: xlib::Colormap,
+    hand_cursor: xlib::Cursor,
+    response_font: *mut XftFont,
+    input_font: *mut XftFont,
+    thinking_font: *mut XftFont,
+
+    /// Decoded frames per clip, indexed like the animation graph's clips
+    frames: Vec<Vec<Sprite>>,
+    panel: Option<Surface>,
+    chat: Option<Surface>,
+    /// Clip and frame, `None` until the core shows one
+    shown_frame: Option<(usize, usize)>,
+    fade: Option<Fade>,
+    pressed_until: Option<Instant>,
+    thinking: bool,
+    response: Option<String>,
+    input: String,
+    placeholder: String,
+}
+
+// The display is only ever touched with X11_STATE locked
+unsafe impl Send for X11 {}
+
+impl X11 {
+    unsafe fn open() -> Result<Self, String> {
+        let xlib = Xlib::open().map_err(|e| format!("libX11 unavailable: {}", e))?;
+        let xfixes = xfixes::Xlib::open().map_err(|e| format!("libXfixes unavailable: {}", e))?;
+        let xft = Xft::open().map_err(|e| format!("libXft unavailable: {}", e))?;
+        let xinerama = xinerama::Xlib::open().ok();
+
+        (xlib.XInitThreads)();
+        (xlib.XSetErrorHandler)(Some(on_x_error));
+
+        let display = (xlib.XOpenDisplay)(ptr::null());
+        if display.is_null() {
+            return Err("Cannot open the X display".to_string());
+        }
+        let screen = (xlib.XDefaultScreen)(display);
+        let root = (xlib.XRootWindow)(display, screen);
+
+        // Screen changes come as RandR events, focus moves as changes of a
+        // root window property
+        let xrandr = xrandr::Xrandr::open().ok().and_then(|xrandr| {
+            let (mut event_base, mut error_base) = (0, 0);
+            if (xrandr.XRRQueryExtension)(display, &mut event_base, &mut error_base) == 0 {
+                return None;
+            }
+            let mask = xrandr::RRScreenChangeNotifyMask | xrandr::RRCrtcChangeNotifyMask | xrandr::RROutputChangeNotifyMask;
+            (xrandr.XRRSelectInput)(display, root, mask);
+            Some((xrandr, event_base))
+        });
+        (xlib.XSelectInput)(display, root, xlib::PropertyChangeMask);
+        let atom_name = CString::new("_NET_ACTIVE_WINDOW").unwrap();
+        let net_active_window = (xlib.XInternAtom)(display, atom_name.as_ptr(), xlib::False);
+
+        // Per-pixel alpha needs a 32-bit visual; a compositor does the blending
+        let mut visual_info: xlib::XVisualInfo = std::mem::zeroed();
+        if (xlib.XMatchVisualInfo)(display, screen, 32, xlib::TrueColor, &mut visual_info) == 0 {
+            (xlib.XCloseDisplay)(display);
+            return Err("No 32-bit TrueColor visual".to_string());
+        }
+        let visual = visual_info.visual;
+        let colormap = (xlib.XCreateColormap)(display, root, visual, xlib::AllocNone);
+
+        let open_font = |pattern: &str| {
+            let name = CString::new(pattern).unwrap();
+            let font = (xft.XftFontOpenName)(display, screen, name.as_ptr());
+            if font.is_null() {
+                Err(format!("No font matches {}", pattern))
+            } else {
+                Ok(font)
+            }
+        };
+        let response_font = open_font(RESPONSE_FONT)?;
+        let input_font = open_font(INPUT_FONT)?;
+        let thinking_font = open_font(THINKING_FONT)?;
+
+        Ok(X11 {
+            root_height: (xlib.XDisplayHeight)(display, screen) as f64,
+            hand_cursor: (xlib.XCreateFontCursor)(display, XC_HAND2),
+            xlib,
+            xfixes,
+            xft,
+            xinerama,
+            xrandr,
+            display,
+            screen,
+            root,
+            net_active_window,
+            visual,
+            colormap,
+            response_font,
+            input_font,
+            thinking_font,
+            frames: Default::default(),
+            panel: None,
+            chat: None,
+            shown_frame: None,
+            fade: None,
+            pressed_until: None,
+            thinking: false,
+            response: None,
+            input: String::new(),
+            placeholder: String::new(),
+        })
+    }
+
+    /// X11 position and size of an overlay-coordinates rect (flipping y)
+    fn to_x11(&self, rect: Rect) -> (c_int, c_int, c_uint, c_uint) {
+        (
+            rect.x.round() as c_int,
+            (self.root_height - rect.y - rect.height).round() as c_int,
+            rect.width.round().max(1.0) as c_uint,
+            rect.height.round().max(1.0) as c_uint,
+        )
+    }
+
+    fn rect_from_x11(&self, x: f64, y: f64, width: f64, height: f64) -> Rect {
+        Rect::new(x, self.root_height - y - height, width, height)
+    }
+
+    unsafe fn pointer(&self) -> (f64, f64) {
+        let (mut root, mut child) = (0, 0);
+        let (mut x, mut y, mut win_x, mut win_y, mut mask) = (0, 0, 0, 0, 0);
+        (self.xlib.XQueryPointer)(
+            self.display, self.root, &mut root, &mut child, &mut x, &mut y, &mut win_x, &mut win_y, &mut mask,
+        );
+        (x as f64, y as f64)
+    }
+
+    /// Monitors in X11 coordinates, or the whole root window without Xinerama
+    unsafe fn monitors(&self) -> Vec<(f64, f64, f64, f64)> {
+        let whole = (
+            0.0,
+            0.0,
+            (self.xlib.XDisplayWidth)(self.display, self.screen) as f64,
+            self.root_height,
+        );
+        let Some(xinerama) = &self.xinerama else {
+            return vec![whole];
+        };
+        if (xinerama.XineramaIsActive)(self.display) == 0 {
+            return vec![whole];
+        }
+        let mut count = 0;
+        let screens = (xinerama.XineramaQueryScreens)(self.display, &mut count);
+        if screens.is_null() {
+            return vec![whole];
+        }
+        let monitors = std::slice::from_raw_parts(screens, count as usize)
+            .iter()
+            .map(|s| (s.x_org as f64, s.y_org as f64, s.width as f64, s.height as f64))
+            .collect();
+        (self.xlib.XFree)(screens.cast());
+        monitors
+    }
+
+    /// The monitor containing (`px`, `py`), in X11 coordinates
+    unsafe fn monitor_at(&self, px: f64, py: f64) -> Option<Rect> {
+        self.monitors()
+            .into_iter()
+            .find(|&(x, y, w, h)| px >= x && px < x + w && py >= y && py < y + h)
+            .map(|(x, y, width, height)| self.rect_from_x11(x, y, width, height))
+    }
+
+    unsafe fn active_screen(&self) -> Rect {
+        let (px, py) = self.pointer();
+        self.monitor_at(px, py).unwrap_or_else(|| {
+            let (x, y, width, height) = self.monitors()[0];
+            self.rect_from_x11(x, y, width, height)
+        })
+    }
+
+    /// The monitor under the centre of the window manager's active window
+    unsafe fn focused_screen(&self) -> Option<Rect> {
+        let (mut actual_type, mut format) = (0, 0);
+        let (mut count, mut remaining): (c_ulong, c_ulong) = (0, 0);
+        let mut data: *mut c_uchar = ptr::null_mut();
+        let status = (self.xlib.XGetWindowProperty)(
+            self.display,
+            self.root,
+            self.net_active_window,
+            0,
+            1,
+            xlib::False,
+            xlib::XA_WINDOW,
+            &mut actual_type,
+            &mut format,
+            &mut count,
+            &mut remaining,
+            &mut data,
+        );
+        if status != xlib::Success as c_int || data.is_null() {
+            return None;
+        }
+        // Format 32 properties come back as longs
+        let window = if format == 32 && count == 1 { *(data as *const xlib::Window) } else { 0 };
+        (self.xlib.XFree)(data.cast());
+        if window == 0 {
+            return None;
+        }
+
+        let mut attributes: xlib::XWindowAttributes = std::mem::zeroed();
+        if (self.xlib.XGetWindowAttributes)(self.display, window, &mut attributes) == 0 {
+            return None;
+        }
+        let (mut x, mut y, mut child) = (0, 0, 0);
+        (self.xlib.XTranslateCoordinates)(self.display, window, self.root, 0, 0, &mut x, &mut y, &mut child);
+        self.monitor_at(x as f64 + attributes.width as f64 / 2.0, y as f64 + attributes.height as f64 / 2.0)
+    }
+
+    unsafe fn create_surface(&self, frame: Rect, event_mask: c_long, cursor: xlib::Cursor) -> Result<Surface, String> {
+        let (x, y, width, height) = self.to_x11(frame);
+        let mut attributes: xlib::XSetWindowAttributes = std::mem::zeroed();
+        attributes.override_redirect = xlib::True;
+        attributes.colormap = self.colormap;
+        attributes.event_mask = event_mask;
+        attributes.cursor = cursor;
+        let window = (self.xlib.XCreateWindow)(
+            self.display,
+            self.root,
+            x,
+            y,
+            width,
+            height,
+            0,
+            32,
+            xlib::InputOutput as c_uint,
+            self.visual,
+            xlib::CWOverrideRedirect | xlib::CWColormap | xlib::CWBackPixel | xlib::CWBorderPixel
+                | xlib::CWEventMask | xlib::CWCursor,
+            &mut attributes,
+        );
+        if window == 0 {
+            return Err("XCreateWindow failed".to_string());
+        }
+        let name = CString::new(WINDOW_NAME).unwrap();
+        (self.xlib.XStoreName)(self.display, window, name.as_ptr());
+
+        let pixmap = (self.xlib.XCreatePixmap)(self.display, window, width, height, 32);
+        let gc = (self.xlib.XCreateGC)(self.display, pixmap, 0, ptr::null_mut());
+        let draw = (self.xft.XftDrawCreate)(self.display, pixmap, self.visual, self.colormap);
+        Ok(Surface { window, pixmap, gc, draw, frame, visible: false, obscured: false })
+    }
+
+    unsafe fn destroy_surface(&self, surface: Surface) {
+        (self.xft.XftDrawDestroy)(surface.draw);
+        (self.xlib.XFreeGC)(self.display, surface.gc);
+        (self.xlib.XFreePixmap)(self.display, surface.pixmap);
+        (self.xlib.XDestroyWindow)(self.display, surface.window);
+        (self.xlib.XFlush)(self.display);
+    }
+
+    unsafe fn map(&self, surface: &mut Surface) {
+        (self.xlib.XMapRaised)(self.display, surface.window);
+        surface.visible = true;
+        // Until the server says otherwise
+        surface.obscured = false;
+    }
+
+    unsafe fn unmap(&self, surface: &mut Surface) {
+        (self.xlib.XUnmapWindow)(self.display, surface.window);
+        (self.xlib.XFlush)(self.display);
+        surface.visible = false;
+    }
+
+    /// Accept clicks only inside `rects` (window coordinates, top-left origin)
+    unsafe fn set_input_shape(&self, window: xlib::Window, rects: &[Rect]) {
+        let mut rectangles: Vec<xlib::XRectangle> = rects
+            .iter()
+            .map(|r| xlib::XRectangle {
+                x: r.x as i16,
+                y: r.y as i16,
+                width: r.width as u16,
+                height: r.height as u16,
+            })
+            .collect();
+        let region = (self.xfixes.XFixesCreateRegion)(self.display, rectangles.as_mut_ptr(), rectangles.len() as c_int);
+        (self.xfixes.XFixesSetWindowShapeRegion)(self.display, window, SHAPE_INPUT, 0, 0, region);
+        (self.xfixes.XFixesDestroyRegion)(self.display, region);
+    }
+
+    /// Copy `canvas` into the surface's pixmap, below any text drawn after it
+    unsafe fn put_canvas(&self, surface: &Surface, canvas: &mut Canvas) {
+        let (width, height) = (canvas.width as c_uint, canvas.height as c_uint);
+        let image = (self.xlib.XCreateImage)(
+            self.display,
+            self.visual,
+            32,
+            xlib::ZPixmap,
+            0,
+            canvas.pixels.as_mut_ptr().cast::<c_char>(),
+            width,
+            height,
+            32,
+            0,
+        );
+        if image.is_null() {
+            return;
+        }
+        // The pixels are native-endian u32s, whatever order the server uses
+        (*image).byte_order = if cfg!(target_endian = "little") { xlib::LSBFirst } else { xlib::MSBFirst };
+        (self.xlib.XPutImage)(self.display, surface.pixmap, surface.gc, image, 0, 0, 0, 0, width, height);
+        // The canvas owns the pixels; keep XDestroyImage from freeing them
+        (*image).data = ptr::null_mut();
+        (self.xlib.XDestroyImage)(image);
+    }
+
+    /// Show the pixmap's contents in the window
+    unsafe fn present(&self, surface: &Surface, width: usize, height: usize) {
+        (self.xlib.XCopyArea)(
+            self.display,
+            surface.pixmap,
+            surface.window,
+            surface.gc,
+            0,
+            0,
+            width as c_uint,
+            height as c_uint,
+            0,
+            0,
+        );
+        (self.xlib.XFlush)(self.display);
+    }
+
+    unsafe fn text_width(&self, font: *mut XftFont, text: &str) -> f64 {
+        let mut extents: XGlyphInfo = std::mem::zeroed();
+        (self.xft.XftTextExtentsUtf8)(self.display, font, text.as_ptr(), text.len() as c_int, &mut extents);
+        extents.xOff as f64
+    }
+
+    /// Baseline that centres a line of `font` vertically in `top..top + height`
+    unsafe fn centred_baseline(&self, font: *mut XftFont, top: f64, height: f64) -> f64 {
+        top + (height - ((*font).ascent + (*font).descent) as f64) / 2.0 + (*font).ascent as f64
+    }
+
+    unsafe fn draw_text(&self, surface: &Surface, font: *mut XftFont, x: f64, baseline: f64, text: &str, rgb: XRenderColor) {
+        let mut xft_color: xft::XftColor = std::mem::zeroed();
+        (self.xft.XftColorAllocValue)(self.display, self.visual, self.colormap, &rgb, &mut xft_color);
+        (self.xft.XftDrawStringUtf8)(
+            surface.draw,
+            &xft_color,
+            font,
+            x.round() as c_int,
+            baseline.round() as c_int,
+            text.as_ptr(),
+            text.len() as c_int,
+        );
+        (self.xft.XftColorFree)(self.display, self.visual, self.colormap, &mut xft_color);
+    }
+
+    /// Redraw the whole panel: cat, thinking label, response
+    unsafe fn redraw_panel(&mut self) {
+        let Some(panel) = &self.panel else {
+            return;
+        };
+        let (width, height) = (panel.frame.width, panel.frame.height);
+        let sprite = self.shown_frame.and_then(|(clip, index)| self.frames.get(clip)?.get(index));
+        let faded = self.fade.as_ref().and_then(|fade| {
+            let amount = fade.progress()?;
+            let from = self.frames.get(fade.from.0)?.get(fade.from.1)?;
+            from.mix(sprite?, amount)
+        });
+        let sprite = faded.as_ref().or(sprite);
+        let pressed = self.pressed_until.is_some_and(|until| Instant::now() < until);
+        let mut canvas = Canvas::panel(width, height, sprite, pressed, self.response.is_some());
+        self.put_canvas(panel, &mut canvas);
+
+        let layout = PanelLayout::new(width, height);
+        if self.thinking {
+            let label = layout.thinking;
+            let text = canvas::THINKING_TEXT;
+            let text_x = label.x + (label.width - self.text_width(self.thinking_font, text)) / 2.0;
+            let baseline = self.centred_baseline(self.thinking_font, label.y, label.height);
+            // White on a black outline, like the stroke shadow on macOS
+            for (dx, dy) in [(-1.5, 0.0), (1.5, 0.0), (0.0, -1.5), (0.0, 1.5), (-1.0, -1.0), (1.0, 1.0), (-1.0, 1.0), (1.0, -1.0)] {
+                self.draw_text(panel, self.thinking_font, text_x + dx, baseline + dy, text, color(0, 0, 0));
+            }
+            self.draw_text(panel, self.thinking_font, text_x, baseline, text, color(255, 255, 255));
+        }
+
+        if let Some(text) = &self.response {
+            let (response, font) = (layout.response, self.response_font);
+            let line_height = (*font).height as f64;
+            let mut baseline = response.y + RESPONSE_PADDING + (*font).ascent as f64;
+            let lines = canvas::wrap_text(text, response.width - RESPONSE_PADDING * 2.0, |t| self.text_width(font, t));
+            for line in lines {
+                if baseline + (*font).descent as f64 > response.y + response.height - RESPONSE_PADDING / 2.0 {
+                    break; // Cut off at the bottom, as the Cocoa text field does
+                }
+                self.draw_text(panel, font, response.x + RESPONSE_PADDING, baseline, &line, color(0, 0, 0));
+                baseline += line_height;
+            }
+        }
+
+        self.present(panel, canvas.width, canvas.height);
+    }
+
+    /// The cat's hit-box, plus the response box while it is up, catch clicks
+    unsafe fn update_panel_shape(&self) {
+        let Some(panel) = &self.panel else {
+            return;
+        };
+        let layout = PanelLayout::new(panel.frame.width, panel.frame.height);
+        let mut rects = vec![layout.hit_box];
+        if self.response.is_some() {
+            rects.push(layout.response);
+        }
+        self.set_input_shape(panel.window, &rects);
+    }
+
+    /// Redraw the chat input: white pill, typed text or placeholder, send button
+    unsafe fn redraw_chat(&self) {
+        let Some(chat) = &self.chat else {
+            return;
+        };
+        let mut canvas = Canvas::chat();
+        self.put_canvas(chat, &mut canvas);
+
+        let font = self.input_font;
+        let baseline = self.centred_baseline(font, 0.0, CHAT_SIZE.1);
+        if self.input.is_empty() {
+            self.draw_text(chat, font, CHAT_PADDING, baseline, &self.placeholder, color(160, 160, 160));
+        } else {
+            let shown = canvas::tail_that_fits(&self.input, canvas::chat_field_width(), |t| self.text_width(font, t));
+            self.draw_text(chat, font, CHAT_PADDING, baseline, shown, color(0, 0, 0));
+        }
+
+        self.present(chat, canvas.width, canvas.height);
+    }
+
+    /// Apply a key press in the chat input; Enter and Escape go to the core
+    unsafe fn chat_key(&mut self, event: &mut xlib::XKeyEvent) -> Option<Input> {
+        let mut buffer = [0 as c_char; 16];
+        let mut sym: xlib::KeySym = 0;
+        (self.xlib.XLookupString)(event, buffer.as_mut_ptr(), buffer.len() as c_int, &mut sym, ptr::null_mut());
+        match sym as c_uint {
+            keysym::XK_Return | keysym::XK_KP_Enter => return Some(Input::Key(OverlayKey::Enter)),
+            keysym::XK_Escape => return Some(Input::Key(OverlayKey::Escape)),
+            keysym::XK_BackSpace => {
+                self.input.pop();
+            }
+            // Latin-1 keysyms are their code point; others carry it under 0x01000000
+            code @ 0x20..=0x7e | code @ 0xa0..=0xff => self.input.extend(char::from_u32(code)),
+            code if code & 0xff00_0000 == 0x0100_0000 => self.input.extend(char::from_u32(code & 0x00ff_ffff)),
+            _ => return None,
+        }
+        self.redraw_chat();
+        None
+    }
+
+    /// Drain the connection, redrawing what was exposed and collecting input
+    unsafe fn pending_input(&mut self) -> Vec<Input> {
+        let mut inputs = Vec::new();
+        let panel = self.panel.as_ref().map(|s| s.window);
+        let chat = self.chat.as_ref().map(|s| s.window);
+        let (mut redraw_panel, mut redraw_chat) = (false, false);
+
+        while (self.xlib.XPending)(self.display) > 0 {
+            let mut event: xlib::XEvent = std::mem::zeroed();
+            (self.xlib.XNextEvent)(self.display, &mut event);
+            let window = event.any.window;
+            match event.get_type() {
+                xlib::Expose if event.expose.count == 0 => {
+                    redraw_panel |= Some(window) == panel;
+                    redraw_chat |= Some(window) == chat;
+                }
+                xlib::MapNotify if Some(window) == chat => {
+                    // Focus can only go to a viewable window, so wait for the map
+                    (self.xlib.XSetInputFocus)(self.display, window, xlib::RevertToParent, xlib::CurrentTime);
+                }
+                xlib::PropertyNotify if window == self.root && event.property.atom == self.net_active_window => {
+                    inputs.retain(|input| !matches!(input, Input::Screens(ScreenEvent::FocusMoved)));
+                    inputs.push(Input::Screens(ScreenEvent::FocusMoved));
+                }
+                kind if self.xrandr.as_ref().is_some_and(|(_, base)| {
+                    kind == base + xrandr::RRScreenChangeNotify || kind == base + xrandr::RRNotify
+                }) =>
+                {
+                    if let Some((xrandr, _)) = &self.xrandr {
+                        (xrandr.XRRUpdateConfiguration)(&mut event);
+                    }
+                    // Overlay coordinates flip at the root window's new height
+                    self.root_height = (self.xlib.XDisplayHeight)(self.display, self.screen) as f64;
+                    inputs.retain(|input| !matches!(input, Input::Screens(ScreenEvent::Rearranged)));
+                    inputs.push(Input::Screens(ScreenEvent::Rearranged));
+                }
+                xlib::VisibilityNotify => {
+                    let state = event.visibility.state;
+                    if let Some(surface) = self.panel.as_mut().filter(|_| Some(window) == panel) {
+                        surface.obscured = state == xlib::VisibilityFullyObscured;
+                    }
+                    if state != xlib::VisibilityUnobscured {
+                        // Something was raised over us; stay on top
+                        (self.xlib.XRaiseWindow)(self.display, window);
+                    }
+                }
+                xlib::MotionNotify if Some(window) == panel => {
+                    // Only the latest position matters
+                    let location = Point::new(event.motion.x_root as f64, self.root_height - event.motion.y_root as f64);
+                    inputs.retain(|input| !matches!(input, Input::PointerMove(_)));
+                    inputs.push(Input::PointerMove(location));
+                }
+                xlib::LeaveNotify if Some(window) == panel => {
+                    let location = Point::new(event.crossing.x_root as f64, self.root_height - event.crossing.y_root as f64);
+                    inputs.push(Input::PointerMove(location));
+                }
+                xlib::ButtonPress if event.button.button == xlib::Button1 => {
+                    let button = event.button;
+                    if Some(window) == panel {
+                        let location = Point::new(button.x_root as f64, self.root_height - button.y_root as f64);
+                        inputs.push(Input::Click(location));
+                    } else if Some(window) == chat
+                        && canvas::send_button().contains(Point::new(button.x as f64, button.y as f64))
+                    {
+                        inputs.push(Input::Submit);
+                    }
+                }
+                xlib::KeyPress if Some(window) == chat => {
+                    inputs.extend(self.chat_key(&mut event.key));
+                }
+                _ => {}
+            }
+        }
+
+        if redraw_panel {
+            self.redraw_panel();
+        }
+        if redraw_chat {
+            self.redraw_chat();
+        }
+        inputs
+    }
+}
+
+pub struct X11Backend;
+
+impl X11Backend {
+    /// Connect to the X server named by `DISPLAY`
+    pub fn connect() -> Result<Self, String> {
+        let x11 = unsafe { X11::open()? };
+        *X11_STATE.lock().unwrap() = Some(x11);
+        Ok(X11Backend)
+    }
+}
+
+impl OverlayBackend for X11Backend {
+    fn name(&self) -> &'static str {
+        "x11"
+    }
+
+    fn active_screen(&self) -> Rect {
+        with_x11(|x| unsafe { x.active_screen() }).unwrap_or_default()
+    }
+
+    fn focused_screen(&self) -> Option<Rect> {
+        with_x11(|x| unsafe { x.focused_screen() }).flatten()
+    }
+
+    fn scale_factor(&self) -> f64 {
+        // Buffers are drawn one pixel per point
+        1.0
+    }
+
+    fn add_frames(&self, clip: usize, frames: &[Frame]) -> usize {
+        // Converted outside the lock; the panel only blits them
+        let sprites: Vec<Sprite> = frames.iter().map(Sprite::from_frame).collect();
+        with_x11(|x| {
+            if x.frames.len() <= clip {
+                x.frames.resize_with(clip + 1, Vec::new);
+            }
+            x.frames[clip].extend(sprites);
+            x.frames[clip].len()
+        })
+        .unwrap_or(0)
+    }
+
+    fn frame_count(&self, clip: usize) -> usize {
+        with_x11(|x| x.frames.get(clip).map_or(0, Vec::len)).unwrap_or(0)
+    }
+
+    fn create_panel(&self, frame: Rect) -> Result<(), String> {
+        with_x11(|x| unsafe {
+            let mask = xlib::ExposureMask
+                | xlib::ButtonPressMask
+                | xlib::PointerMotionMask
+                | xlib::LeaveWindowMask
+                | xlib::VisibilityChangeMask;
+            let mut panel = x.create_surface(frame, mask, 0)?;
+            x.shown_frame = None;
+            x.fade = None;
+            x.thinking = false;
+            x.response = None;
+            x.map(&mut panel);
+            x.panel = Some(panel);
+            x.update_panel_shape();
+            x.redraw_panel();
+            Ok(())
+        })
+        .unwrap_or_else(|| Err("Not connected to X".to_string()))
+    }
+
+    fn close_panel(&self) {
+        with_x11(|x| unsafe {
+            if let Some(panel) = x.panel.take() {
+                x.destroy_surface(panel);
+            }
+            x.frames = Default::default();
+        });
+    }
+
+    fn show_panel(&self) {
+        with_x11(|x| unsafe {
+            if let Some(mut panel) = x.panel.take() {
+                x.map(&mut panel);
+                x.panel = Some(panel);
+                x.redraw_panel();
+            }
+        });
+    }
+
+    fn hide_panel(&self) {
+        with_x11(|x| unsafe {
+            if let Some(mut panel) = x.panel.take() {
+                x.unmap(&mut panel);
+                x.panel = Some(panel);
+            }
+        });
+    }
+
+    fn is_panel_visible(&self) -> bool {
+        with_x11(|x| x.panel.as_ref().is_some_and(|p| p.visible)).unwrap_or(false)
+    }
+
+    fn is_panel_occluded(&self) -> bool {
+        with_x11(|x| x.panel.as_ref().is_some_and(|p| p.visible && p.obscured)).unwrap_or(false)
+    }
+
+    fn panel_frame(&self) -> Option<Rect> {
+        with_x11(|x| x.panel.as_ref().map(|p| p.frame)).flatten()
+    }
+
+    fn move_panel(&self, origin: Point) {
+        with_x11(|x| unsafe {
+            let Some(frame) = x.panel.as_ref().map(|p| p.frame) else {
+                return;
+            };
+            let frame = Rect::new(origin.x, origin.y, frame.width, frame.height);
+            let (left, top, _, _) = x.to_x11(frame);
+            let panel = x.panel.as_mut().unwrap();
+            panel.frame = frame;
+            let window = panel.window;
+            (x.xlib.XMoveWindow)(x.display, window, left, top);
+            (x.xlib.XRaiseWindow)(x.display, window);
+            (x.xlib.XFlush)(x.display);
+        });
+    }
+
+    fn show_frame(&self, clip: usize, index: usize) {
+        with_x11(|x| unsafe {
+            x.shown_frame = Some((clip, index));
+            x.redraw_panel();
+        });
+    }
+
+    fn fade_frames(&self, duration_ms: u64) {
+        with_x11(|x| x.fade = x.shown_frame.map(|from| Fade::new(from, duration_ms)));
+    }
+
+    fn play_click_feedback(&self) {
+        // The cat is drawn squashed until this runs out; the animation's
+        // next frames bring it back
+        with_x11(|x| unsafe {
+            x.pressed_until = Some(Instant::now() + canvas::CLICK_FEEDBACK);
+            x.redraw_panel();
+        });
+    }
+
+    fn set_hand_cursor(&self, hand: bool) {
+        with_x11(|x| unsafe {
+            let Some(window) = x.panel.as_ref().map(|p| p.window) else {
+                return;
+            };
+            if hand {
+                (x.xlib.XDefineCursor)(x.display, window, x.hand_cursor);
+            } else {
+                (x.xlib.XUndefineCursor)(x.display, window);
+            }
+            (x.xlib.XFlush)(x.display);
+        });
+    }
+
+    /// Chat input box in its own window, centred on the active screen
+    fn show_chat_input(&self, placeholder: &str) {
+        with_x11(|x| unsafe {
+            let screen = x.active_screen();
+            let frame = Rect::new(
+                screen.x + (screen.width - CHAT_SIZE.0) / 2.0,
+                screen.y + (screen.height - CHAT_SIZE.1) / 2.0,
+                CHAT_SIZE.0,
+                CHAT_SIZE.1,
+            );
+            let mut chat = match x.chat.take() {
+                Some(mut chat) => {
+                    let (left, top, _, _) = x.to_x11(frame);
+                    (x.xlib.XMoveWindow)(x.display, chat.window, left, top);
+                    chat.frame = frame;
+                    chat
+                }
+                None => {
+                    let mask = xlib::ExposureMask
+                        | xlib::ButtonPressMask
+                        | xlib::KeyPressMask
+                        | xlib::StructureNotifyMask
+                        | xlib::VisibilityChangeMask;
+                    match x.create_surface(frame, mask, 0) {
+                        Ok(chat) => chat,
+                        Err(e) => {
+                            log::error!("Failed to create chat input: {}", e);
+                            return;
+                        }
+                    }
+                }
+            };
+            x.placeholder = placeholder.to_string();
+            x.input.clear();
+            x.map(&mut chat);
+            x.chat = Some(chat);
+            x.redraw_chat();
+        });
+    }
+
+    fn hide_chat_input(&self) {
+        with_x11(|x| unsafe {
+            if let Some(mut chat) = x.chat.take() {
+                x.unmap(&mut chat);
+                x.chat = Some(chat);
+            }
+            x.input.clear();
+        });
+    }
+
+    fn take_chat_input(&self) -> String {
+        with_x11(|x| unsafe {
+            let text = std::mem::take(&mut x.input);
+            x.redraw_chat();
+            text
+        })
+        .unwrap_or_default()
+    }
+
+    fn show_thinking(&self) {
+        with_x11(|x| unsafe {
+            x.thinking = true;
+            x.redraw_panel();
+        });
+    }
+
+    fn hide_thinking(&self) {
+        with_x11(|x| unsafe {
+            x.thinking = false;
+            x.redraw_panel();
+        });
+    }
+
+    fn show_response(&self) {
+        with_x11(|x| unsafe {
+            x.response = Some(String::new());
+            x.update_panel_shape();
+            x.redraw_panel();
+        });
+    }
+
+    fn set_response_text(&self, text: &str) {
+        with_x11(|x| unsafe {
+            if let Some(response) = x.response.as_mut() {
+                *response = text.to_string();
+                x.redraw_panel();
+            }
+        });
+    }
+
+    fn hide_response(&self) {
+        with_x11(|x| unsafe {
+            x.response = None;
+            x.update_panel_shape();
+            x.redraw_panel();
+        });
+    }
+
+    /// Pump X events on a task: pointer moves and clicks on the panel, keys
+    /// and the send button in the chat input, and exposes that need a redraw
+    fn start_input_monitor(&self) {
+        if EVENTS_RUNNING.swap(true, Ordering::SeqCst) {
+            return; // Already running
+        }
+
+        crate::tasks::spawn("x11-events", async {
+            while EVENTS_RUNNING.load(Ordering::SeqCst) {
+                super::step_then_sleep(|| {
+                    // Dispatched after the state lock is released; the handlers call back in
+                    let inputs = with_x11(|x| unsafe { x.pending_input() }).unwrap_or_default();
+                    for input in inputs {
+                        match input {
+                            Input::PointerMove(location) => super::handle_pointer_move(location),
+                            Input::Click(location) => super::handle_click(location),
+                            Input::Key(key) => super::handle_key(key),
+                            Input::Submit => super::submit_input_from_window(),
+                            Input::Screens(event) => super::handle_screen_event(event),
+                        }
+                    }
+                    if super::wants_input() { EVENT_POLL_MS } else { IDLE_EVENT_POLL_MS }
+                })
+                .await;
+            }
+        });
+    }
+
+    fn stop_input_monitor(&self) {
+        EVENTS_RUNNING.store(false, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x11_dl::xrender::Xrender;
+
+    /// Needs an X server with a 32-bit visual, e.g.
+    /// `xvfb-run cargo test -- --ignored overlay::x11`
+    #[test]
+    #[ignore]
+    fn panel_is_an_argb_override_redirect_window_shaped_to_the_hit_box() {
+        let _globals = crate::lock_globals();
+        let backend = X11Backend::connect().expect("no X server on $DISPLAY");
+        let frame = Rect::new(100.0, 100.0, 320.0, 320.0);
+        backend.create_panel(frame).unwrap();
+
+        let (attributes, has_alpha, shape) = with_x11(|x| unsafe {
+            let window = x.panel.as_ref().unwrap().window;
+            let mut attributes: xlib::XWindowAttributes = std::mem::zeroed();
+            (x.xlib.XGetWindowAttributes)(x.display, window, &mut attributes);
+
+            let xrender = Xrender::open().expect("libXrender unavailable");
+            let format = (xrender.XRenderFindVisualFormat)(x.display, attributes.visual);
+            let has_alpha = !format.is_null() && (*format).direct.alphaMask != 0;
+
+            let region = (x.xfixes.XFixesCreateRegionFromWindow)(x.display, window, SHAPE_INPUT);
+            let mut count = 0;
+            let rects = (x.xfixes.XFixesFetchRegion)(x.display, region, &mut count);
+            let shape: Vec<_> = std::slice::from_raw_parts(rects, count as usize)
+                .iter()
+                .map(|r| (r.x, r.y, r.width, r.height))
+                .collect();
+            (x.xlib.XFree)(rects.cast());
+            (x.xfixes.XFixesDestroyRegion)(x.display, region);
+            (attributes, has_alpha, shape)
+        })
+        .unwrap();
+        backend.close_panel();
+
+        assert_eq!(attributes.override_redirect, xlib::True);
+        assert_eq!(attributes.depth, 32);
+        assert!(has_alpha, "the panel's visual has no alpha channel");
+        let hit_box = PanelLayout::new(frame.width, frame.height).hit_box;
+        let expected = (hit_box.x as i16, hit_box.y as i16, hit_box.width as u16, hit_box.height as u16);
+        assert_eq!(shape, [expected]);
+    }
+}
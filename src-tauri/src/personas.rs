@@ -0,0 +1,349 @@
+//! Personas module - Named cat personalities and their system prompts
+//! Built-in personas ship in `resources/personas.json`; the user's own (or
+//! edited built-ins) live in `personas.json` in the app config dir. Prompts
+//! and greetings are templates filled in right before they are used
+
+use chrono::{Local, Timelike};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+const BUILTIN_PERSONAS: &str = include_str!("../resources/personas.json");
+const PERSONAS_FILE_NAME: &str = "personas.json";
+
+/// Used if a persona id no longer exists
+pub const DEFAULT_PERSONA_ID: &str = "mittens";
+
+const DEFAULT_GREETING: &str = "Ask me anything...";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Persona {
+    pub id: String,
+    pub name: String,
+    /// Template, see `render`
+    pub system_prompt: String,
+    pub temperature: f32,
+    pub max_tokens: u32,
+    /// Overrides the provider's model when set
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Shown as the chat input placeholder, one picked at random
+    #[serde(default)]
+    pub greetings: Vec<String>,
+}
+
+impl Persona {
+    fn validate(&self) -> Result<(), String> {
+        if self.id.is_empty() || !self.id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(format!("Persona id must be letters, digits, '-' or '_': {:?}", self.id));
+        }
+        if self.name.trim().is_empty() {
+            return Err("Persona name must not be empty".to_string());
+        }
+        if self.system_prompt.trim().is_empty() {
+            return Err("Persona system prompt must not be empty".to_string());
+        }
+        if !(0.0..=2.0).contains(&self.temperature) {
+            return Err(format!("Temperature must be between 0 and 2 (got {})", self.temperature));
+        }
+        if self.max_tokens == 0 || self.max_tokens > 4096 {
+            return Err(format!("Max tokens must be between 1 and 4096 (got {})", self.max_tokens));
+        }
+        Ok(())
+    }
+}
+
+/// A persona as listed in the UI
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PersonaEntry {
+    #[serde(flatten)]
+    pub persona: Persona,
+    pub builtin: bool,
+    /// True for a built-in the user has edited
+    pub customized: bool,
+}
+
+// Personas from the user's file, set by `init`
+static USER_PERSONAS: Mutex<Vec<Persona>> = Mutex::new(Vec::new());
+
+// User personas file path, set by `init`
+static PERSONAS_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Load the user's personas from the config dir
+pub fn init(config_dir: PathBuf) {
+    let path = config_dir.join(PERSONAS_FILE_NAME);
+    *PERSONAS_PATH.lock().unwrap() = Some(path.clone());
+    USER_PERSONAS.lock().unwrap().clear();
+
+    if !path.exists() {
+        return;
+    }
+    let loaded = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {:?}: {}", path, e))
+        .and_then(|contents| {
+            serde_json::from_str::<Vec<Persona>>(&contents).map_err(|e| format!("Invalid personas file: {}", e))
+        });
+    match loaded {
+        Ok(personas) => {
+            let (valid, invalid): (Vec<_>, Vec<_>) = personas.into_iter().partition(|p| p.validate().is_ok());
+            for persona in invalid {
+                log::warn!("Skipping invalid persona {:?}", persona.id);
+            }
+            log::info!("Loaded {} custom personas", valid.len());
+            *USER_PERSONAS.lock().unwrap() = valid;
+        }
+        Err(e) => log::error!("{}", e),
+    }
+}
+
+/// The shipped personas, parsed on first use
+fn builtins() -> &'static [Persona] {
+    static BUILTINS: OnceLock<Vec<Persona>> = OnceLock::new();
+    BUILTINS.get_or_init(|| serde_json::from_str(BUILTIN_PERSONAS).expect("resources/personas.json is invalid"))
+}
+
+/// Every persona, built-ins first, with user edits applied
+pub fn list() -> Vec<PersonaEntry> {
+    let user = USER_PERSONAS.lock().unwrap().clone();
+    let builtins = builtins();
+
+    let mut entries: Vec<PersonaEntry> = builtins
+        .iter()
+        .map(|builtin| match user.iter().find(|p| p.id == builtin.id) {
+            Some(custom) => PersonaEntry { persona: custom.clone(), builtin: true, customized: true },
+            None => PersonaEntry { persona: builtin.clone(), builtin: true, customized: false },
+        })
+        .collect();
+    entries.extend(
+        user.into_iter()
+            .filter(|p| !builtins.iter().any(|b| b.id == p.id))
+            .map(|persona| PersonaEntry { persona, builtin: false, customized: false }),
+    );
+    entries
+}
+
+/// Look up a persona by id
+pub fn get(id: &str) -> Option<Persona> {
+    list().into_iter().map(|e| e.persona).find(|p| p.id == id)
+}
+
+/// The persona of the current conversation, falling back to the default
+pub fn active() -> Persona {
+    let id = crate::conversation::persona_id();
+    get(&id).or_else(|| {
+        log::warn!("Persona {:?} not found, using {}", id, DEFAULT_PERSONA_ID);
+        get(DEFAULT_PERSONA_ID)
+    })
+    .expect("default persona missing from resources/personas.json")
+}
+
+/// Add a persona or replace one with the same id (built-ins included)
+pub fn save_persona(persona: Persona) -> Result<Vec<PersonaEntry>, String> {
+    persona.validate()?;
+    {
+        let mut user = USER_PERSONAS.lock().unwrap();
+        match user.iter_mut().find(|p| p.id == persona.id) {
+            Some(existing) => *existing = persona,
+            None => user.push(persona),
+        }
+        save(&user)?;
+    }
+    let entries = list();
+    crate::events::emit("personas-changed", entries.clone());
+    Ok(entries)
+}
+
+/// Delete a custom persona, or restore a built-in to its shipped version
+pub fn delete_persona(id: &str) -> Result<Vec<PersonaEntry>, String> {
+    {
+        let mut user = USER_PERSONAS.lock().unwrap();
+        let before = user.len();
+        user.retain(|p| p.id != id);
+        if user.len() == before {
+            return if builtins().iter().any(|b| b.id == id) {
+                Err(format!("Built-in persona {} can't be deleted", id))
+            } else {
+                Err(format!("No persona with id {}", id))
+            };
+        }
+        save(&user)?;
+    }
+    let entries = list();
+    crate::events::emit("personas-changed", entries.clone());
+    Ok(entries)
+}
+
+fn save(personas: &[Persona]) -> Result<(), String> {
+    let Some(path) = PERSONAS_PATH.lock().unwrap().clone() else {
+        return Err("Personas path not initialised".to_string());
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create config dir: {}", e))?;
+    }
+    let json = serde_json::to_string_pretty(personas).map_err(|e| format!("Failed to serialise personas: {}", e))?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+/// Fill in `{{user_name}}`, `{{time_of_day}}` and `{{focus_session}}`
+/// Unknown variables are left as they are
+pub fn render(template: &str) -> String {
+    let settings = crate::settings::current();
+    let user_name = match settings.user_name.trim() {
+        "" => "friend",
+        name => name,
+    };
+
+    template
+        .replace("{{user_name}}", user_name)
+        .replace("{{time_of_day}}", time_of_day(Local::now().hour()))
+        .replace("{{focus_session}}", &crate::focus::describe())
+}
+
+/// A random rendered greeting of the active persona
+pub fn greeting() -> String {
+    let greetings = active().greetings;
+    if greetings.is_empty() {
+        return DEFAULT_GREETING.to_string();
+    }
+    // Good enough randomness for picking a greeting
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .subsec_nanos() as usize;
+    render(&greetings[nanos % greetings.len()])
+}
+
+fn time_of_day(hour: u32) -> &'static str {
+    match hour {
+        5..=11 => "morning",
+        12..=16 => "afternoon",
+        17..=21 => "evening",
+        _ => "night",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn persona(id: &str, name: &str) -> Persona {
+        Persona {
+            id: id.to_string(),
+            name: name.to_string(),
+            system_prompt: "You are a cat.".to_string(),
+            temperature: 0.7,
+            max_tokens: 200,
+            model: None,
+            greetings: Vec::new(),
+        }
+    }
+
+    /// Load `file` as the user's personas file, or none at all
+    fn with_personas_file(test: &str, file: Option<&str>) {
+        let dir = crate::temp_dir(test);
+        if let Some(contents) = file {
+            std::fs::write(dir.join(PERSONAS_FILE_NAME), contents).unwrap();
+        }
+        init(dir);
+    }
+
+    #[test]
+    fn builtins_parse_and_include_the_default() {
+        let builtins = builtins();
+        assert!(builtins.iter().any(|p| p.id == DEFAULT_PERSONA_ID));
+        for persona in builtins {
+            persona.validate().unwrap();
+        }
+    }
+
+    #[test]
+    fn render_fills_in_what_it_knows() {
+        let _globals = crate::lock_globals();
+        crate::settings::set_current(crate::settings::Settings::default());
+        crate::focus::stop();
+        let rendered = render("Hi {{user_name}}, good {{time_of_day}}! {{focus_session}}. {{mood}} {{user_name}}");
+        let time_of_day = time_of_day(Local::now().hour());
+        assert_eq!(rendered, format!("Hi friend, good {}! none running. {{{{mood}}}} friend", time_of_day));
+
+        let settings = crate::settings::Settings { user_name: "  Sam ".to_string(), ..Default::default() };
+        crate::settings::set_current(settings);
+        crate::focus::start("Essay", 25).unwrap();
+        assert_eq!(render("{{user_name}}: {{focus_session}}"), "Sam: \"Essay\", 0 of 25 minutes done");
+
+        crate::focus::stop();
+        crate::settings::set_current(crate::settings::Settings::default());
+    }
+
+    #[test]
+    fn times_of_day() {
+        let cases = [
+            (0, "night"),
+            (4, "night"),
+            (5, "morning"),
+            (11, "morning"),
+            (12, "afternoon"),
+            (16, "afternoon"),
+            (17, "evening"),
+            (21, "evening"),
+            (22, "night"),
+            (23, "night"),
+        ];
+        for (hour, expected) in cases {
+            assert_eq!(time_of_day(hour), expected, "{}h", hour);
+        }
+    }
+
+    #[test]
+    fn user_personas_override_builtins_and_add_to_them() {
+        let _globals = crate::lock_globals();
+        let file = serde_json::to_string(&[
+            persona(DEFAULT_PERSONA_ID, "Grumpy Mittens"),
+            persona("tiger", "Tiger"),
+            // Skipped, the rest still load
+            Persona { temperature: 5.0, ..persona("broken", "Broken") },
+        ])
+        .unwrap();
+        with_personas_file("personas-load", Some(&file));
+
+        let entries = list();
+        assert_eq!(entries.len(), builtins().len() + 1);
+        let mittens = entries.iter().find(|e| e.persona.id == DEFAULT_PERSONA_ID).unwrap();
+        assert_eq!(mittens.persona.name, "Grumpy Mittens");
+        assert!(mittens.builtin && mittens.customized);
+        let tiger = entries.last().unwrap();
+        assert_eq!(tiger.persona.id, "tiger");
+        assert!(!tiger.builtin && !tiger.customized);
+        assert_eq!(get("broken"), None);
+
+        // Deleting an edited built-in restores the shipped one
+        delete_persona(DEFAULT_PERSONA_ID).unwrap();
+        assert_eq!(get(DEFAULT_PERSONA_ID).as_ref(), builtins().iter().find(|p| p.id == DEFAULT_PERSONA_ID));
+        assert!(delete_persona(DEFAULT_PERSONA_ID).unwrap_err().contains("can't be deleted"));
+        delete_persona("tiger").unwrap();
+        assert!(delete_persona("tiger").unwrap_err().contains("No persona"));
+    }
+
+    #[test]
+    fn saved_personas_are_reloaded() {
+        let _globals = crate::lock_globals();
+        with_personas_file("personas-save", None);
+        assert!(save_persona(persona("bad id", "Bad")).is_err());
+        save_persona(persona("tiger", "Tiger")).unwrap();
+        save_persona(persona("tiger", "Tiger II")).unwrap();
+
+        let path = PERSONAS_PATH.lock().unwrap().clone().unwrap();
+        init(path.parent().unwrap().to_path_buf());
+        assert_eq!(get("tiger").unwrap().name, "Tiger II");
+        assert_eq!(list().len(), builtins().len() + 1);
+    }
+
+    #[test]
+    fn broken_personas_files_leave_the_builtins() {
+        let _globals = crate::lock_globals();
+        with_personas_file("personas-broken", Some("{ not json"));
+        assert_eq!(list().len(), builtins().len());
+        assert!(list().iter().all(|e| !e.customized));
+    }
+}
@@ -0,0 +1,67 @@
+//! Power module - Whether the machine is running on battery
+//! Checked in the background once a minute and cached, so the overlay's
+//! loops can ask on every step for free and slow down while unplugged.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// How often the power source is checked
+const CHECK_INTERVAL_MS: u64 = 60_000;
+
+static ON_BATTERY: AtomicBool = AtomicBool::new(false);
+static MONITOR_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Whether the machine was on battery at the last check
+pub fn on_battery() -> bool {
+    ON_BATTERY.load(Ordering::SeqCst)
+}
+
+/// Start checking the power source
+pub fn start_monitor() {
+    if MONITOR_RUNNING.swap(true, Ordering::SeqCst) {
+        return; // Already running
+    }
+
+    crate::tasks::spawn("power-monitor", async {
+        while MONITOR_RUNNING.load(Ordering::SeqCst) {
+            // Reading it may start a process, kept off the runtime's workers
+            let on_battery = tokio::task::spawn_blocking(read_on_battery).await.unwrap_or(false);
+            if ON_BATTERY.swap(on_battery, Ordering::SeqCst) != on_battery {
+                log::info!("Now on {} power", if on_battery { "battery" } else { "mains" });
+            }
+            crate::tasks::sleep_ms(CHECK_INTERVAL_MS).await;
+        }
+    });
+}
+
+/// On battery when no mains adapter is online and a battery is discharging
+#[cfg(target_os = "linux")]
+fn read_on_battery() -> bool {
+    let Ok(supplies) = std::fs::read_dir("/sys/class/power_supply") else {
+        return false;
+    };
+    let read = |path: std::path::PathBuf| std::fs::read_to_string(path).unwrap_or_default().trim().to_string();
+    let mut discharging = false;
+    for supply in supplies.flatten().map(|entry| entry.path()) {
+        match read(supply.join("type")).as_str() {
+            "Mains" | "USB" if read(supply.join("online")) == "1" => return false,
+            "Battery" => discharging |= read(supply.join("status")) == "Discharging",
+            _ => {}
+        }
+    }
+    discharging
+}
+
+/// `pmset` names the source the machine is drawing from
+#[cfg(target_os = "macos")]
+fn read_on_battery() -> bool {
+    std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).contains("'Battery Power'"))
+        .unwrap_or(false)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn read_on_battery() -> bool {
+    false
+}
@@ -0,0 +1,414 @@
+//! Secrets module - Loads and stores the chat provider API key
+//! Lookup order: environment variable, OS keyring (Keychain, Credential Manager,
+//! Secret Service), then an encrypted file in the app config dir for systems
+//! without a usable keyring. The key never travels back to the webview
+
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const KEYRING_SERVICE: &str = "com.catpanion.app";
+const KEYRING_USER: &str = "chat-api-key";
+const KEY_FILE_NAME: &str = "api_key.enc";
+const ENV_VARS: [&str; 2] = ["CATPANION_API_KEY", "GROQ_API_KEY"];
+
+// ChaCha20-Poly1305 nonce length in bytes
+const NONCE_LEN: usize = 12;
+
+/// Where the current API key came from
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum KeySource {
+    Environment,
+    Keyring,
+    EncryptedFile,
+    None,
+}
+
+/// What the frontend gets to know about the key (never the key itself)
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyStatus {
+    pub configured: bool,
+    pub source: KeySource,
+    /// False for local providers that run without a key
+    pub required: bool,
+}
+
+// Cached key and its source, filled by `init`
+static API_KEY: Mutex<Option<(String, KeySource)>> = Mutex::new(None);
+
+// Directory holding the encrypted fallback file
+static CONFIG_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Remember the config dir and load the key from the first source that has one
+pub fn init(config_dir: PathBuf) {
+    *CONFIG_DIR.lock().unwrap() = Some(config_dir);
+
+    let loaded = load();
+    match &loaded {
+        Some((_, source)) => log::info!("API key loaded from {:?}", source),
+        None => log::warn!("No API key configured"),
+    }
+    *API_KEY.lock().unwrap() = loaded;
+}
+
+/// The current API key, or an empty string if none is configured
+pub fn api_key() -> String {
+    API_KEY
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|(key, _)| key.clone())
+        .unwrap_or_default()
+}
+
+/// Describe the current key for the frontend
+pub fn status() -> ApiKeyStatus {
+    let source = API_KEY
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|(_, source)| *source)
+        .unwrap_or(KeySource::None);
+    ApiKeyStatus {
+        configured: source != KeySource::None,
+        source,
+        required: crate::ai::provider().requires_api_key(),
+    }
+}
+
+/// Store a new key in the keyring, falling back to the encrypted file
+pub fn set_api_key(key: &str) -> Result<ApiKeyStatus, String> {
+    let key = key.trim();
+    if key.is_empty() {
+        return Err("API key must not be empty".to_string());
+    }
+
+    let source = match keyring_entry().and_then(|e| e.set_password(key).map_err(|e| e.to_string())) {
+        Ok(()) => {
+            // Don't leave an older copy behind in the fallback file
+            let _ = remove_key_file();
+            KeySource::Keyring
+        }
+        Err(e) => {
+            log::warn!("Keyring unavailable ({}), using encrypted file", e);
+            write_key_file(key)?;
+            KeySource::EncryptedFile
+        }
+    };
+
+    *API_KEY.lock().unwrap() = Some((key.to_string(), source));
+    let status = status();
+    crate::events::emit("api-key-status", status.clone());
+    Ok(status)
+}
+
+/// Remove the stored key from the keyring and the encrypted file
+pub fn clear_api_key() -> Result<ApiKeyStatus, String> {
+    if let Ok(entry) = keyring_entry() {
+        match entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(e) => log::warn!("Failed to delete keyring entry: {}", e),
+        }
+    }
+    remove_key_file()?;
+
+    // An environment variable still wins after clearing the stored key
+    *API_KEY.lock().unwrap() = env_key().map(|key| (key, KeySource::Environment));
+    let status = status();
+    crate::events::emit("api-key-status", status.clone());
+    Ok(status)
+}
+
+fn load() -> Option<(String, KeySource)> {
+    if let Some(key) = env_key() {
+        return Some((key, KeySource::Environment));
+    }
+
+    match keyring_entry().map(|e| e.get_password()) {
+        Ok(Ok(key)) if !key.trim().is_empty() => return Some((key, KeySource::Keyring)),
+        Ok(Err(keyring::Error::NoEntry)) | Ok(Ok(_)) => {}
+        Ok(Err(e)) => log::warn!("Failed to read keyring: {}", e),
+        Err(e) => log::warn!("Keyring unavailable: {}", e),
+    }
+
+    match read_key_file() {
+        Ok(Some(key)) => Some((key, KeySource::EncryptedFile)),
+        Ok(None) => None,
+        Err(e) => {
+            log::error!("{}", e);
+            None
+        }
+    }
+}
+
+fn env_key() -> Option<String> {
+    ENV_VARS
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .map(|key| key.trim().to_string())
+        .find(|key| !key.is_empty())
+}
+
+fn keyring_entry() -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER).map_err(|e| e.to_string())
+}
+
+fn key_file_path() -> Result<PathBuf, String> {
+    CONFIG_DIR
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|dir| dir.join(KEY_FILE_NAME))
+        .ok_or_else(|| "Config dir not available".to_string())
+}
+
+/// Cipher keyed to this machine and user, so a copied file is useless elsewhere
+/// This guards against leaks through backups or sync, not against code running as the user
+fn file_cipher() -> ChaCha20Poly1305 {
+    let machine_id = ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+        .iter()
+        .find_map(|path| std::fs::read_to_string(path).ok())
+        .unwrap_or_default();
+    let user = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default();
+
+    let mut hasher = Sha256::new();
+    hasher.update(KEYRING_SERVICE.as_bytes());
+    hasher.update(machine_id.trim().as_bytes());
+    hasher.update(user.as_bytes());
+    let digest = hasher.finalize();
+    ChaCha20Poly1305::new(Key::from_slice(&digest))
+}
+
+fn write_key_file(key: &str) -> Result<(), String> {
+    let path = key_file_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create config dir: {}", e))?;
+    }
+
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = file_cipher()
+        .encrypt(&nonce, key.as_bytes())
+        .map_err(|_| "Failed to encrypt API key".to_string())?;
+
+    let mut contents = nonce.to_vec();
+    contents.extend_from_slice(&ciphertext);
+    std::fs::write(&path, contents).map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600));
+    }
+    Ok(())
+}
+
+fn read_key_file() -> Result<Option<String>, String> {
+    let path = key_file_path()?;
+    let contents = match std::fs::read(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {:?}: {}", path, e)),
+    };
+
+    if contents.len() <= NONCE_LEN {
+        return Err(format!("API key file {:?} is corrupt", path));
+    }
+    let (nonce, ciphertext) = contents.split_at(NONCE_LEN);
+    let plaintext = file_cipher()
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| format!("API key file {:?} could not be decrypted", path))?;
+
+    String::from_utf8(plaintext)
+        .map(Some)
+        .map_err(|_| format!("API key file {:?} is corrupt", path))
+}
+
+fn remove_key_file() -> Result<(), String> {
+    let path = key_file_path()?;
+    match std::fs::remove_file(&path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(format!("Failed to remove {:?}: {}", path, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keyring::credential::{Credential, CredentialApi, CredentialBuilderApi};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    // What the test keyring holds, shared by all its entries like a real one
+    static STORED: Mutex<Option<Vec<u8>>> = Mutex::new(None);
+    static AVAILABLE: AtomicBool = AtomicBool::new(true);
+
+    /// A keyring kept in memory that can be made unavailable
+    #[derive(Debug)]
+    struct TestKeyring;
+
+    impl CredentialBuilderApi for TestKeyring {
+        fn build(&self, _target: Option<&str>, _service: &str, _user: &str) -> keyring::Result<Box<Credential>> {
+            if !AVAILABLE.load(Ordering::SeqCst) {
+                return Err(keyring::Error::NoStorageAccess("no keyring in this test".into()));
+            }
+            Ok(Box::new(TestKeyring))
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+    }
+
+    impl CredentialApi for TestKeyring {
+        fn set_secret(&self, secret: &[u8]) -> keyring::Result<()> {
+            *STORED.lock().unwrap() = Some(secret.to_vec());
+            Ok(())
+        }
+
+        fn get_secret(&self) -> keyring::Result<Vec<u8>> {
+            STORED.lock().unwrap().clone().ok_or(keyring::Error::NoEntry)
+        }
+
+        fn delete_credential(&self) -> keyring::Result<()> {
+            STORED.lock().unwrap().take().map(|_| ()).ok_or(keyring::Error::NoEntry)
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+    }
+
+    /// Nothing stored anywhere, the keyring `available` or not, and the
+    /// encrypted file going in a fresh folder
+    fn start(test: &str, available: bool) -> PathBuf {
+        keyring::set_default_credential_builder(Box::new(TestKeyring));
+        *STORED.lock().unwrap() = None;
+        AVAILABLE.store(available, Ordering::SeqCst);
+        for name in ENV_VARS {
+            std::env::remove_var(name);
+        }
+        let dir = crate::temp_dir(test);
+        init(dir.clone());
+        dir
+    }
+
+    fn loaded() -> (String, KeySource) {
+        (api_key(), status().source)
+    }
+
+    #[test]
+    fn env_then_keyring_then_file() {
+        let _globals = crate::lock_globals();
+        let dir = start("secrets-order", true);
+        write_key_file("from-file").unwrap();
+        *STORED.lock().unwrap() = Some(b"from-keyring".to_vec());
+        std::env::set_var("GROQ_API_KEY", " from-env ");
+
+        init(dir.clone());
+        assert_eq!(loaded(), ("from-env".to_string(), KeySource::Environment));
+
+        std::env::remove_var("GROQ_API_KEY");
+        init(dir.clone());
+        assert_eq!(loaded(), ("from-keyring".to_string(), KeySource::Keyring));
+
+        AVAILABLE.store(false, Ordering::SeqCst);
+        init(dir.clone());
+        assert_eq!(loaded(), ("from-file".to_string(), KeySource::EncryptedFile));
+
+        remove_key_file().unwrap();
+        init(dir.clone());
+        assert_eq!(loaded(), (String::new(), KeySource::None));
+    }
+
+    #[test]
+    fn key_file_round_trip() {
+        let _globals = crate::lock_globals();
+        start("secrets-round-trip", false);
+        write_key_file("gsk_secret").unwrap();
+
+        let contents = std::fs::read(key_file_path().unwrap()).unwrap();
+        assert!(!contents.windows(b"gsk_secret".len()).any(|w| w == b"gsk_secret"));
+        assert_eq!(read_key_file().unwrap().as_deref(), Some("gsk_secret"));
+        // A fresh nonce every time
+        write_key_file("gsk_secret").unwrap();
+        assert_ne!(std::fs::read(key_file_path().unwrap()).unwrap(), contents);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn key_file_is_only_readable_by_the_user() {
+        use std::os::unix::fs::PermissionsExt;
+        let _globals = crate::lock_globals();
+        start("secrets-mode", false);
+        write_key_file("gsk_secret").unwrap();
+        let mode = std::fs::metadata(key_file_path().unwrap()).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn bad_key_files_are_errors() {
+        let _globals = crate::lock_globals();
+        let dir = start("secrets-bad", false);
+        let path = key_file_path().unwrap();
+
+        // Encrypted for another machine or user
+        let other = ChaCha20Poly1305::new(Key::from_slice(&[7; 32]));
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut contents = nonce.to_vec();
+        contents.extend(other.encrypt(&nonce, b"gsk_secret".as_slice()).unwrap());
+        std::fs::write(&path, &contents).unwrap();
+        assert!(read_key_file().unwrap_err().contains("could not be decrypted"));
+
+        write_key_file("gsk_secret").unwrap();
+        let mut tampered = std::fs::read(&path).unwrap();
+        *tampered.last_mut().unwrap() ^= 1;
+        std::fs::write(&path, &tampered).unwrap();
+        assert!(read_key_file().unwrap_err().contains("could not be decrypted"));
+
+        std::fs::write(&path, [0; NONCE_LEN]).unwrap();
+        assert!(read_key_file().unwrap_err().contains("corrupt"));
+
+        // Loading carries on without a key
+        init(dir);
+        assert_eq!(loaded(), (String::new(), KeySource::None));
+    }
+
+    #[test]
+    fn set_uses_the_keyring_and_clears_the_file() {
+        let _globals = crate::lock_globals();
+        start("secrets-set-keyring", true);
+        write_key_file("old").unwrap();
+
+        assert_eq!(set_api_key("  new  ").unwrap().source, KeySource::Keyring);
+        assert_eq!(STORED.lock().unwrap().as_deref(), Some(b"new".as_slice()));
+        assert!(!key_file_path().unwrap().exists());
+        assert!(set_api_key(" ").is_err());
+
+        let status = clear_api_key().unwrap();
+        assert!(!status.configured);
+        assert_eq!(*STORED.lock().unwrap(), None);
+    }
+
+    #[test]
+    fn set_falls_back_to_the_file_and_clear_removes_it() {
+        let _globals = crate::lock_globals();
+        start("secrets-set-file", false);
+
+        let status = set_api_key("gsk_secret").unwrap();
+        assert!(status.configured);
+        assert_eq!(status.source, KeySource::EncryptedFile);
+        assert_eq!(read_key_file().unwrap().as_deref(), Some("gsk_secret"));
+
+        let status = clear_api_key().unwrap();
+        assert_eq!((status.configured, status.source), (false, KeySource::None));
+        assert!(!key_file_path().unwrap().exists());
+        assert_eq!(api_key(), "");
+    }
+}
@@ -0,0 +1,379 @@
+//! Settings module - Typed, versioned user settings stored as JSON
+//! Loaded once at startup, migrated from older versions, validated, and
+//! re-applied whenever `update_settings` runs or the file changes on disk
+
+use crate::ai::ProviderConfig;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// Bump this and add a step to `migrate` whenever the format changes
+pub const SETTINGS_VERSION: u32 = 1;
+
+const SETTINGS_FILE_NAME: &str = "settings.json";
+
+// How often the watcher checks the file for external edits
+const WATCH_INTERVAL_MS: u64 = 1000;
+
+/// Which screen the overlay follows
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ScreenFollow {
+    /// The one the pointer is on
+    #[default]
+    Mouse,
+    /// The one the focused window is on
+    FocusedWindow,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Settings {
+    pub version: u32,
+    /// Overlay panel size in points
+    pub overlay_width: f64,
+    pub overlay_height: f64,
+    /// Per-frame delays of the clips naming them as their `delaySetting`,
+    /// the built-in skin's spawn, yawn and idle; they override the timing
+    /// in the clips' files
+    pub spawn_delay_ms: u64,
+    pub yawn_delay_ms: u64,
+    pub idle_delay_ms: u64,
+    /// Clicks on the cat closer together than this are ignored
+    pub click_debounce_ms: u64,
+    /// Which screen the overlay sits on
+    pub screen_follow: ScreenFollow,
+    /// How long the pointer or focused window must stay on another screen
+    /// before the overlay follows it there
+    pub screen_switch_delay_ms: u64,
+    /// How often the overlay checks the pointer or focused window between
+    /// screen change events
+    pub screen_monitor_interval_ms: u64,
+    /// On battery, the idle cat is drawn at most this often and keeps its
+    /// pace by skipping frames; 0 draws every frame regardless
+    pub battery_frame_ms: u64,
+    /// Chat endpoint and model
+    pub provider: ProviderConfig,
+    /// Filled into `{{user_name}}` in persona prompts
+    pub user_name: String,
+    /// Persona new conversations start with
+    pub default_persona: String,
+    /// Character the overlay draws, see `skins`
+    pub skin: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            overlay_width: 320.0,
+            overlay_height: 500.0,
+            spawn_delay_ms: 50,
+            yawn_delay_ms: 50,
+            idle_delay_ms: 80,
+            click_debounce_ms: 800,
+            screen_follow: ScreenFollow::Mouse,
+            screen_switch_delay_ms: 400,
+            screen_monitor_interval_ms: 500,
+            battery_frame_ms: 200,
+            provider: ProviderConfig::groq(),
+            user_name: String::new(),
+            default_persona: crate::personas::DEFAULT_PERSONA_ID.to_string(),
+            skin: crate::skins::DEFAULT_SKIN_ID.to_string(),
+        }
+    }
+}
+
+impl Settings {
+    /// Reject values the overlay can't sensibly work with
+    pub fn validate(&self) -> Result<(), String> {
+        check_range("overlayWidth", self.overlay_width, 160.0, 2000.0)?;
+        check_range("overlayHeight", self.overlay_height, 160.0, 2000.0)?;
+        check_range("spawnDelayMs", self.spawn_delay_ms, 10, 1000)?;
+        check_range("yawnDelayMs", self.yawn_delay_ms, 10, 1000)?;
+        check_range("idleDelayMs", self.idle_delay_ms, 10, 1000)?;
+        check_range("clickDebounceMs", self.click_debounce_ms, 0, 5000)?;
+        check_range("screenSwitchDelayMs", self.screen_switch_delay_ms, 0, 5000)?;
+        check_range("screenMonitorIntervalMs", self.screen_monitor_interval_ms, 100, 10_000)?;
+        if self.battery_frame_ms != 0 {
+            check_range("batteryFrameMs", self.battery_frame_ms, 50, 1000)?;
+        }
+        if self.default_persona.trim().is_empty() {
+            return Err("defaultPersona must not be empty".to_string());
+        }
+        if self.skin.trim().is_empty() {
+            return Err("skin must not be empty".to_string());
+        }
+        self.provider.validate()
+    }
+}
+
+fn check_range<T: PartialOrd + std::fmt::Display>(name: &str, value: T, min: T, max: T) -> Result<(), String> {
+    if value < min || value > max {
+        return Err(format!("{} must be between {} and {} (got {})", name, min, max, value));
+    }
+    Ok(())
+}
+
+/// Upgrade raw settings JSON from any older version to `SETTINGS_VERSION`
+fn migrate(mut value: Value) -> Result<Value, String> {
+    let Some(object) = value.as_object_mut() else {
+        return Err("Settings file is not a JSON object".to_string());
+    };
+
+    // Files written before versioning have no `version` field
+    let found = object.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if found > SETTINGS_VERSION as u64 {
+        return Err(format!(
+            "Settings file is version {}, newer than this app supports ({})",
+            found, SETTINGS_VERSION
+        ));
+    }
+    let mut version = found as u32;
+
+    while version < SETTINGS_VERSION {
+        match version {
+            0 => {
+                // v0 -> v1: unversioned files already use the v1 field names,
+                // missing fields are filled from defaults on deserialise
+            }
+            _ => return Err(format!("No migration from settings version {}", version)),
+        }
+        version += 1;
+        log::info!("Migrated settings to version {}", version);
+    }
+
+    object.insert("version".to_string(), Value::from(SETTINGS_VERSION));
+    Ok(value)
+}
+
+// Current settings, defaults until `init` loads the file
+static SETTINGS: Mutex<Option<Settings>> = Mutex::new(None);
+
+// Settings file path, set by `init`
+static SETTINGS_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+// Modification time of the file as last read or written by us
+static LAST_MTIME: Mutex<Option<SystemTime>> = Mutex::new(None);
+
+static WATCHER_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Load settings from the config dir and start watching the file for edits
+pub fn init(config_dir: PathBuf) {
+    let path = config_dir.join(SETTINGS_FILE_NAME);
+    *SETTINGS_PATH.lock().unwrap() = Some(path.clone());
+
+    let settings = if path.exists() {
+        match load(&path) {
+            Ok(settings) => settings,
+            Err(e) => {
+                log::error!("Failed to load settings, using defaults: {}", e);
+                Settings::default()
+            }
+        }
+    } else {
+        let settings = Settings::default();
+        if let Err(e) = save(&settings) {
+            log::warn!("Failed to write default settings: {}", e);
+        }
+        settings
+    };
+
+    *SETTINGS.lock().unwrap() = Some(settings);
+    start_watcher();
+}
+
+/// Get a copy of the current settings
+pub fn current() -> Settings {
+    SETTINGS.lock().unwrap().clone().unwrap_or_default()
+}
+
+/// Use `settings` until the next load, without saving or applying them,
+/// e.g. in tests
+#[cfg(test)]
+pub fn set_current(settings: Settings) {
+    *SETTINGS.lock().unwrap() = Some(settings);
+}
+
+/// Replace all settings, persisting and applying them
+pub fn replace(settings: Settings) -> Result<Settings, String> {
+    let mut settings = settings;
+    settings.version = SETTINGS_VERSION;
+    settings.validate()?;
+    save(&settings)?;
+    apply(settings.clone());
+    Ok(settings)
+}
+
+/// Change some settings in place, persisting and applying the result
+pub fn update(change: impl FnOnce(&mut Settings)) -> Result<Settings, String> {
+    let mut settings = current();
+    change(&mut settings);
+    replace(settings)
+}
+
+fn load(path: &PathBuf) -> Result<Settings, String> {
+    let contents = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let raw: Value = serde_json::from_str(&contents).map_err(|e| format!("Invalid settings JSON: {}", e))?;
+
+    let was_outdated = raw.get("version").and_then(Value::as_u64) != Some(SETTINGS_VERSION as u64);
+    let settings: Settings = serde_json::from_value(migrate(raw)?)
+        .map_err(|e| format!("Invalid settings: {}", e))?;
+    settings.validate()?;
+
+    *LAST_MTIME.lock().unwrap() = modified_time(path);
+    if was_outdated {
+        // Write the migrated form back so the migration only runs once
+        save(&settings)?;
+    }
+    Ok(settings)
+}
+
+fn save(settings: &Settings) -> Result<(), String> {
+    let Some(path) = SETTINGS_PATH.lock().unwrap().clone() else {
+        return Err("Settings path not initialised".to_string());
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create config dir: {}", e))?;
+    }
+
+    let json = serde_json::to_string_pretty(settings).map_err(|e| format!("Failed to serialise settings: {}", e))?;
+
+    // Write then rename so a crash never leaves a half-written file
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, json).map_err(|e| format!("Failed to write {:?}: {}", tmp_path, e))?;
+    std::fs::rename(&tmp_path, &path).map_err(|e| format!("Failed to replace {:?}: {}", path, e))?;
+
+    *LAST_MTIME.lock().unwrap() = modified_time(&path);
+    Ok(())
+}
+
+/// Store new settings and let every subsystem react to them
+fn apply(settings: Settings) {
+    let previous = {
+        let mut guard = SETTINGS.lock().unwrap();
+        guard.replace(settings.clone()).unwrap_or_default()
+    };
+    if previous == settings {
+        return;
+    }
+
+    if previous.provider != settings.provider {
+        crate::ai::reload_provider();
+    }
+    crate::overlay::apply_settings(&previous, &settings);
+    crate::events::emit("settings-changed", settings);
+}
+
+fn modified_time(path: &PathBuf) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Poll the settings file and re-apply it when edited outside the app
+fn start_watcher() {
+    if WATCHER_RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    crate::tasks::spawn("settings-watcher", async {
+        while WATCHER_RUNNING.load(Ordering::SeqCst) {
+            crate::tasks::sleep_ms(WATCH_INTERVAL_MS).await;
+
+            let Some(path) = SETTINGS_PATH.lock().unwrap().clone() else {
+                continue;
+            };
+            let mtime = modified_time(&path);
+            if mtime.is_none() || mtime == *LAST_MTIME.lock().unwrap() {
+                continue;
+            }
+
+            log::info!("Settings file changed, reloading");
+            match load(&path) {
+                Ok(settings) => apply(settings),
+                Err(e) => {
+                    // Remember the bad version so we don't log it every second
+                    *LAST_MTIME.lock().unwrap() = mtime;
+                    log::error!("Ignoring invalid settings file: {}", e);
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Load `file` as the settings file, with saves going next to it
+    fn load_file(test: &str, file: Value) -> (Result<Settings, String>, Value) {
+        let path = crate::temp_dir(test).join(SETTINGS_FILE_NAME);
+        std::fs::write(&path, file.to_string()).unwrap();
+        let previous = SETTINGS_PATH.lock().unwrap().replace(path.clone());
+        let loaded = load(&path);
+        *SETTINGS_PATH.lock().unwrap() = previous;
+        let on_disk = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        (loaded, on_disk)
+    }
+
+    #[test]
+    fn unversioned_files_migrate_to_the_current_version() {
+        let _globals = crate::lock_globals();
+        let (loaded, on_disk) = load_file("settings-v0", json!({ "overlayWidth": 400.0, "userName": "Sam" }));
+        let settings = loaded.unwrap();
+        assert_eq!(settings.version, SETTINGS_VERSION);
+        assert_eq!(settings.overlay_width, 400.0);
+        assert_eq!(settings.user_name, "Sam");
+        assert_eq!(settings.idle_delay_ms, Settings::default().idle_delay_ms);
+        // Written back so the migration only runs once
+        assert_eq!(on_disk["version"], json!(SETTINGS_VERSION));
+        assert_eq!(on_disk["overlayWidth"], json!(400.0));
+    }
+
+    #[test]
+    fn current_files_load_unchanged() {
+        let _globals = crate::lock_globals();
+        let mut saved = Settings { skin: "tabby".to_string(), ..Settings::default() };
+        saved.screen_follow = ScreenFollow::FocusedWindow;
+        let (loaded, _) = load_file("settings-v1", serde_json::to_value(&saved).unwrap());
+        assert_eq!(loaded.unwrap(), saved);
+        assert_eq!(migrate(json!({ "version": 1 })).unwrap(), json!({ "version": 1 }));
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+        assert!(migrate(json!({ "version": SETTINGS_VERSION + 1 })).is_err());
+        assert!(migrate(json!({ "version": u64::from(u32::MAX) + 2 })).is_err());
+        assert!(migrate(json!([1, 2])).is_err());
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        let invalid = [
+            Settings { overlay_width: 100.0, ..Settings::default() },
+            Settings { overlay_height: 5000.0, ..Settings::default() },
+            Settings { idle_delay_ms: 0, ..Settings::default() },
+            Settings { screen_monitor_interval_ms: 50, ..Settings::default() },
+            Settings { battery_frame_ms: 20, ..Settings::default() },
+            Settings { default_persona: " ".to_string(), ..Settings::default() },
+            Settings { skin: String::new(), ..Settings::default() },
+        ];
+        for settings in invalid {
+            assert!(settings.validate().is_err(), "{:?}", settings);
+        }
+        assert_eq!(Settings::default().validate(), Ok(()));
+        assert_eq!(Settings { battery_frame_ms: 0, ..Settings::default() }.validate(), Ok(()));
+    }
+
+    #[test]
+    fn files_with_invalid_values_are_not_loaded() {
+        let _globals = crate::lock_globals();
+        let file = json!({ "version": 1, "clickDebounceMs": 60_000 });
+        let (loaded, on_disk) = load_file("settings-invalid", file.clone());
+        assert!(loaded.unwrap_err().contains("clickDebounceMs"));
+        assert_eq!(on_disk, file);
+    }
+}
@@ -0,0 +1,549 @@
+//! Skins module - Character packs the overlay can draw instead of Mittens
+//! A pack is a folder or zip in `skins/` in the app data dir with a
+//! `skin.json` (name, sprite size and placement, hit-box, default persona
+//! and the animation graph) next to the clip files it names. Mittens ships
+//! built in. Packs are validated when discovered, on first use and on each
+//! refresh of the list; broken ones are listed with the reason and can't
+//! be picked.
+
+use crate::overlay::animation;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const BUILTIN_SKIN: &str = include_str!("../resources/skin.json");
+const SKIN_FILE_NAME: &str = "skin.json";
+
+// Zipped packs are unpacked here, inside the skins dir, one folder per zip
+const UNPACKED_DIR_NAME: &str = ".unpacked";
+
+// Most a zipped pack may unpack to, so a dropped-in zip can't fill the disk
+const MAX_UNPACKED_BYTES: u64 = 64 * 1024 * 1024;
+const MAX_UNPACKED_ENTRIES: usize = 1024;
+
+/// Used if the chosen skin is gone or broken
+pub const DEFAULT_SKIN_ID: &str = "mittens";
+
+// Sprites bigger than this would not fit any sensible overlay
+const MAX_FRAME_SIZE: f64 = 1024.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Size {
+    pub width: f64,
+    pub height: f64,
+}
+
+/// A point in the sprite, from its top-left corner
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Anchor {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// A rectangle in the sprite, from its top-left corner
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HitBox {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// The contents of `skin.json`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkinManifest {
+    pub id: String,
+    pub name: String,
+    /// Size the sprite is drawn at, in points; frames are scaled to it
+    pub frame_size: Size,
+    /// The point of the sprite that stands on the panel's bottom centre;
+    /// the middle of the sprite's bottom edge if unset
+    #[serde(default)]
+    pub anchor: Option<Anchor>,
+    /// Where clicks count as clicking the character; the whole sprite if unset
+    #[serde(default)]
+    pub hit_box: Option<HitBox>,
+    /// Persona new conversations use while the skin is active, instead of
+    /// the default in the settings
+    #[serde(default)]
+    pub default_persona: Option<String>,
+    #[serde(flatten)]
+    pub animations: animation::Manifest,
+}
+
+impl SkinManifest {
+    fn validate(&self) -> Result<(), String> {
+        if self.id.is_empty() || !self.id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(format!("Skin id must be letters, digits, '-' or '_': {:?}", self.id));
+        }
+        if self.name.trim().is_empty() {
+            return Err("Skin name must not be empty".to_string());
+        }
+        let Size { width, height } = self.frame_size;
+        if !(16.0..=MAX_FRAME_SIZE).contains(&width) || !(16.0..=MAX_FRAME_SIZE).contains(&height) {
+            return Err(format!("frameSize must be between 16 and {} (got {}x{})", MAX_FRAME_SIZE, width, height));
+        }
+        let anchor = self.anchor();
+        if !(0.0..=width).contains(&anchor.x) || !(0.0..=height).contains(&anchor.y) {
+            return Err(format!("anchor ({}, {}) is outside the {}x{} frame", anchor.x, anchor.y, width, height));
+        }
+        let hit_box = self.hit_box();
+        if hit_box.width <= 0.0
+            || hit_box.height <= 0.0
+            || hit_box.x < 0.0
+            || hit_box.y < 0.0
+            || hit_box.x + hit_box.width > width
+            || hit_box.y + hit_box.height > height
+        {
+            return Err(format!("hitBox must be a non-empty area inside the {}x{} frame", width, height));
+        }
+        if self.default_persona.as_ref().is_some_and(|id| id.trim().is_empty()) {
+            return Err("defaultPersona must not be empty".to_string());
+        }
+        self.animations.validate()
+    }
+
+    pub fn anchor(&self) -> Anchor {
+        self.anchor.unwrap_or(Anchor { x: self.frame_size.width / 2.0, y: self.frame_size.height })
+    }
+
+    pub fn hit_box(&self) -> HitBox {
+        self.hit_box.unwrap_or(HitBox { x: 0.0, y: 0.0, width: self.frame_size.width, height: self.frame_size.height })
+    }
+}
+
+/// A validated skin and the folder its clip files are in
+#[derive(Clone, Debug)]
+pub struct Skin {
+    pub manifest: SkinManifest,
+    pub root: PathBuf,
+}
+
+/// A skin as listed in the UI
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkinEntry {
+    /// The pack's file name if its manifest can't be read
+    pub id: String,
+    pub name: String,
+    pub builtin: bool,
+    pub active: bool,
+    /// Why the pack can't be used, `None` if it can
+    pub error: Option<String>,
+}
+
+// Where packs are installed, set by `init`
+static SKINS_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// An installed pack by the name of its folder or zip, loaded or with the
+/// reason it couldn't be
+type Pack = (String, Result<Skin, String>);
+
+// Packs found by the last scan of `SKINS_DIR`, `None` until the first one
+static PACKS: Mutex<Option<Vec<Pack>>> = Mutex::new(None);
+
+/// Remember where packs live, creating the folder so users can find it
+pub fn init(dir: PathBuf) {
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        log::warn!("Failed to create {:?}: {}", dir, e);
+    }
+    *SKINS_DIR.lock().unwrap() = Some(dir);
+    *PACKS.lock().unwrap() = None;
+}
+
+fn builtin() -> Skin {
+    let manifest: SkinManifest = serde_json::from_str(BUILTIN_SKIN).expect("resources/skin.json is invalid");
+    Skin { manifest, root: crate::overlay::resource_path("") }
+}
+
+/// Read and check the pack in `root`
+fn load(root: PathBuf) -> Result<Skin, String> {
+    let path = root.join(SKIN_FILE_NAME);
+    let contents = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let manifest: SkinManifest = serde_json::from_str(&contents).map_err(|e| format!("Invalid skin file: {}", e))?;
+    manifest.validate()?;
+    manifest.animations.check_files(&root)?;
+    Ok(Skin { manifest, root })
+}
+
+/// Unpack a zipped pack (again if the zip changed since), returning the
+/// folder holding its `skin.json`
+fn unpack(zip: &Path, skins_dir: &Path) -> Result<PathBuf, String> {
+    let stem = zip.file_stem().and_then(|s| s.to_str()).unwrap_or("skin");
+    let target = skins_dir.join(UNPACKED_DIR_NAME).join(stem);
+
+    // Unpacking leaves the folder newer than the zip, until the zip is replaced
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let stale = match (modified(zip), modified(&target)) {
+        (Some(zipped), Some(unpacked)) => zipped > unpacked,
+        _ => true,
+    };
+    if stale {
+        log::info!("Unpacking skin {:?}", zip);
+        // Unpacked next to the target and moved into place when complete, so
+        // half a pack never looks up to date; dot names are never packs
+        let partial = target.with_file_name(format!(".{}.partial", stem));
+        let _ = std::fs::remove_dir_all(&partial);
+        let file = std::fs::File::open(zip).map_err(|e| format!("Failed to open {:?}: {}", zip, e))?;
+        let mut archive = zip::ZipArchive::new(file).map_err(|e| format!("Invalid zip {:?}: {}", zip, e))?;
+        if let Err(e) = extract(&mut archive, &partial) {
+            let _ = std::fs::remove_dir_all(&partial);
+            return Err(format!("Failed to unpack {:?}: {}", zip, e));
+        }
+        let _ = std::fs::remove_dir_all(&target);
+        std::fs::rename(&partial, &target).map_err(|e| format!("Failed to move {:?} into place: {}", partial, e))?;
+    }
+
+    // Zipping a folder puts everything one level down
+    if target.join(SKIN_FILE_NAME).exists() {
+        return Ok(target);
+    }
+    let nested = std::fs::read_dir(&target)
+        .map_err(|e| format!("Failed to read {:?}: {}", target, e))?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|dir| dir.join(SKIN_FILE_NAME).exists());
+    nested.ok_or_else(|| format!("No {} in {:?}", SKIN_FILE_NAME, zip))
+}
+
+/// Write the files of `archive` under `dest`, refusing links, entries that
+/// would land outside it and archives past the unpacked size caps
+fn extract(archive: &mut zip::ZipArchive<std::fs::File>, dest: &Path) -> Result<(), String> {
+    if archive.len() > MAX_UNPACKED_ENTRIES {
+        return Err(format!("more than {} entries", MAX_UNPACKED_ENTRIES));
+    }
+    let mut budget = MAX_UNPACKED_BYTES;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(|e| e.to_string())?;
+        let name = entry.name().to_string();
+        if entry.is_symlink() {
+            return Err(format!("{} is a link", name));
+        }
+        let path = dest.join(entry.enclosed_name().ok_or_else(|| format!("{} is outside the pack", name))?);
+        if entry.is_dir() {
+            std::fs::create_dir_all(&path).map_err(|e| e.to_string())?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        // The sizes in the archive can lie, so count what is actually written
+        let mut out = std::fs::File::create(&path).map_err(|e| e.to_string())?;
+        let written =
+            std::io::copy(&mut (&mut entry).take(budget + 1), &mut out).map_err(|e| format!("{}: {}", name, e))?;
+        if written > budget {
+            return Err(format!("more than {} MB unpacked", MAX_UNPACKED_BYTES / (1024 * 1024)));
+        }
+        budget -= written;
+    }
+    Ok(())
+}
+
+/// Every installed pack as of the last scan
+fn discover() -> Vec<Pack> {
+    PACKS.lock().unwrap().get_or_insert_with(scan).clone()
+}
+
+fn scan() -> Vec<Pack> {
+    let Some(dir) = SKINS_DIR.lock().unwrap().clone() else {
+        return Vec::new();
+    };
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+
+    let mut packs: Vec<_> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter_map(|path| {
+            let name = path.file_name()?.to_str()?.to_string();
+            if name.starts_with('.') {
+                return None;
+            }
+            let skin = if path.is_dir() {
+                load(path)
+            } else if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip")) {
+                unpack(&path, &dir).and_then(load)
+            } else {
+                return None;
+            };
+            Some((name, skin))
+        })
+        .collect();
+    packs.sort_by(|a, b| a.0.cmp(&b.0));
+    packs
+}
+
+/// The built-in skin and every valid installed pack; a pack with the id of
+/// one before it is skipped
+fn available() -> Vec<Skin> {
+    let mut skins = vec![builtin()];
+    for (name, skin) in discover() {
+        match skin {
+            Ok(skin) if skins.iter().any(|s| s.manifest.id == skin.manifest.id) => {
+                log::warn!("Skipping skin {} ({:?}), its id is taken", name, skin.manifest.id);
+            }
+            Ok(skin) => skins.push(skin),
+            Err(e) => log::warn!("Skipping skin {}: {}", name, e),
+        }
+    }
+    skins
+}
+
+/// Every skin, built-in first, broken packs included
+pub fn list() -> Vec<SkinEntry> {
+    let active = crate::settings::current().skin;
+    let builtin = builtin();
+    let mut entries = vec![SkinEntry {
+        active: builtin.manifest.id == active,
+        id: builtin.manifest.id,
+        name: builtin.manifest.name,
+        builtin: true,
+        error: None,
+    }];
+    for (name, skin) in discover() {
+        // `available` skips a pack whose id an earlier one has, as if broken
+        let skin = skin.and_then(|skin| {
+            match entries.iter().find(|e| e.error.is_none() && e.id == skin.manifest.id) {
+                Some(taken) => Err(format!("id {:?} is already used by {}", skin.manifest.id, taken.name)),
+                None => Ok(skin),
+            }
+        });
+        entries.push(match skin {
+            Ok(skin) => SkinEntry {
+                active: skin.manifest.id == active,
+                id: skin.manifest.id,
+                name: skin.manifest.name,
+                builtin: false,
+                error: None,
+            },
+            Err(e) => SkinEntry { id: name.clone(), name, builtin: false, active: false, error: Some(e) },
+        });
+    }
+    entries
+}
+
+/// Scan the skins dir again for packs added, changed or removed since
+pub fn refresh() -> Vec<SkinEntry> {
+    *PACKS.lock().unwrap() = None;
+    list()
+}
+
+/// The skin chosen in the settings, falling back to the built-in one
+pub fn active() -> Skin {
+    let id = crate::settings::current().skin;
+    available().into_iter().find(|s| s.manifest.id == id).unwrap_or_else(|| {
+        if id != DEFAULT_SKIN_ID {
+            log::warn!("Skin {:?} not found, using {}", id, DEFAULT_SKIN_ID);
+        }
+        builtin()
+    })
+}
+
+/// Switch to another skin; an open overlay is rebuilt with it
+pub fn set_active(id: &str) -> Result<Vec<SkinEntry>, String> {
+    if !available().iter().any(|s| s.manifest.id == id) {
+        return Err(format!("No usable skin with id {}", id));
+    }
+    crate::settings::update(|settings| settings.skin = id.to_string())?;
+    let entries = list();
+    crate::events::emit("skins-changed", entries.clone());
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir;
+
+    const TABBY: &str = r#"{
+        "id": "tabby",
+        "name": "Tabby",
+        "frameSize": { "width": 64, "height": 64 },
+        "start": "idle",
+        "clips": [{ "name": "idle", "frames": "idle.gif", "mode": "loop" }]
+    }"#;
+
+    fn tabby() -> SkinManifest {
+        serde_json::from_str(TABBY).unwrap()
+    }
+
+    #[test]
+    fn valid_manifests() {
+        tabby().validate().unwrap();
+        builtin().manifest.validate().unwrap();
+    }
+
+    #[test]
+    fn anchor_must_be_in_the_frame() {
+        let mut manifest = tabby();
+        manifest.anchor = Some(Anchor { x: 65.0, y: 64.0 });
+        assert_eq!(manifest.validate().unwrap_err(), "anchor (65, 64) is outside the 64x64 frame");
+    }
+
+    #[test]
+    fn hit_box_must_be_in_the_frame() {
+        let mut manifest = tabby();
+        for hit_box in [
+            HitBox { x: 32.0, y: 0.0, width: 40.0, height: 64.0 },
+            HitBox { x: -1.0, y: 0.0, width: 10.0, height: 10.0 },
+            HitBox { x: 0.0, y: 0.0, width: 0.0, height: 10.0 },
+        ] {
+            manifest.hit_box = Some(hit_box);
+            assert_eq!(
+                manifest.validate().unwrap_err(),
+                "hitBox must be a non-empty area inside the 64x64 frame",
+                "{:?}",
+                hit_box
+            );
+        }
+    }
+
+    #[test]
+    fn animation_errors_are_reported() {
+        let mut manifest = tabby();
+        manifest.animations.start = "nap".to_string();
+        assert_eq!(manifest.validate().unwrap_err(), "Unknown clip \"nap\" in start");
+    }
+
+    /// A pack in `folder` of `skins_dir` with `manifest` and its one clip
+    fn write_pack(skins_dir: &Path, folder: &str, manifest: &str) {
+        let root = skins_dir.join(folder);
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join(SKIN_FILE_NAME), manifest).unwrap();
+        std::fs::write(root.join("idle.gif"), b"").unwrap();
+    }
+
+    /// Forget the skins dir and its packs again
+    fn init_none() {
+        *SKINS_DIR.lock().unwrap() = None;
+        *PACKS.lock().unwrap() = None;
+    }
+
+    /// A zip of `files` as stored, uncompressed, so their bytes can be found
+    fn zip_of(files: &[(&str, &[u8])]) -> Vec<u8> {
+        use std::io::Write;
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        for (name, contents) in files {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn zipped_packs_are_unpacked() {
+        let dir = temp_dir("skins-unpack");
+        let zip = dir.join("tabby.zip");
+        std::fs::write(&zip, zip_of(&[("tabby/skin.json", TABBY.as_bytes()), ("tabby/idle.gif", b"GIF89a")])).unwrap();
+
+        let root = unpack(&zip, &dir).unwrap();
+        assert_eq!(root, dir.join(UNPACKED_DIR_NAME).join("tabby").join("tabby"));
+        assert_eq!(load(root).unwrap().manifest.id, "tabby");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_failed_unpack_leaves_nothing_behind() {
+        let dir = temp_dir("skins-unpack-failed");
+        let zip = dir.join("tabby.zip");
+        let mut bytes = zip_of(&[("skin.json", TABBY.as_bytes()), ("idle.gif", b"GIF89a not really")]);
+        // Break the second file's checksum, so it fails after the first is out
+        let at = bytes.windows(6).position(|w| w == b"really").unwrap();
+        bytes[at] = b'R';
+        std::fs::write(&zip, bytes).unwrap();
+
+        assert!(unpack(&zip, &dir).is_err());
+        let unpacked: Vec<_> = std::fs::read_dir(dir.join(UNPACKED_DIR_NAME)).unwrap().flatten().collect();
+        assert!(unpacked.is_empty(), "left behind: {:?}", unpacked);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unpacking_refuses_links_and_oversized_zips() {
+        let dir = temp_dir("skins-unpack-refused");
+        let options = zip::write::SimpleFileOptions::default();
+        let write_zip = |name: &str, add: &dyn Fn(&mut zip::ZipWriter<std::fs::File>)| {
+            let path = dir.join(name);
+            let mut writer = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+            writer.start_file("skin.json", options).unwrap();
+            std::io::Write::write_all(&mut writer, TABBY.as_bytes()).unwrap();
+            add(&mut writer);
+            writer.finish().unwrap();
+            path
+        };
+
+        let link = write_zip("link.zip", &|writer| writer.add_symlink("idle.gif", "/etc/passwd", options).unwrap());
+        let many = write_zip("many.zip", &|writer| {
+            for i in 0..MAX_UNPACKED_ENTRIES {
+                writer.start_file(format!("{}.gif", i), options).unwrap();
+            }
+        });
+        // Zeros compress well, so this is a small zip that unpacks past the cap
+        let big = write_zip("big.zip", &|writer| {
+            writer.start_file("idle.gif", options).unwrap();
+            let mut zeros = std::io::repeat(0).take(MAX_UNPACKED_BYTES);
+            std::io::copy(&mut zeros, writer).unwrap();
+        });
+
+        for (zip, error) in [
+            (link, "idle.gif is a link"),
+            (many, "more than 1024 entries"),
+            (big, "more than 64 MB unpacked"),
+        ] {
+            let e = unpack(&zip, &dir).unwrap_err();
+            assert!(e.ends_with(error), "{:?}: {}", zip, e);
+        }
+        let unpacked: Vec<_> = std::fs::read_dir(dir.join(UNPACKED_DIR_NAME)).unwrap().flatten().collect();
+        assert!(unpacked.is_empty(), "left behind: {:?}", unpacked);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn packs_are_rescanned_only_on_refresh() {
+        let _globals = crate::lock_globals();
+        let dir = temp_dir("skins-refresh");
+        write_pack(&dir, "tabby", TABBY);
+        init(dir.clone());
+        let ids = |entries: Vec<SkinEntry>| entries.into_iter().map(|e| e.id).collect::<Vec<_>>();
+        assert_eq!(ids(list()), ["mittens", "tabby"]);
+
+        write_pack(&dir, "calico", &TABBY.replace("tabby", "calico"));
+        std::fs::remove_dir_all(dir.join("tabby")).unwrap();
+        assert_eq!(ids(list()), ["mittens", "tabby"]);
+        assert!(set_active("calico").is_err());
+
+        assert_eq!(ids(refresh()), ["mittens", "calico"]);
+        assert_eq!(ids(list()), ["mittens", "calico"]);
+        init_none();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn packs_with_a_taken_id_are_listed_as_broken() {
+        let _globals = crate::lock_globals();
+        let dir = temp_dir("skins-taken-id");
+        write_pack(&dir, "a-tabby", TABBY);
+        write_pack(&dir, "b-tabby-again", TABBY);
+        write_pack(&dir, "c-mittens", &TABBY.replace("\"tabby\"", "\"mittens\""));
+        init(dir.clone());
+        let entries = list();
+        init_none();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let summary: Vec<_> = entries.iter().map(|e| (e.id.as_str(), e.active, e.error.as_deref())).collect();
+        assert_eq!(
+            summary,
+            [
+                ("mittens", true, None),
+                ("tabby", false, None),
+                ("b-tabby-again", false, Some("id \"tabby\" is already used by Tabby")),
+                // Not the active skin either, even though the settings name its id
+                ("c-mittens", false, Some("id \"mittens\" is already used by Mittens")),
+            ]
+        );
+    }
+}
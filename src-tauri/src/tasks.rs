@@ -0,0 +1,58 @@
+//! Tasks module - Background work on Tauri's shared async runtime
+//! Chat requests and the overlay's loops (animation, screen monitor, typing
+//! effect) plus the settings watcher run as tasks here instead of on their
+//! own OS threads and runtimes, and are all aborted when the app exits
+
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tauri::async_runtime::JoinHandle;
+
+// Every task spawned and not yet known to be finished
+static TASKS: Mutex<Vec<(&'static str, JoinHandle<()>)>> = Mutex::new(Vec::new());
+
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// Run `task` on the shared runtime, tracked so `shutdown` can stop it
+pub fn spawn<F>(name: &'static str, task: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    if SHUTTING_DOWN.load(Ordering::SeqCst) {
+        log::warn!("Not starting task {} during shutdown", name);
+        return;
+    }
+
+    let handle = tauri::async_runtime::spawn(task);
+    let mut tasks = TASKS.lock().unwrap();
+    // Forget finished tasks so rapid questions don't grow the list forever
+    tasks.retain(|(_, handle)| !handle.inner().is_finished());
+    tasks.push((name, handle));
+}
+
+/// How many tasks spawned as `name` are still running
+#[cfg(test)]
+pub fn running(name: &str) -> usize {
+    let tasks = TASKS.lock().unwrap();
+    tasks.iter().filter(|(task, handle)| *task == name && !handle.inner().is_finished()).count()
+}
+
+/// Sleep without blocking a runtime worker thread
+pub async fn sleep_ms(ms: u64) {
+    tokio::time::sleep(std::time::Duration::from_millis(ms)).await;
+}
+
+/// Abort every running task; called once when the app exits
+pub fn shutdown() {
+    SHUTTING_DOWN.store(true, Ordering::SeqCst);
+    let tasks = std::mem::take(&mut *TASKS.lock().unwrap());
+    let running: Vec<_> = tasks
+        .into_iter()
+        .filter(|(_, handle)| !handle.inner().is_finished())
+        .collect();
+    log::info!("Stopping {} background tasks", running.len());
+    for (name, handle) in running {
+        log::info!("Aborting task {}", name);
+        handle.abort();
+    }
+}
@@ -1,10 +1,43 @@
-import { useState } from 'react'
+import { useEffect, useState } from 'react'
 import { invoke } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
 import 'katex/dist/katex.min.css'
 import { InlineMath, BlockMath } from 'react-katex'
 import './App.css'
 
-const GROQ_API_KEY = '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'
+type Persona = {
+  id: string
+  name: string
+  builtin: boolean
+}
+
+// Mirrors `ai::AiError` on the backend
+type AiError = {
+  kind:
+    | 'noApiKey'
+    | 'network'
+    | 'timeout'
+    | 'unauthorized'
+    | 'rateLimited'
+    | 'quotaExceeded'
+    | 'malformedResponse'
+    | 'emptyResponse'
+    | 'provider'
+    | 'cancelled'
+  message: string
+  detail: string
+  status?: number
+  retryAfterSecs?: number
+}
+
+const describeError = (error: unknown) =>
+  typeof error === 'object' && error !== null && 'message' in error ? (error as AiError).message : `${error}`
+
+type ApiKeyStatus = {
+  configured: boolean
+  source: 'environment' | 'keyring' | 'encryptedFile' | 'none'
+  required: boolean
+}
 
 // Render text with LaTeX support
 const renderWithLatex = (text: string) => {
@@ -30,35 +63,111 @@ function App() {
   const [question, setQuestion] = useState('')
   const [response, setResponse] = useState('')
   const [loading, setLoading] = useState(false)
+  const [keyStatus, setKeyStatus] = useState<ApiKeyStatus | null>(null)
+  const [keyInput, setKeyInput] = useState('')
+  const [keyError, setKeyError] = useState('')
+  const [personas, setPersonas] = useState<Persona[]>([])
+  const [personaId, setPersonaId] = useState('')
+  const [greeting, setGreeting] = useState('Ask anything...')
+
+  useEffect(() => {
+    invoke<Persona[]>('list_personas').then(setPersonas)
+    invoke<{ personaId: string }>('get_conversation').then((info) => setPersonaId(info.personaId))
+    invoke<string>('get_persona_greeting').then(setGreeting)
+    const unlisten = listen<Persona[]>('personas-changed', (event) => setPersonas(event.payload))
+    return () => {
+      unlisten.then((fn) => fn())
+    }
+  }, [])
+
+  // The key lives in the backend; the UI only ever sees whether one is set
+  useEffect(() => {
+    invoke<ApiKeyStatus>('get_api_key_status').then(setKeyStatus)
+    const unlisten = listen<ApiKeyStatus>('api-key-status', (event) => setKeyStatus(event.payload))
+    return () => {
+      unlisten.then((fn) => fn())
+    }
+  }, [])
+
+  // Stream tokens from the backend into the response as they arrive
+  useEffect(() => {
+    const unlisten = listen<{ source: string; token: string }>('chat-token', (event) => {
+      if (event.payload.source === 'main') {
+        setResponse((prev) => prev + event.payload.token)
+      }
+    })
+    return () => {
+      unlisten.then((fn) => fn())
+    }
+  }, [])
 
   const askAI = async () => {
     if (!question.trim()) return
     setLoading(true)
     setResponse('')
     try {
-      const result = await invoke<string>('ask_ai', {
-        question: question,
-        apiKey: GROQ_API_KEY
-      })
+      const result = await invoke<string>('ask_ai', { question: question })
       setResponse(result)
     } catch (error) {
-      setResponse(`Error: ${error}`)
+      const aiError = error as AiError
+      if (aiError.kind === 'cancelled') {
+        // Keep whatever streamed in before the user stopped it
+      } else if (aiError.kind === 'noApiKey' || aiError.kind === 'unauthorized') {
+        // Point the user at the key card instead of the chat
+        setKeyError(aiError.message)
+        setResponse('')
+      } else {
+        setResponse(describeError(error))
+      }
     }
     setLoading(false)
   }
 
+  const stopAI = async () => {
+    await invoke('cancel_chat')
+  }
+
   const toggleOverlay = async () => {
     if (overlayVisible) {
       await invoke('hide_overlay_window')
       setOverlayVisible(false)
     } else {
-      await invoke('create_overlay', { width: 320.0, height: 500.0 })
-      // Set the Groq API key for the overlay chat
-      await invoke('set_groq_api_key', { key: GROQ_API_KEY })
+      await invoke('create_overlay')
       setOverlayVisible(true)
     }
   }
 
+  const saveApiKey = async () => {
+    if (!keyInput.trim()) return
+    setKeyError('')
+    try {
+      setKeyStatus(await invoke<ApiKeyStatus>('set_api_key', { key: keyInput }))
+      setKeyInput('')
+      await invoke('validate_api_key')
+    } catch (error) {
+      setKeyError(describeError(error))
+    }
+  }
+
+  const clearApiKey = async () => {
+    setKeyError('')
+    try {
+      setKeyStatus(await invoke<ApiKeyStatus>('clear_api_key'))
+    } catch (error) {
+      setKeyError(`${error}`)
+    }
+  }
+
+  const choosePersona = async (id: string) => {
+    try {
+      await invoke('set_conversation_persona', { id })
+      setPersonaId(id)
+      setGreeting(await invoke<string>('get_persona_greeting'))
+    } catch (error) {
+      setResponse(`Error: ${error}`)
+    }
+  }
+
   const closeOverlay = async () => {
     await invoke('close_overlay_window')
     setOverlayVisible(false)
@@ -95,25 +204,73 @@ function App() {
           )}
         </div>
 
+        {keyStatus?.required && (
+          <div className="card gemini-card">
+            <h3>API Key</h3>
+            {keyStatus.configured ? (
+              <div className="status">
+                <div className="status-indicator active" />
+                <span>Key stored ({keyStatus.source})</span>
+                {keyStatus.source !== 'environment' && (
+                  <button className="secondary-btn" onClick={clearApiKey}>
+                    Remove
+                  </button>
+                )}
+              </div>
+            ) : (
+              <div className="gemini-input-row">
+                <input
+                  type="password"
+                  value={keyInput}
+                  onChange={(e) => setKeyInput(e.target.value)}
+                  onKeyDown={(e) => e.key === 'Enter' && saveApiKey()}
+                  placeholder="Paste your API key..."
+                  className="gemini-input"
+                />
+                <button className="primary-btn gemini-btn" onClick={saveApiKey} disabled={!keyInput.trim()}>
+                  Save
+                </button>
+              </div>
+            )}
+            {keyError && <div className="gemini-response">{keyError}</div>}
+          </div>
+        )}
+
         <div className="card gemini-card">
-          <h3>Chat with Mittens</h3>
+          <h3>Chat with {personas.find((p) => p.id === personaId)?.name ?? 'Mittens'}</h3>
+          {personas.length > 1 && (
+            <select
+              value={personaId}
+              onChange={(e) => choosePersona(e.target.value)}
+              className="gemini-input"
+              disabled={loading}
+            >
+              {personas.map((p) => (
+                <option key={p.id} value={p.id}>
+                  {p.name}
+                </option>
+              ))}
+            </select>
+          )}
           <div className="gemini-input-row">
             <input
               type="text"
               value={question}
               onChange={(e) => setQuestion(e.target.value)}
               onKeyDown={(e) => e.key === 'Enter' && askAI()}
-              placeholder="Ask anything..."
+              placeholder={greeting}
               className="gemini-input"
               disabled={loading}
             />
-            <button
-              className="primary-btn gemini-btn"
-              onClick={askAI}
-              disabled={loading || !question.trim()}
-            >
-              {loading ? '...' : 'Ask'}
-            </button>
+            {loading ? (
+              <button className="secondary-btn gemini-btn" onClick={stopAI}>
+                Stop
+              </button>
+            ) : (
+              <button className="primary-btn gemini-btn" onClick={askAI} disabled={!question.trim()}>
+                Ask
+              </button>
+            )}
           </div>
           {response && (
             <div className="gemini-response">